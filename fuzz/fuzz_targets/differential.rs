@@ -0,0 +1,10 @@
+// CPU and the reference interpreter must agree after every tick
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../tests/reference/mod.rs"]
+#[allow(dead_code)] // each target uses half of it
+mod reference;
+
+fuzz_target!(|data: &[u8]| reference::run_differential(data));
@@ -0,0 +1,10 @@
+// Random ROMs and keypad sequences must never make the CPU panic
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../tests/reference/mod.rs"]
+#[allow(dead_code)] // each target uses half of it
+mod reference;
+
+fuzz_target!(|data: &[u8]| reference::run(data));
@@ -0,0 +1,72 @@
+#ifndef YARC_H
+#define YARC_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define YARC_WIDTH 64
+
+#define YARC_HEIGHT 32
+
+#define YARC_RAM_SIZE 4096
+
+#define YARC_STATE_SIZE 6214
+
+#define YARC_VRAM_CHANGED 1
+
+#define YARC_BEEP 2
+
+#define YARC_OK 0
+
+#define YARC_ERROR -1
+
+typedef struct yarc_cpu yarc_cpu;
+
+typedef struct yarc_registers {
+  uint8_t v[16];
+  uint16_t i;
+  uint16_t pc;
+  uint8_t sp;
+  uint8_t delay_timer;
+  uint8_t sound_timer;
+  uint16_t stack[16];
+} yarc_registers;
+
+struct yarc_cpu *yarc_cpu_create(uint64_t seed);
+
+void yarc_cpu_destroy(struct yarc_cpu *cpu);
+
+int32_t yarc_cpu_load_rom(struct yarc_cpu *cpu, const uint8_t *rom, size_t len);
+
+int32_t yarc_cpu_step(struct yarc_cpu *cpu);
+
+int32_t yarc_cpu_run_frame(struct yarc_cpu *cpu, uint32_t ticks);
+
+int32_t yarc_cpu_set_keypad(struct yarc_cpu *cpu, uint16_t keys);
+
+int32_t yarc_cpu_framebuffer(const struct yarc_cpu *cpu, uint8_t *out, size_t len);
+
+int32_t yarc_cpu_read_memory(const struct yarc_cpu *cpu,
+                             uint16_t address,
+                             uint8_t *out,
+                             size_t len);
+
+int32_t yarc_cpu_write_memory(struct yarc_cpu *cpu,
+                              uint16_t address,
+                              const uint8_t *data,
+                              size_t len);
+
+int32_t yarc_cpu_get_registers(const struct yarc_cpu *cpu, struct yarc_registers *out);
+
+int32_t yarc_cpu_set_registers(struct yarc_cpu *cpu, const struct yarc_registers *registers);
+
+int32_t yarc_cpu_save_state(const struct yarc_cpu *cpu, uint8_t *out, size_t len);
+
+int32_t yarc_cpu_load_state(struct yarc_cpu *cpu, const uint8_t *data, size_t len);
+
+#endif /* YARC_H */
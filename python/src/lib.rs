@@ -0,0 +1,212 @@
+// Python bindings: the CHIP-8 core as a Gym-style environment, for scripts and agents.
+// Each Env is just a CPU, so thousands of them fit on a core with no SDL or global state.
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+
+use pyo3::exceptions::{PyBufferError, PyValueError};
+use pyo3::ffi;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use yarc::cpu::CPU;
+use yarc::{CHIP8_HEIGHT, CHIP8_RAM, CHIP8_WIDTH};
+
+// Roughly what the desktop front-ends run per 60 Hz frame
+const DEFAULT_TICKS_PER_FRAME: u32 = 16;
+
+// A copy of the screen, one byte per pixel (0 or 1). Supports the buffer protocol with
+// shape (HEIGHT, WIDTH), so numpy.asarray(frame) needs no copy.
+#[pyclass(module = "yarc", frozen)]
+struct Frame {
+    pixels: [u8; CHIP8_WIDTH * CHIP8_HEIGHT],
+    shape: [ffi::Py_ssize_t; 2],
+    strides: [ffi::Py_ssize_t; 2],
+}
+
+impl Frame {
+    fn new(cpu: &CPU) -> Self {
+        let mut pixels = [0; CHIP8_WIDTH * CHIP8_HEIGHT];
+        pixels.copy_from_slice(cpu.vram().pixels());
+        Frame {
+            pixels,
+            shape: [CHIP8_HEIGHT as ffi::Py_ssize_t, CHIP8_WIDTH as ffi::Py_ssize_t],
+            strides: [CHIP8_WIDTH as ffi::Py_ssize_t, 1],
+        }
+    }
+}
+
+#[pymethods]
+impl Frame {
+    fn tobytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.pixels)
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> PyResult<u8> {
+        if x >= CHIP8_WIDTH || y >= CHIP8_HEIGHT {
+            return Err(PyValueError::new_err(format!("({}, {}) is off screen", x, y)));
+        }
+        Ok(self.pixels[y * CHIP8_WIDTH + x])
+    }
+
+    fn __len__(&self) -> usize {
+        self.pixels.len()
+    }
+
+    unsafe fn __getbuffer__(slf: Bound<'_, Self>, view: *mut ffi::Py_buffer, flags: c_int) -> PyResult<()> {
+        if view.is_null() {
+            return Err(PyBufferError::new_err("No buffer view to fill"));
+        }
+        if flags & ffi::PyBUF_WRITABLE == ffi::PyBUF_WRITABLE {
+            return Err(PyBufferError::new_err("Frames are read-only"));
+        }
+        let frame = slf.get();
+        (*view).buf = frame.pixels.as_ptr() as *mut c_void;
+        (*view).len = frame.pixels.len() as ffi::Py_ssize_t;
+        (*view).readonly = 1;
+        (*view).itemsize = 1;
+        (*view).format = if flags & ffi::PyBUF_FORMAT == ffi::PyBUF_FORMAT {
+            b"B\0".as_ptr() as *mut c_char
+        } else {
+            ptr::null_mut()
+        };
+        // Without PyBUF_ND the consumer wants a flat run of bytes
+        if flags & ffi::PyBUF_ND == ffi::PyBUF_ND {
+            (*view).ndim = 2;
+            (*view).shape = frame.shape.as_ptr() as *mut ffi::Py_ssize_t;
+        } else {
+            (*view).ndim = 1;
+            (*view).shape = ptr::null_mut();
+        }
+        (*view).strides = if flags & ffi::PyBUF_STRIDES == ffi::PyBUF_STRIDES {
+            frame.strides.as_ptr() as *mut ffi::Py_ssize_t
+        } else {
+            ptr::null_mut()
+        };
+        (*view).suboffsets = ptr::null_mut();
+        (*view).internal = ptr::null_mut();
+        // The view keeps the frame alive
+        (*view).obj = slf.into_any().into_ptr();
+        Ok(())
+    }
+
+    unsafe fn __releasebuffer__(&self, _view: *mut ffi::Py_buffer) {}
+}
+
+// step(keys) runs frame_skip frames of ticks_per_frame instructions with the keys held
+#[pyclass(module = "yarc")]
+struct Env {
+    cpu: CPU,
+    rom: Vec<u8>,
+    seed: u64,
+    frame_skip: u32,
+    ticks_per_frame: u32,
+    beeping: bool,
+}
+
+impl Env {
+    // A seed of 0 picks a random one; anything else replays the same random numbers
+    fn boot(&mut self) {
+        self.cpu = if self.seed == 0 { CPU::new() } else { CPU::with_seed(self.seed) };
+        self.cpu.load_program(&self.rom);
+        self.beeping = false;
+    }
+}
+
+#[pymethods]
+impl Env {
+    #[new]
+    #[pyo3(signature = (rom, frame_skip = 1, seed = 0, ticks_per_frame = DEFAULT_TICKS_PER_FRAME))]
+    fn new(rom: Vec<u8>, frame_skip: u32, seed: u64, ticks_per_frame: u32) -> PyResult<Self> {
+        if rom.len() > CHIP8_RAM - 0x200 {
+            return Err(PyValueError::new_err(format!("ROM is {} bytes, the most that fits is {}", rom.len(), CHIP8_RAM - 0x200)));
+        }
+        if frame_skip == 0 || ticks_per_frame == 0 {
+            return Err(PyValueError::new_err("frame_skip and ticks_per_frame must be at least 1"));
+        }
+        let mut env = Env { cpu: CPU::with_seed(seed), rom, seed, frame_skip, ticks_per_frame, beeping: false };
+        env.boot();
+        Ok(env)
+    }
+
+    // Restarts the game, optionally with a new seed
+    #[pyo3(signature = (seed = None))]
+    fn reset(&mut self, seed: Option<u64>) -> Frame {
+        if let Some(seed) = seed {
+            self.seed = seed;
+        }
+        self.boot();
+        Frame::new(&self.cpu)
+    }
+
+    // Bit n of `keys` set means key n of the keypad is held. Returns the screen afterwards.
+    fn step(&mut self, py: Python<'_>, keys: u16) -> Frame {
+        let mut keypad = [false; 16];
+        for (n, key) in keypad.iter_mut().enumerate() {
+            *key = keys & (1 << n) != 0;
+        }
+        // Other Python threads can step their own Envs meanwhile
+        py.allow_threads(|| {
+            let mut beeping = false;
+            for _ in 0..self.frame_skip * self.ticks_per_frame {
+                beeping |= self.cpu.tick(keypad).beep;
+            }
+            self.beeping = beeping;
+        });
+        Frame::new(&self.cpu)
+    }
+
+    fn frame(&self) -> Frame {
+        Frame::new(&self.cpu)
+    }
+
+    // One byte of RAM, e.g. a score for a reward function
+    fn peek(&self, address: usize) -> PyResult<u8> {
+        match self.cpu.ram().get(address) {
+            Some(&byte) => Ok(byte),
+            None => Err(PyValueError::new_err(format!("Address {:#x} is past the end of RAM", address))),
+        }
+    }
+
+    fn ram<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.cpu.ram())
+    }
+
+    fn save_state<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.cpu.save_state())
+    }
+
+    fn restore_state(&mut self, state: &[u8]) -> PyResult<()> {
+        self.cpu.load_state(state).map_err(PyValueError::new_err)
+    }
+
+    // Whether the sound timer ran during the last step
+    #[getter]
+    fn beeping(&self) -> bool {
+        self.beeping
+    }
+
+    #[getter]
+    fn frame_skip(&self) -> u32 {
+        self.frame_skip
+    }
+
+    #[setter]
+    fn set_frame_skip(&mut self, frame_skip: u32) -> PyResult<()> {
+        if frame_skip == 0 {
+            return Err(PyValueError::new_err("frame_skip must be at least 1"));
+        }
+        self.frame_skip = frame_skip;
+        Ok(())
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "yarc")]
+fn yarc_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Env>()?;
+    module.add_class::<Frame>()?;
+    module.add("WIDTH", CHIP8_WIDTH)?;
+    module.add("HEIGHT", CHIP8_HEIGHT)?;
+    module.add("RAM_SIZE", CHIP8_RAM)?;
+    Ok(())
+}
@@ -0,0 +1,47 @@
+// Runs test_yarc.py against the built module. Skipped without python3.
+#![cfg(unix)]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn python_module() {
+    // The test binary lives in target/<profile>/deps, next to the library's directory
+    let exe = env::current_exe().unwrap();
+    let lib_dir = exe.parent().and_then(|deps| deps.parent()).unwrap().to_path_buf();
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    // Integration tests don't depend on a cdylib, so build it first
+    let mut build = Command::new(env!("CARGO"));
+    build.arg("build").arg("--lib").arg("--manifest-path").arg(root.join("Cargo.toml"));
+    if lib_dir.ends_with("release") {
+        build.arg("--release");
+    }
+    assert!(build.status().unwrap().success(), "building the Python module failed");
+
+    let library = ["libyarc_python.so", "libyarc_python.dylib"]
+        .iter()
+        .map(|name| lib_dir.join(name))
+        .find(|path| path.exists())
+        .expect("yarc_python library not built");
+
+    // Python imports the module by file name
+    let module_dir = lib_dir.join("python");
+    fs::create_dir_all(&module_dir).unwrap();
+    fs::copy(&library, module_dir.join("yarc.so")).unwrap();
+
+    let python = env::var("PYTHON").unwrap_or_else(|_| "python3".to_string());
+    let script = root.join("tests/test_yarc.py");
+    let output = match Command::new(&python).arg(&script).env("PYTHONPATH", &module_dir).output() {
+        Ok(output) => output,
+        Err(_) => {
+            eprintln!("No Python ({}), skipping", python);
+            return;
+        }
+    };
+    print!("{}", String::from_utf8_lossy(&output.stdout));
+    eprint!("{}", String::from_utf8_lossy(&output.stderr));
+    assert!(output.status.success());
+}
@@ -0,0 +1,77 @@
+# Run by python/tests/python.rs with the built module on the path, or by hand after maturin develop
+import unittest
+
+import yarc
+
+# Draws the font sprite for 5 at (5, 5), then loops forever
+ROM = bytes([0x60, 0x05, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06])
+# Puts a random byte in V1 and stores it at 0x300, over and over
+RANDOM_ROM = bytes([0xC1, 0xFF, 0xA3, 0x00, 0xF1, 0x55, 0x12, 0x00])
+
+
+class EnvTest(unittest.TestCase):
+    def test_step_draws(self):
+        env = yarc.Env(ROM)
+        frame = env.reset()
+        self.assertEqual(frame.pixel(5, 5), 0)
+        frame = env.step(0)
+        self.assertEqual(frame.pixel(5, 5), 1)
+        self.assertEqual(len(frame), yarc.WIDTH * yarc.HEIGHT)
+
+    def test_frame_buffer(self):
+        env = yarc.Env(ROM)
+        view = memoryview(env.step(0))
+        self.assertEqual(view.shape, (yarc.HEIGHT, yarc.WIDTH))
+        self.assertTrue(view.readonly)
+        self.assertEqual(view[5, 5], 1)
+        self.assertEqual(view.tobytes(), env.frame().tobytes())
+
+    def test_numpy(self):
+        try:
+            import numpy
+        except ImportError:
+            self.skipTest("numpy is not installed")
+        pixels = numpy.asarray(yarc.Env(ROM).step(0))
+        self.assertEqual(pixels.shape, (yarc.HEIGHT, yarc.WIDTH))
+        self.assertEqual(pixels[5, 5], 1)
+
+    def test_peek(self):
+        env = yarc.Env(ROM)
+        self.assertEqual(env.peek(0x200), 0x60)
+        self.assertEqual(len(env.ram()), yarc.RAM_SIZE)
+        with self.assertRaises(ValueError):
+            env.peek(yarc.RAM_SIZE)
+
+    def test_seed_replays(self):
+        first = yarc.Env(RANDOM_ROM, seed=3)
+        second = yarc.Env(RANDOM_ROM, seed=3)
+        for _ in range(5):
+            first.step(0)
+            second.step(0)
+            self.assertEqual(first.peek(0x300), second.peek(0x300))
+
+    def test_save_and_restore(self):
+        env = yarc.Env(RANDOM_ROM, seed=9)
+        env.step(0)
+        state = env.save_state()
+        after = env.step(0).tobytes(), env.peek(0x300)
+        env.restore_state(state)
+        self.assertEqual((env.step(0).tobytes(), env.peek(0x300)), after)
+        with self.assertRaises(ValueError):
+            env.restore_state(b"nope")
+
+    def test_frame_skip(self):
+        env = yarc.Env(ROM, frame_skip=4, ticks_per_frame=1)
+        self.assertEqual(env.frame_skip, 4)
+        # 4 ticks are enough to draw
+        self.assertEqual(env.step(0).pixel(5, 5), 1)
+        with self.assertRaises(ValueError):
+            env.frame_skip = 0
+
+    def test_bad_rom(self):
+        with self.assertRaises(ValueError):
+            yarc.Env(bytes(4000))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -1,5 +1,9 @@
 // Ripped off from github
 use super::*;
+use crate::font::{FontSet, BIG_FONT_SET, FONT_SET};
+use crate::quirks::{QuirkPreset, Quirks};
+use crate::timing::{vip_cycles, Timing, FRAME_CYCLES};
+use proptest::prelude::*;
 const OPCODE_SIZE: u16 = 2;
 const START_PC: u16 = 0xF00;
 const NEXT_PC: u16 = START_PC + OPCODE_SIZE;
@@ -41,14 +45,12 @@ fn test_load_data() {
 #[test]
 fn test_op_00e0() {
     let mut cpu = build_cpu();
-    cpu.vram = [[128; CHIP8_WIDTH]; CHIP8_HEIGHT];
+    cpu.vram.load(&[1; CHIP8_WIDTH * CHIP8_HEIGHT]);
+    cpu.vram.mark_clean();
     cpu.run_opcode(0x00e0);
 
-    for y in 0..CHIP8_HEIGHT {
-        for x in 0..CHIP8_WIDTH {
-            assert_eq!(cpu.vram[y][x], 0);
-        }
-    }
+    assert!(cpu.vram.pixels().iter().all(|&pixel| pixel == 0));
+    assert!(cpu.vram.dirty().is_some());
     assert_eq!(cpu.pc, NEXT_PC);
 }
 // RET
@@ -56,10 +58,17 @@ fn test_op_00e0() {
 fn test_op_00ee() {
     let mut cpu = CPU::new();
     cpu.sp = 5;
-    cpu.stack[4] = 0x6666;
+    cpu.stack[4] = 0x0666;
     cpu.run_opcode(0x00ee);
     assert_eq!(cpu.sp, 4);
-    assert_eq!(cpu.pc, 0x6666);
+    assert_eq!(cpu.pc, 0x0666);
+
+    // Return addresses past the end of RAM wrap round
+    let mut cpu = CPU::new();
+    cpu.sp = 1;
+    cpu.stack[0] = 0x6666;
+    cpu.run_opcode(0x00ee);
+    assert_eq!(cpu.pc, 0x0666);
 }
 // JP
 #[test]
@@ -170,6 +179,8 @@ fn test_op_8xy4() {
 fn test_op_8xy5() {
     check_math(0x0F, 0x01, 5, 0x0E, 1);
     check_math(0x0F, 0xFF, 5, 0x10, 0);
+    // Equal values don't borrow
+    check_math(0x0F, 0x0F, 5, 0x00, 1);
 }
 // SHR Vx
 #[test]
@@ -184,6 +195,7 @@ fn test_op_8xy6() {
 fn test_op_8xy7() {
     check_math(0x01, 0x0F, 7, 0x0E, 1);
     check_math(0xFF, 0x0F, 7, 0x10, 0);
+    check_math(0x0F, 0x0F, 7, 0x00, 1);
 }
 
 // SHL Vx
@@ -240,19 +252,21 @@ fn test_op_dxyn() {
     cpu.i = 0;
     cpu.ram[0] = 0b11111111;
     cpu.ram[1] = 0b00000000;
-    cpu.vram[0][0] = 1;
-    cpu.vram[0][1] = 0;
-    cpu.vram[1][0] = 1;
-    cpu.vram[1][1] = 0;
+    cpu.vram.set(0, 0, 1);
+    cpu.vram.set(1, 0, 0);
+    cpu.vram.set(0, 1, 1);
+    cpu.vram.set(1, 1, 0);
+    cpu.vram.mark_clean();
     cpu.v[0] = 0;
     cpu.run_opcode(0xd002);
 
-    assert_eq!(cpu.vram[0][0], 0);
-    assert_eq!(cpu.vram[0][1], 1);
-    assert_eq!(cpu.vram[1][0], 1);
-    assert_eq!(cpu.vram[1][1], 0);
+    assert_eq!(cpu.vram.get(0, 0), 0);
+    assert_eq!(cpu.vram.get(1, 0), 1);
+    assert_eq!(cpu.vram.get(0, 1), 1);
+    assert_eq!(cpu.vram.get(1, 1), 0);
     assert_eq!(cpu.v[0x0f], 1);
-    assert!(cpu.vram_changed);
+    assert_eq!(cpu.vram.dirty().map(|region| (region.width, region.height)), Some((8, 1)));
+    assert_eq!(cpu.last_ram_read(), Some(0..2));
     assert_eq!(cpu.pc, NEXT_PC);
 }
 
@@ -269,16 +283,16 @@ fn test_op_dxyn_wrap_horizontal() {
     cpu.v[1] = 0;
     cpu.run_opcode(0xd011);
 
-    assert_eq!(cpu.vram[0][x - 1], 0);
-    assert_eq!(cpu.vram[0][x], 1);
-    assert_eq!(cpu.vram[0][x + 1], 1);
-    assert_eq!(cpu.vram[0][x + 2], 1);
-    assert_eq!(cpu.vram[0][x + 3], 1);
-    assert_eq!(cpu.vram[0][0], 1);
-    assert_eq!(cpu.vram[0][1], 1);
-    assert_eq!(cpu.vram[0][2], 1);
-    assert_eq!(cpu.vram[0][3], 1);
-    assert_eq!(cpu.vram[0][4], 0);
+    assert_eq!(cpu.vram.get(x - 1, 0), 0);
+    assert_eq!(cpu.vram.get(x, 0), 1);
+    assert_eq!(cpu.vram.get(x + 1, 0), 1);
+    assert_eq!(cpu.vram.get(x + 2, 0), 1);
+    assert_eq!(cpu.vram.get(x + 3, 0), 1);
+    assert_eq!(cpu.vram.get(0, 0), 1);
+    assert_eq!(cpu.vram.get(1, 0), 1);
+    assert_eq!(cpu.vram.get(2, 0), 1);
+    assert_eq!(cpu.vram.get(3, 0), 1);
+    assert_eq!(cpu.vram.get(4, 0), 0);
 
     assert_eq!(cpu.v[0x0f], 0);
 }
@@ -296,8 +310,8 @@ fn test_op_dxyn_wrap_vertical() {
     cpu.v[1] = y as u8;
     cpu.run_opcode(0xd012);
 
-    assert_eq!(cpu.vram[y][0], 1);
-    assert_eq!(cpu.vram[0][0], 1);
+    assert_eq!(cpu.vram.get(0, y), 1);
+    assert_eq!(cpu.vram.get(0, 0), 1);
     assert_eq!(cpu.v[0x0f], 0);
 }
 
@@ -349,26 +363,79 @@ fn test_op_fx07() {
 fn test_op_fx0a() {
     let mut cpu = build_cpu();
     cpu.run_opcode(0xf50a);
-    assert_eq!(cpu.keypad_waiting, true);
+    assert!(cpu.keypad_waiting);
     assert_eq!(cpu.keypad_register, 5);
     assert_eq!(cpu.pc, NEXT_PC);
 
     // Tick with no keypresses doesn't do anything
     cpu.tick([false; 16]);
-    assert_eq!(cpu.keypad_waiting, true);
+    assert!(cpu.keypad_waiting);
     assert_eq!(cpu.keypad_register, 5);
     assert_eq!(cpu.pc, NEXT_PC);
 
     // Tick with a keypress finishes wait and loads
     // first pressed key into vx
     cpu.tick([true; 16]);
-    assert_eq!(cpu.keypad_waiting, false);
+    assert!(!cpu.keypad_waiting);
     assert_eq!(cpu.v[5], 0);
     assert_eq!(cpu.pc, NEXT_PC);
 
 
 }
 
+// The timers keep running while Fx0A waits
+#[test]
+fn test_fx0a_timers_run() {
+    let mut cpu = build_cpu();
+    cpu.run_opcode(0xf50a);
+    cpu.delay_timer = 10;
+    cpu.sound_timer = 10;
+    for _ in 0..4 {
+        cpu.tick([false; 16]);
+    }
+    assert!(cpu.waiting_for_key());
+    assert_eq!((cpu.delay_timer, cpu.sound_timer), (6, 6));
+}
+
+#[test]
+fn test_quirk_key_release() {
+    let mut key_7 = [false; 16];
+    key_7[7] = true;
+    let mut cpu = build_cpu_with(QuirkPreset::Vip);
+    cpu.run_opcode(0xf50a);
+    // Pressing isn't enough, nor is pressing another key while it's held
+    cpu.tick(key_7);
+    cpu.tick([true; 16]);
+    assert!(cpu.waiting_for_key());
+    cpu.tick([false; 16]);
+    assert!(!cpu.waiting_for_key());
+    assert_eq!(cpu.v[5], 7);
+
+    // Without the quirk the press is taken
+    let mut cpu = build_cpu();
+    cpu.run_opcode(0xf50a);
+    cpu.tick(key_7);
+    assert!(!cpu.waiting_for_key());
+    assert_eq!(cpu.v[5], 7);
+}
+
+// A key held during Fx0A is saved too
+#[test]
+fn test_save_state_key_held() {
+    let mut key_7 = [false; 16];
+    key_7[7] = true;
+    let mut cpu = build_cpu_with(QuirkPreset::Vip);
+    cpu.run_opcode(0xf50a);
+    cpu.tick(key_7);
+    let state = cpu.save_state();
+
+    let mut restored = build_cpu_with(QuirkPreset::Vip);
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.keypad_held, Some(7));
+    restored.tick([false; 16]);
+    assert_eq!(restored.v[5], 7);
+}
+
 // LD DT, vX
 #[test]
 fn test_op_fx15() {
@@ -411,6 +478,62 @@ fn test_op_fx29() {
 
 }
 
+// LD HF, Vx
+#[test]
+fn test_op_fx30() {
+    let mut cpu = build_cpu();
+    cpu.v[5] = 9;
+    cpu.run_opcode(0xf530);
+    assert_eq!(cpu.i, 80 + 10 * 9);
+    assert_eq!(&cpu.ram[cpu.i as usize..cpu.i as usize + 10], &BIG_FONT_SET[90..100]);
+    assert_eq!(cpu.pc, NEXT_PC);
+}
+
+#[test]
+fn test_font_address() {
+    let mut font = Font::from_set(FontSet::Vip);
+    font.address = 0x50;
+    let mut cpu = build_cpu();
+    cpu.set_font(&font).unwrap();
+    assert_eq!(&cpu.ram[0x50..0xA0], FontSet::Vip.glyphs());
+    assert_eq!(&cpu.ram[0xA0..0x140], &BIG_FONT_SET);
+
+    cpu.v[5] = 0xB;
+    cpu.run_opcode(0xf529);
+    assert_eq!(cpu.i, 0x50 + 5 * 0xB);
+    assert_eq!(&cpu.ram[cpu.i as usize..cpu.i as usize + 5], &[0xF0, 0x50, 0x70, 0x50, 0xF0]);
+    cpu.run_opcode(0xf530);
+    assert_eq!(cpu.i, 0xA0 + 10 * 0xB);
+
+    // Too high, and the big digits would run into the program
+    font.address = 0x1D0;
+    assert!(cpu.set_font(&font).is_err());
+    assert_eq!(cpu.font().address, 0x50);
+}
+
+#[test]
+fn test_font_files() {
+    let small = [0xAA; 80];
+    let font = Font::from_bytes(&small).unwrap();
+    assert_eq!(font.small, small);
+    assert_eq!(font.big, BIG_FONT_SET);
+
+    let both: Vec<u8> = (0..240).map(|byte| byte as u8).collect();
+    let font = Font::from_bytes(&both).unwrap();
+    assert_eq!(&font.small[..], &both[..80]);
+    assert_eq!(&font.big[..], &both[80..]);
+
+    assert!(Font::from_bytes(&[0; 100]).is_err());
+}
+
+#[test]
+fn test_font_set_names() {
+    for set in FontSet::ALL {
+        assert_eq!(set.to_string().parse::<FontSet>(), Ok(set));
+    }
+    assert!("amiga".parse::<FontSet>().is_err());
+}
+
 // LD B, Vx
 #[test]
 fn test_op_fx33() {
@@ -421,6 +544,7 @@ fn test_op_fx33() {
     assert_eq!(cpu.ram[1000], 1);
     assert_eq!(cpu.ram[1001], 2);
     assert_eq!(cpu.ram[1002], 3);
+    assert_eq!(cpu.last_ram_write(), Some(1000..1003));
     assert_eq!(cpu.pc, NEXT_PC);
 
 }
@@ -432,8 +556,9 @@ fn test_op_fx55() {
     cpu.i = 1000;
     cpu.run_opcode(0xff55);
     for i in 0..16 {
-        assert_eq!(cpu.ram[1000 + i as usize], cpu.v[i]);
+        assert_eq!(cpu.ram[1000 + i], cpu.v[i]);
     }
+    assert_eq!(cpu.last_ram_write(), Some(1000..1016));
     assert_eq!(cpu.pc, NEXT_PC);
 }
 
@@ -441,15 +566,16 @@ fn test_op_fx55() {
 #[test]
 fn test_op_fx65() {
     let mut cpu = build_cpu();
-    for i in 0..16 as usize {
+    for i in 0..16_usize {
         cpu.ram[1000 + i] = i as u8;
     }
     cpu.i = 1000;
     cpu.run_opcode(0xff65);
 
-    for i in 0..16 as usize {
+    for i in 0..16_usize {
         assert_eq!(cpu.v[i], cpu.ram[1000 + i]);
     }
+    assert_eq!(cpu.last_ram_read(), Some(1000..1016));
     assert_eq!(cpu.pc, NEXT_PC);
 
 }
@@ -463,3 +589,554 @@ fn test_timers() {
     assert_eq!(cpu.delay_timer, 199);
     assert_eq!(cpu.sound_timer, 99);
 }
+
+// Same seed, same random numbers
+#[test]
+fn test_seeded_rng() {
+    let mut first = CPU::with_seed(42);
+    let mut second = CPU::with_seed(42);
+    for _ in 0..16 {
+        first.run_opcode(0xc0ff);
+        second.run_opcode(0xc0ff);
+        assert_eq!(first.v[0], second.v[0]);
+    }
+}
+
+#[test]
+fn test_registers_round_trip() {
+    let mut cpu = build_cpu();
+    let mut registers = cpu.registers();
+    assert_eq!(registers.pc, START_PC);
+    registers.v[0xF] = 9;
+    registers.sp = 3;
+    registers.stack[2] = 0x345;
+    cpu.set_registers(&registers);
+    assert_eq!(cpu.registers(), registers);
+}
+
+// Loading a save state brings back the machine exactly, random numbers included
+#[test]
+fn test_save_state_round_trip() {
+    let mut cpu = CPU::with_seed(7);
+    cpu.load_program(&[0x60, 0x05, 0xF0, 0x29, 0xD0, 0x05, 0xC1, 0xFF]);
+    for _ in 0..3 {
+        cpu.tick([false; 16]);
+    }
+    let state = cpu.save_state();
+    assert_eq!(state.len(), STATE_SIZE);
+
+    let mut restored = CPU::with_seed(1);
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.registers(), cpu.registers());
+    assert_eq!(restored.ram[..], cpu.ram[..]);
+    assert_eq!(restored.vram, cpu.vram);
+    cpu.tick([false; 16]);
+    restored.tick([false; 16]);
+    assert_eq!(restored.v[1], cpu.v[1]);
+}
+
+#[test]
+fn test_load_state_rejects_bad_data() {
+    let mut cpu = build_cpu();
+    let mut state = cpu.save_state();
+    assert!(cpu.load_state(&state[1..]).is_err());
+    state[0] = b'X';
+    assert!(cpu.load_state(&state).is_err());
+    // Key 16 held during Fx0A
+    let mut state = cpu.save_state();
+    state[STATE_SIZE - CHIP8_WIDTH * CHIP8_HEIGHT - CHIP8_RAM - 8 - 2] = 18;
+    assert!(cpu.load_state(&state).is_err());
+    // Untouched on error
+    assert_eq!(cpu.pc, START_PC);
+}
+
+fn build_cpu_with(preset: QuirkPreset) -> CPU {
+    let mut cpu = build_cpu();
+    cpu.set_quirks(preset.quirks());
+    cpu
+}
+
+#[test]
+fn test_quirk_vf_reset() {
+    for opcode in [0x8011, 0x8012, 0x8013] {
+        let mut cpu = build_cpu_with(QuirkPreset::Vip);
+        cpu.v[0xF] = 1;
+        cpu.run_opcode(opcode);
+        assert_eq!(cpu.v[0xF], 0);
+
+        let mut cpu = build_cpu();
+        cpu.v[0xF] = 1;
+        cpu.run_opcode(opcode);
+        assert_eq!(cpu.v[0xF], 1);
+    }
+}
+
+#[test]
+fn test_quirk_shift_vy() {
+    let mut cpu = build_cpu_with(QuirkPreset::Vip);
+    cpu.v[0] = 0xFF;
+    cpu.v[1] = 0x05;
+    cpu.run_opcode(0x8016);
+    assert_eq!((cpu.v[0], cpu.v[1], cpu.v[0xF]), (0x02, 0x05, 1));
+    cpu.v[1] = 0x81;
+    cpu.run_opcode(0x801e);
+    assert_eq!((cpu.v[0], cpu.v[0xF]), (0x02, 1));
+}
+
+#[test]
+fn test_quirk_memory_increment() {
+    let mut cpu = build_cpu_with(QuirkPreset::Vip);
+    cpu.i = 1000;
+    cpu.run_opcode(0xf255);
+    assert_eq!(cpu.i, 1003);
+    cpu.run_opcode(0xf065);
+    assert_eq!(cpu.i, 1004);
+}
+
+#[test]
+fn test_quirk_jump_vx() {
+    let mut cpu = build_cpu_with(QuirkPreset::Schip);
+    cpu.v[0] = 3;
+    cpu.v[1] = 5;
+    cpu.run_opcode(0xb123);
+    assert_eq!(cpu.pc, 0x128);
+}
+
+#[test]
+fn test_quirk_clip_sprites() {
+    let mut cpu = build_cpu_with(QuirkPreset::Vip);
+    cpu.i = 0;
+    cpu.ram[0] = 0b11111111;
+    cpu.ram[1] = 0b11111111;
+    cpu.v[0] = (CHIP8_WIDTH - 4) as u8;
+    cpu.v[1] = (CHIP8_HEIGHT - 1) as u8;
+    cpu.run_opcode(0xd012);
+    assert_eq!(cpu.vram.get(CHIP8_WIDTH - 1, CHIP8_HEIGHT - 1), 1);
+    assert_eq!(cpu.vram.get(0, CHIP8_HEIGHT - 1), 0);
+    assert_eq!(cpu.vram.get(CHIP8_WIDTH - 1, 0), 0);
+
+    // Only the start position wraps
+    let mut cpu = build_cpu_with(QuirkPreset::Vip);
+    cpu.i = 0;
+    cpu.ram[0] = 0b10000000;
+    cpu.v[0] = CHIP8_WIDTH as u8 + 1;
+    cpu.v[1] = CHIP8_HEIGHT as u8;
+    cpu.run_opcode(0xd011);
+    assert_eq!(cpu.vram.get(1, 0), 1);
+}
+
+#[test]
+fn test_quirk_presets() {
+    assert_eq!(CPU::new().quirks(), QuirkPreset::Yarc.quirks());
+    for preset in QuirkPreset::ALL {
+        assert_eq!(preset.to_string().parse(), Ok(preset));
+    }
+    assert!("chip48".parse::<QuirkPreset>().is_err());
+}
+
+#[test]
+fn test_vip_cycles() {
+    assert_eq!(vip_cycles(0x6012, 0, false), 46);
+    assert_eq!(vip_cycles(0x3012, 0, false), 50);
+    assert_eq!(vip_cycles(0x3012, 0x12, true), 54);
+    assert_eq!(vip_cycles(0xF255, 0, false), 96);
+    // Fx33 counts each digit down
+    assert_eq!(vip_cycles(0xF033, 0, false), 120);
+    assert_eq!(vip_cycles(0xF033, 123, false), 216);
+    // Dxyn is slower off a byte boundary
+    assert_eq!(vip_cycles(0xD015, 8, false), 292);
+    assert_eq!(vip_cycles(0xD015, 9, false), 342);
+
+    assert_eq!(Timing::default(), Timing::Fixed);
+    for timing in [Timing::Fixed, Timing::Vip] {
+        assert_eq!(timing.to_string().parse(), Ok(timing));
+    }
+    assert!("eti660".parse::<Timing>().is_err());
+}
+
+#[test]
+fn test_cycle_counters() {
+    let mut cpu = CPU::new();
+    cpu.load_program(&[0x60, 0x12, 0x30, 0x12]); // LD V0, 0x12; SE V0, 0x12
+    let output = cpu.tick([false; 16]);
+    assert_eq!((output.cycles, output.total_cycles), (46, 46));
+    let output = cpu.tick([false; 16]);
+    assert_eq!((output.cycles, output.total_cycles), (54, 100));
+
+    // Waiting for a key takes no cycles
+    cpu.run_opcode(0xF00A);
+    let output = cpu.tick([false; 16]);
+    assert_eq!((output.cycles, output.total_cycles), (0, 150));
+}
+
+#[test]
+fn test_fixed_timing() {
+    // The timers count down every tick, and nothing waits for vblank()
+    let mut cpu = CPU::new();
+    cpu.load_program(&[0xD0, 0x01, 0xD0, 0x01, 0x12, 0x00]); // DRW twice, then loop
+    cpu.delay_timer = 10;
+    for _ in 0..100 {
+        assert!(!cpu.waiting_for_vblank());
+        cpu.tick([false; 16]);
+    }
+    cpu.vblank();
+    assert_eq!(cpu.delay_timer, 0);
+}
+
+#[test]
+fn test_vip_timing_budget() {
+    let mut cpu = CPU::new();
+    cpu.set_timing(Timing::Vip);
+    cpu.load_program(&[0x70, 0x01, 0x12, 0x00]); // ADD V0, 1; JP 0x200
+    cpu.delay_timer = 10;
+    let mut ticks = 0;
+    while !cpu.waiting_for_vblank() {
+        cpu.tick([false; 16]);
+        ticks += 1;
+    }
+    // 50 + 52 cycles round the loop; the instruction that crosses the line still runs
+    assert_eq!(ticks, 51);
+    assert_eq!(cpu.v[0], 26);
+    assert_eq!(cpu.frame_cycles, 2600);
+    // The timers wait for the vertical blank
+    assert_eq!(cpu.delay_timer, 10);
+
+    // Held until vblank(), which keeps what was overspent
+    let output = cpu.tick([false; 16]);
+    assert_eq!((output.cycles, cpu.v[0]), (0, 26));
+    cpu.vblank();
+    assert_eq!(cpu.delay_timer, 9);
+    assert_eq!(cpu.frame_cycles, 2600 - FRAME_CYCLES);
+    assert!(!cpu.waiting_for_vblank());
+}
+
+#[test]
+fn test_vip_timing_one_draw_per_frame() {
+    let mut cpu = CPU::new();
+    cpu.set_timing(Timing::Vip);
+    cpu.load_program(&[0xD0, 0x01, 0x60, 0x08, 0xD0, 0x01]); // DRW; LD V0, 8; DRW
+    cpu.tick([false; 16]);
+    cpu.tick([false; 16]);
+    assert!(cpu.waiting_for_vblank());
+    assert!(!cpu.tick([false; 16]).vram_changed);
+    assert_eq!(cpu.pc, 0x204);
+
+    cpu.vblank();
+    assert!(cpu.tick([false; 16]).vram_changed);
+    assert_eq!(cpu.vram.get(8, 8), 1);
+
+    // Back to fixed timing, nothing is held
+    cpu.set_timing(Timing::Fixed);
+    cpu.run_opcode(0x1204);
+    assert!(!cpu.waiting_for_vblank());
+}
+
+// Bad ROMs wrap around RAM and the stack rather than crash
+#[test]
+fn test_stack_wraps() {
+    let mut cpu = build_cpu();
+    cpu.stack[15] = 0x345;
+    cpu.run_opcode(0x00ee);
+    assert_eq!((cpu.sp, cpu.pc), (15, 0x345));
+
+    let mut cpu = build_cpu();
+    for _ in 0..17 {
+        cpu.run_opcode(0x2F00);
+    }
+    assert_eq!(cpu.sp, 1);
+    assert_eq!(cpu.stack[0], 0xF02);
+}
+
+#[test]
+fn test_addresses_wrap() {
+    let mut cpu = build_cpu();
+    cpu.pc = 0xFFE;
+    cpu.run_opcode(0x6000);
+    assert_eq!(cpu.pc, 0);
+
+    let mut cpu = build_cpu();
+    cpu.pc = 0xFFF;
+    cpu.ram[0xFFF] = 0x60;
+    cpu.ram[0] = 0x42;
+    cpu.tick([false; 16]);
+    assert_eq!(cpu.v[0], 0x42);
+
+    let mut cpu = build_cpu();
+    cpu.v[0] = 0xFF;
+    cpu.run_opcode(0xBFFF);
+    assert_eq!(cpu.pc, 0x0FE);
+
+    let mut cpu = build_cpu();
+    cpu.i = 0xFFE;
+    cpu.v[0] = 234;
+    cpu.run_opcode(0xF033);
+    assert_eq!((cpu.ram[0xFFE], cpu.ram[0xFFF], cpu.ram[0]), (2, 3, 4));
+    assert_eq!(cpu.last_ram_write(), Some(0xFFE..0x1000));
+
+    let mut cpu = build_cpu();
+    cpu.i = 0xFFF;
+    cpu.run_opcode(0xF155);
+    assert_eq!((cpu.ram[0xFFF], cpu.ram[0]), (0, 0));
+    cpu.run_opcode(0xF165);
+    cpu.run_opcode(0xD015);
+
+    let mut cpu = build_cpu_with(QuirkPreset::Vip);
+    cpu.i = 0xFFFF;
+    cpu.run_opcode(0xFF65);
+    assert_eq!(cpu.i, 0xF);
+    cpu.i = 0xFFFF;
+    cpu.v[0] = 2;
+    cpu.run_opcode(0xF01E);
+    assert_eq!(cpu.i, 1);
+}
+
+#[test]
+fn test_keys_past_f() {
+    let mut cpu = build_cpu();
+    cpu.v[0] = 0x13;
+    let mut keypad = [false; 16];
+    keypad[3] = true;
+    cpu.keypad = keypad;
+    cpu.run_opcode(0xE09E);
+    assert_eq!(cpu.pc, SKIPPED_PC);
+    cpu.run_opcode(0xE0A1);
+    assert_eq!(cpu.pc, SKIPPED_PC + OPCODE_SIZE);
+}
+
+#[test]
+fn test_load_program_too_big() {
+    let mut cpu = CPU::new();
+    cpu.load_program(&[0xAA; 0x1000]);
+    assert_eq!(cpu.ram[0xFFF], 0xAA);
+    assert_eq!(cpu.ram[0], 0xF0); // the font is still there
+}
+
+// What 8xyN leaves in Vx, and in VF for those that set it, worked out longhand
+fn alu(n: u16, vx: u8, vy: u8, quirks: Quirks) -> (u8, Option<u8>) {
+    let logic_flag = if quirks.vf_reset { Some(0) } else { None };
+    let shifted = if quirks.shift_vy { vy } else { vx };
+    let sum = vx as u16 + vy as u16;
+    match n {
+        0x0 => (vy, None),
+        0x1 => (vx | vy, logic_flag),
+        0x2 => (vx & vy, logic_flag),
+        0x3 => (vx ^ vy, logic_flag),
+        0x4 => ((sum % 0x100) as u8, Some((sum > 0xFF) as u8)),
+        0x5 => (vx.wrapping_sub(vy), Some((vx >= vy) as u8)),
+        0x6 => (shifted / 2, Some(shifted % 2)),
+        0x7 => (vy.wrapping_sub(vx), Some((vy >= vx) as u8)),
+        0xE => ((shifted as u16 * 2 % 0x100) as u8, Some(shifted / 0x80)),
+        _ => unreachable!(),
+    }
+}
+
+const ALU_OPS: [u16; 9] = [0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0xE];
+
+// Every pair of values, with Vx = V0 and Vy = V1
+#[test]
+fn test_alu_all_pairs() {
+    for preset in QuirkPreset::ALL {
+        let mut cpu = build_cpu_with(preset);
+        for n in ALU_OPS {
+            for vx in 0..=0xFF {
+                for vy in 0..=0xFF {
+                    cpu.pc = START_PC;
+                    cpu.v[0] = vx;
+                    cpu.v[1] = vy;
+                    cpu.v[0xF] = 0xAA;
+                    cpu.run_opcode(0x8010 | n);
+                    let (result, flag) = alu(n, vx, vy, preset.quirks());
+                    assert_eq!((cpu.v[0], cpu.v[0xF]), (result, flag.unwrap_or(0xAA)), "8x1{:X} with {:02X}, {:02X} under {}", n, vx, vy, preset);
+                }
+            }
+        }
+    }
+}
+
+// The flag is written after the result, so with VF as Vx it's the flag that's left
+#[test]
+fn test_vf_written_last() {
+    for (opcode, vf, vy, expected) in [
+        (0x8F14, 0xFF, 0x02, 1), // ADD: 0x01 with a carry
+        (0x8F15, 0x01, 0x02, 0), // SUB: 0xFF with a borrow
+        (0x8F17, 0x01, 0x02, 1), // SUBN: 0x01, no borrow
+        (0x8F16, 0x03, 0x00, 1), // SHR: 0x01, shifting out a 1
+        (0x8F1E, 0x40, 0x00, 0), // SHL: 0x80, shifting out a 0
+    ] {
+        let mut cpu = build_cpu();
+        cpu.v[0xF] = vf;
+        cpu.v[1] = vy;
+        cpu.run_opcode(opcode);
+        assert_eq!(cpu.v[0xF], expected, "{:04X}", opcode);
+    }
+}
+
+proptest! {
+    // Any registers, with x and y often VF
+    #[test]
+    fn test_alu_opcodes(
+        n in prop::sample::select(ALU_OPS.to_vec()),
+        x in prop_oneof![Just(0xF), 0..16_usize],
+        y in prop_oneof![Just(0xF), 0..16_usize],
+        v in any::<[u8; 16]>(),
+        preset in prop::sample::select(QuirkPreset::ALL.to_vec()),
+    ) {
+        let mut cpu = build_cpu_with(preset);
+        cpu.v = v;
+        cpu.run_opcode(0x8000 | (x as u16) << 8 | (y as u16) << 4 | n);
+
+        let (result, flag) = alu(n, v[x], v[y], preset.quirks());
+        let mut expected = v;
+        expected[x] = result;
+        if let Some(flag) = flag {
+            expected[0xF] = flag;
+        }
+        prop_assert_eq!(cpu.v, expected);
+        prop_assert_eq!(cpu.pc, NEXT_PC);
+    }
+
+    // 7xkk wraps and never touches VF, unless VF is Vx
+    #[test]
+    fn test_7xkk_opcodes(x in prop_oneof![Just(0xF), 0..16_usize], kk: u8, v in any::<[u8; 16]>()) {
+        let mut cpu = build_cpu();
+        cpu.v = v;
+        cpu.run_opcode(0x7000 | (x as u16) << 8 | kk as u16);
+
+        let mut expected = v;
+        expected[x] = v[x].wrapping_add(kk);
+        prop_assert_eq!(cpu.v, expected);
+        prop_assert_eq!(cpu.pc, NEXT_PC);
+    }
+}
+
+#[test]
+fn test_platform_addresses() {
+    let mut cpu = CPU::new();
+    cpu.set_platform(Platform::Hires);
+    assert_eq!(cpu.pc, 0x2C0);
+    cpu.load_program(&[0x12, 0x60]);
+    assert_eq!(&cpu.ram[0x200..0x202], &[0x12, 0x60]);
+
+    cpu.set_platform(Platform::Chip8x);
+    assert_eq!(cpu.pc, 0x300);
+    cpu.load_program(&[0x02, 0xA0]);
+    assert_eq!(&cpu.ram[0x300..0x302], &[0x02, 0xA0]);
+}
+
+#[test]
+fn test_platform_screens() {
+    // HIRES: 64 rows, and 0230 clears them
+    let mut cpu = build_cpu();
+    cpu.set_platform(Platform::Hires);
+    cpu.pc = START_PC;
+    cpu.i = 0;
+    cpu.v[0] = 0;
+    cpu.v[1] = 60;
+    cpu.run_opcode(0xd015); // the 0 glyph, 5 rows from row 60
+    assert_eq!(cpu.vram.get(0, 63), 1);
+    assert_eq!(cpu.vram.get(0, 0), 1); // wrapped round
+    cpu.run_opcode(0x0230);
+    assert!(cpu.vram.pixels().iter().all(|&pixel| pixel == 0));
+
+    // CHIP-10: 128 columns
+    let mut cpu = build_cpu();
+    cpu.set_platform(Platform::Chip10);
+    cpu.i = 0;
+    cpu.v[0] = 124;
+    cpu.v[1] = 40;
+    cpu.run_opcode(0xd011);
+    assert!((124..128).all(|x| cpu.vram.get(x, 40) == 1));
+    // 0230 is only HIRES's
+    cpu.run_opcode(0x0230);
+    assert_eq!(cpu.vram.get(124, 40), 1);
+}
+
+#[test]
+fn test_chip8x_colours() {
+    let mut cpu = build_cpu();
+    assert!(cpu.colours().is_none());
+    cpu.set_platform(Platform::Chip8x);
+    cpu.pc = START_PC;
+    assert_eq!(cpu.colours(), Some(&Colours::default()));
+
+    cpu.run_opcode(0x02a0);
+    cpu.run_opcode(0x02a0);
+    assert_eq!(cpu.colours.background, 2);
+
+    // 2x1 zones from column 3 and zone row 1, in yellow
+    cpu.v[0] = 0x13;
+    cpu.v[1] = 0x01;
+    cpu.v[2] = 5;
+    let pc = cpu.pc;
+    cpu.run_opcode(0xb020);
+    assert_eq!(cpu.pc, pc + 2); // not a jump
+    assert!((4..8).all(|row| cpu.colours.strips[row][3..5] == [5, 5]));
+    assert_eq!(cpu.colours.strips[8][3], 1);
+    assert_eq!(cpu.colours.strips[4][5], 1);
+
+    // 3 rows from row 30 in column 7, wrapping to the top
+    cpu.v[0] = 7;
+    cpu.v[1] = 30;
+    cpu.run_opcode(0xb023);
+    assert_eq!([cpu.colours.strips[30][7], cpu.colours.strips[31][7], cpu.colours.strips[0][7]], [5, 5, 5]);
+    assert_eq!(cpu.colours.strips[1][7], 1);
+
+    // Nibble by nibble, 0-7 each
+    cpu.v[3] = 0x36;
+    cpu.v[4] = 0x15;
+    cpu.run_opcode(0x5341);
+    assert_eq!(cpu.v[3], 0x43);
+}
+
+#[test]
+fn test_platform_save_state() {
+    let mut cpu = CPU::with_seed(7);
+    cpu.set_platform(Platform::Chip8x);
+    cpu.load_program(&[0x02, 0xA0, 0x60, 0x21, 0x62, 0x07, 0xB0, 0x20, 0xD0, 0x05]);
+    for _ in 0..5 {
+        cpu.tick([false; 16]);
+    }
+    let state = cpu.save_state();
+    assert_eq!(state.len(), STATE_SIZE + 1 + 256);
+
+    let mut restored = CPU::with_seed(1);
+    assert!(restored.load_state(&state).is_err());
+    restored.set_platform(Platform::Chip8x);
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.vram, cpu.vram);
+    assert_eq!(restored.colours(), cpu.colours());
+
+    let mut cpu = CPU::new();
+    cpu.set_platform(Platform::Chip10);
+    assert_eq!(cpu.save_state().len(), STATE_SIZE + 128 * 64 - 64 * 32);
+}
+
+// A reset keeps the settings, and the random numbers carry on as if it hadn't happened
+#[test]
+fn test_reset() {
+    let mut cpu = CPU::with_seed(7);
+    let mut same = CPU::with_seed(7);
+    let mut font = Font::from_set(FontSet::Vip);
+    font.address = 0x50;
+    cpu.set_platform(Platform::Chip10);
+    cpu.set_quirks(QuirkPreset::Vip.quirks());
+    cpu.set_timing(Timing::Vip);
+    cpu.set_font(&font).unwrap();
+    cpu.load_program(&[0xC0, 0xFF, 0xC0, 0xFF]);
+    cpu.tick([false; 16]);
+    same.run_opcode(0xC0FF);
+
+    cpu.reset();
+    assert_eq!(cpu.platform(), Platform::Chip10);
+    assert_eq!(cpu.quirks(), QuirkPreset::Vip.quirks());
+    assert_eq!(cpu.timing(), Timing::Vip);
+    assert_eq!(cpu.font(), &font);
+    assert_eq!(&cpu.ram[0x50..0xA0], FontSet::Vip.glyphs());
+    assert_eq!(cpu.ram[0x200], 0);
+    let mut fresh = CPU::with_seed(7);
+    fresh.set_platform(Platform::Chip10);
+    assert_eq!(cpu.registers(), fresh.registers());
+    cpu.run_opcode(0xC0FF);
+    same.run_opcode(0xC0FF);
+    assert_eq!(cpu.v[0], same.v[0]);
+}
@@ -0,0 +1,246 @@
+// Screenshots (PNG) and recordings (animated GIF) of the chip8 screen.
+// This only needs the vram and colours from the CPU, so any front-end can use it.
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::palette::Palette;
+use yarc::framebuffer::Framebuffer;
+use yarc::platform::Colours;
+
+const FRAMES_PER_SECOND: u32 = 60;
+const GIF_TICKS_PER_SECOND: u32 = 100; // GIF frame delays are in hundredths of a second
+
+pub struct Capture {
+    rom_name: String, // captures are named <rom_name>-<timestamp>
+    directory: PathBuf,
+    palette: Palette,
+    scale: usize, // the scaled screenshot and recordings are this many times the chip8 size
+    size: (usize, usize), // of the last frame recorded, which recordings start at
+    coloured: bool, // the last frame recorded had CHIP-8X colours, and so do recordings
+    recording: Option<Recording>,
+}
+
+impl Capture {
+    // `screen` is the platform's, which recordings start at until a frame says otherwise
+    pub fn new(rom_path: &str, directory: &Path, palette: Palette, scale: usize, screen: (usize, usize)) -> Self {
+        let rom_name = Path::new(rom_path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| String::from("yarc"));
+
+        Capture {
+            rom_name,
+            directory: directory.to_path_buf(),
+            palette,
+            scale: scale.max(1),
+            size: screen,
+            coloured: false,
+            recording: None,
+        }
+    }
+
+    // Capture settings from capture.dir and capture.scale in the config
+    pub fn from_config(rom_path: &str, config: &Config, palette: Palette, screen: (usize, usize)) -> Result<Self, String> {
+        let directory = config.get("capture.dir").unwrap_or(".");
+        let scale = config.get_or("capture.scale", 10)?;
+        // Catch a scale too big to record now rather than when recording starts
+        gif_size(screen, scale)?;
+        Ok(Capture::new(rom_path, Path::new(directory), palette, scale, screen))
+    }
+
+    // Saves the screen as two PNGs: one at the chip8 resolution and one scaled up. `colours` are
+    // the CPU's, on platforms that have them; otherwise the palette's are used.
+    // Returns the paths written.
+    pub fn screenshot(&self, vram: &Framebuffer, colours: Option<&Colours>) -> Result<Vec<PathBuf>, String> {
+        let name = format!("{}-{}", self.rom_name, timestamp());
+        let native = self.directory.join(format!("{}.png", name));
+        let scaled = self.directory.join(format!("{}-x{}.png", name, self.scale));
+
+        write_png(&native, vram, &self.palette, colours, 1)?;
+        write_png(&scaled, vram, &self.palette, colours, self.scale)?;
+        Ok(vec![native, scaled])
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    // Starts a GIF recording, returning the path it is written to
+    pub fn start_recording(&mut self) -> Result<PathBuf, String> {
+        let path = self.directory.join(format!("{}-{}.gif", self.rom_name, timestamp()));
+        self.recording = Some(Recording::new(&path, &self.palette, self.coloured, self.size, self.scale)?);
+        Ok(path)
+    }
+
+    // Finishes the GIF recording, if there is one
+    pub fn stop_recording(&mut self) -> Result<(), String> {
+        match self.recording.take() {
+            Some(recording) => recording.finish(),
+            None => Ok(()),
+        }
+    }
+
+    // Adds a frame to the recording, if there is one. Call once per 60 Hz frame.
+    pub fn record_frame(&mut self, vram: &Framebuffer, colours: Option<&Colours>) -> Result<(), String> {
+        self.size = vram.size();
+        self.coloured = colours.is_some();
+        match &mut self.recording {
+            Some(recording) => recording.add_frame(vram, colours),
+            None => Ok(()),
+        }
+    }
+}
+
+struct Recording {
+    encoder: gif::Encoder<BufWriter<File>>,
+    scale: usize,
+    size: (usize, usize),
+    gif_size: (u16, u16), // size scaled up
+    coloured: bool, // the GIF palette is Colours::palette() rather than the palette's two colours
+    pending: Option<Vec<u8>>, // last frame, held back until we know how long it stays on screen
+    pending_frames: u32, // how many 60 Hz frames the pending frame has been shown for
+    frames: u32, // 60 Hz frames written so far
+    ticks: u32, // GIF delay ticks written so far
+}
+
+impl Recording {
+    fn new(path: &Path, palette: &Palette, coloured: bool, size: (usize, usize), scale: usize) -> Result<Self, String> {
+        let gif_size = gif_size(size, scale)?;
+        let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let global_palette = if coloured {
+            Colours::palette().concat()
+        } else {
+            [palette.off, palette.on].concat()
+        };
+
+        let mut encoder = gif::Encoder::new(
+                BufWriter::new(file),
+                gif_size.0,
+                gif_size.1,
+                &global_palette)
+                .map_err(|e| e.to_string())?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(|e| e.to_string())?;
+
+        Ok(Recording { encoder, scale, size, gif_size, coloured, pending: None, pending_frames: 0, frames: 0, ticks: 0 })
+    }
+
+    fn add_frame(&mut self, vram: &Framebuffer, colours: Option<&Colours>) -> Result<(), String> {
+        if vram.size() != self.size || colours.is_some() != self.coloured {
+            return Err(String::from("The screen changed size or colours while recording"));
+        }
+        let indices = match colours {
+            Some(colours) => scale_up(vram, self.scale, |x, y, pixel| colours.palette_index(x, y, pixel != 0)),
+            None => scale_up(vram, self.scale, |_, _, pixel| if pixel == 0 { 0 } else { 1 }),
+        };
+
+        // Identical frames are merged into one longer GIF frame
+        if self.pending.as_ref() == Some(&indices) {
+            self.pending_frames += 1;
+            return Ok(());
+        }
+        self.flush()?;
+        self.pending = Some(indices);
+        self.pending_frames = 1;
+        Ok(())
+    }
+
+    // Writes the pending frame. 60 Hz does not divide into GIF ticks evenly,
+    // so delays are rounded against the total time to keep the recording in sync.
+    fn flush(&mut self) -> Result<(), String> {
+        let indices = match self.pending.take() {
+            Some(indices) => indices,
+            None => return Ok(()),
+        };
+        self.frames += self.pending_frames;
+        let ticks = (self.frames * GIF_TICKS_PER_SECOND + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND;
+        let delay = (ticks - self.ticks).max(1);
+        self.ticks += delay;
+
+        let mut frame = gif::Frame::from_indexed_pixels(self.gif_size.0, self.gif_size.1, &indices, None);
+        frame.delay = delay as u16;
+        self.encoder.write_frame(&frame).map_err(|e| e.to_string())
+    }
+
+    fn finish(mut self) -> Result<(), String> {
+        self.flush()
+        // The GIF trailer is written when the encoder is dropped
+    }
+}
+
+// The size of a recording of a `size` screen scaled up, if it fits in a GIF
+fn gif_size(size: (usize, usize), scale: usize) -> Result<(u16, u16), String> {
+    let scaled = |length: usize| length.checked_mul(scale).and_then(|length| u16::try_from(length).ok());
+    match (scaled(size.0), scaled(size.1)) {
+        (Some(width), Some(height)) => Ok((width, height)),
+        _ => Err(format!("A {}x{} screen scaled {} times is too big to record", size.0, size.1, scale)),
+    }
+}
+
+fn write_png(path: &Path, vram: &Framebuffer, palette: &Palette, colours: Option<&Colours>, scale: usize) -> Result<(), String> {
+    let error = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let file = File::create(path).map_err(|e| error(&e))?;
+
+    let (width, height) = vram.size();
+    let mut encoder = png::Encoder::new(BufWriter::new(file), (width * scale) as u32, (height * scale) as u32);
+    encoder.set_color(png::ColorType::RGB);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| error(&e))?;
+
+    let rgb = scale_up(vram, scale, |x, y, pixel| {
+        let brightness = if pixel == 0 { 0 } else { 255 };
+        match colours {
+            Some(colours) => colours.rgb(x, y, brightness),
+            None => palette.rgb(brightness),
+        }
+    });
+    writer.write_image_data(&rgb.concat()).map_err(|e| error(&e))
+}
+
+// One value per pixel of the scaled-up screen, row-major, from the chip8 pixel at (x, y)
+fn scale_up<T: Copy, F: Fn(usize, usize, u8) -> T>(vram: &Framebuffer, scale: usize, value: F) -> Vec<T> {
+    let mut out = Vec::with_capacity(vram.pixels().len() * scale * scale);
+    for (y, row) in vram.rows().enumerate() {
+        for _ in 0..scale {
+            for (x, &pixel) in row.iter().enumerate() {
+                let value = value(x, y, pixel);
+                for _ in 0..scale {
+                    out.push(value);
+                }
+            }
+        }
+    }
+    out
+}
+
+// UTC time as YYYYMMDD-HHMMSS-mmm. The milliseconds keep quick successive captures apart.
+fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = now.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let time = seconds % 86400;
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+            year, month, day, time / 3600, time % 3600 / 60, time % 60, now.subsec_millis())
+}
+
+// Converts days since 1970-01-01 to a (year, month, day) date.
+// Howard Hinnant's algorithm: http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+#[path = "./capture_test.rs"]
+mod tests;
@@ -0,0 +1,147 @@
+use super::*;
+use crate::{CHIP8_HEIGHT, CHIP8_WIDTH};
+use std::env;
+use std::fs;
+
+// Each test writes into its own directory so they can run in parallel
+fn test_directory(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("yarc-capture-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn vram_with(x: usize, y: usize) -> Framebuffer {
+    sized_vram_with(CHIP8_WIDTH, CHIP8_HEIGHT, x, y)
+}
+
+fn sized_vram_with(width: usize, height: usize, x: usize, y: usize) -> Framebuffer {
+    let mut vram = Framebuffer::new(width, height, 1);
+    vram.set(x, y, 1);
+    vram
+}
+
+#[test]
+fn test_screenshot() {
+    let directory = test_directory("screenshot");
+    let capture = Capture::new("games/PONG", &directory, Palette::default(), 2, (CHIP8_WIDTH, CHIP8_HEIGHT));
+    let paths = capture.screenshot(&vram_with(1, 0), None).unwrap();
+
+    assert_eq!(paths.len(), 2);
+    let name = paths[0].file_name().unwrap().to_string_lossy().into_owned();
+    assert!(name.starts_with("PONG-") && name.ends_with(".png"));
+    assert!(paths[1].to_string_lossy().ends_with("-x2.png"));
+
+    let decoder = png::Decoder::new(File::open(&paths[1]).unwrap());
+    let (info, mut reader) = decoder.read_info().unwrap();
+    assert_eq!((info.width, info.height), (128, 64));
+    let mut rgb = vec![0; info.buffer_size()];
+    reader.next_frame(&mut rgb).unwrap();
+    assert_eq!(rgb[0..3], [0, 0, 0]);
+    assert_eq!(rgb[6..9], [0, 250, 0]); // x = 2 is the first scaled pixel of chip8 pixel 1
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+// CHIP-8X screens are captured in their own colours rather than the palette's
+#[test]
+fn test_colours() {
+    let directory = test_directory("colours");
+    let mut capture = Capture::new("PONG", &directory, Palette::default(), 1, (CHIP8_WIDTH, CHIP8_HEIGHT));
+    let colours = Colours::default();
+    let paths = capture.screenshot(&vram_with(1, 0), Some(&colours)).unwrap();
+
+    let decoder = png::Decoder::new(File::open(&paths[0]).unwrap());
+    let (info, mut reader) = decoder.read_info().unwrap();
+    let mut rgb = vec![0; info.buffer_size()];
+    reader.next_frame(&mut rgb).unwrap();
+    assert_eq!(rgb[0..3], colours.rgb(0, 0, 0));
+    assert_eq!(rgb[3..6], colours.rgb(1, 0, 255));
+
+    capture.record_frame(&vram_with(1, 0), Some(&colours)).unwrap();
+    let path = capture.start_recording().unwrap();
+    capture.record_frame(&vram_with(1, 0), Some(&colours)).unwrap();
+    capture.stop_recording().unwrap();
+    let mut decoder = gif::DecodeOptions::new().read_info(File::open(&path).unwrap()).unwrap();
+    assert_eq!(decoder.global_palette().unwrap()[..36], Colours::palette().concat());
+    let frame = decoder.read_next_frame().unwrap().unwrap();
+    assert_eq!(frame.buffer[..2], [colours.palette_index(0, 0, false), colours.palette_index(1, 0, true)]);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+// Other platforms' screens are captured at their own size
+#[test]
+fn test_screenshot_size() {
+    let directory = test_directory("size");
+    let capture = Capture::new("PONG", &directory, Palette::default(), 1, (CHIP8_WIDTH, CHIP8_HEIGHT));
+    let paths = capture.screenshot(&sized_vram_with(128, 64, 127, 63), None).unwrap();
+
+    let decoder = png::Decoder::new(File::open(&paths[0]).unwrap());
+    let (info, mut reader) = decoder.read_info().unwrap();
+    assert_eq!((info.width, info.height), (128, 64));
+    let mut rgb = vec![0; info.buffer_size()];
+    reader.next_frame(&mut rgb).unwrap();
+    assert_eq!(rgb[rgb.len() - 3..], [0, 250, 0]);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_recording() {
+    let directory = test_directory("recording");
+    let mut capture = Capture::new("PONG", &directory, Palette::default(), 1, (CHIP8_WIDTH, CHIP8_HEIGHT));
+    assert!(!capture.is_recording());
+
+    let path = capture.start_recording().unwrap();
+    assert!(capture.is_recording());
+    // 60 identical frames collapse into one frame lasting a second
+    for _ in 0..60 {
+        capture.record_frame(&vram_with(0, 0), None).unwrap();
+    }
+    capture.record_frame(&vram_with(1, 0), None).unwrap();
+    capture.stop_recording().unwrap();
+    assert!(!capture.is_recording());
+
+    let mut decoder = gif::DecodeOptions::new().read_info(File::open(&path).unwrap()).unwrap();
+    let first = decoder.read_next_frame().unwrap().unwrap();
+    assert_eq!(first.delay, 100);
+    let second = decoder.read_next_frame().unwrap().unwrap();
+    assert_eq!(second.delay, 2);
+    assert!(decoder.read_next_frame().unwrap().is_none());
+
+    // A recording keeps the size and colours it started at
+    capture.start_recording().unwrap();
+    assert!(capture.record_frame(&sized_vram_with(128, 64, 0, 0), None).is_err());
+    capture.start_recording().unwrap();
+    assert!(capture.record_frame(&vram_with(0, 0), Some(&Colours::default())).is_err());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_too_big_to_record() {
+    let directory = test_directory("too-big");
+    // GIFs are at most 65535 pixels a side
+    let config = Config::parse("capture.scale = 1000").unwrap();
+    assert!(Capture::from_config("PONG", &config, Palette::default(), (CHIP8_WIDTH, CHIP8_HEIGHT)).is_ok());
+    assert!(Capture::from_config("PONG", &config, Palette::default(), (128, 64)).is_err());
+    let config = Config::parse(&format!("capture.scale = {}", usize::MAX)).unwrap();
+    assert!(Capture::from_config("PONG", &config, Palette::default(), (CHIP8_WIDTH, CHIP8_HEIGHT)).is_err());
+
+    // The screen can grow to a size that doesn't fit after the capture is set up
+    let mut capture = Capture::new("PONG", &directory, Palette::default(), 1000, (CHIP8_WIDTH, CHIP8_HEIGHT));
+    capture.record_frame(&sized_vram_with(128, 64, 0, 0), None).unwrap();
+    assert!(capture.start_recording().is_err());
+    assert!(!capture.is_recording());
+    assert_eq!(fs::read_dir(&directory).unwrap().count(), 0);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_civil_from_days() {
+    assert_eq!(civil_from_days(0), (1970, 1, 1));
+    assert_eq!(civil_from_days(11016), (2000, 2, 29));
+    assert_eq!(civil_from_days(20744), (2026, 10, 18));
+}
@@ -0,0 +1,206 @@
+// Cheats: RAM search to find where a game keeps something (lives, score...), and codes that
+// freeze or patch those addresses.
+//
+// A cheat is written ADDRESS=VALUE to freeze the byte (it is written back after every
+// instruction) or ADDRESS:VALUE to patch it once at startup, both in hex, e.g. 0x2F4=09.
+// Cheat lists are kept per game in <cheats.dir>/<ROM hash>.cht, one cheat per line.
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::config::Config;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CheatKind {
+    Freeze,
+    Patch,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Cheat {
+    pub address: usize,
+    pub value: u8,
+    pub kind: CheatKind,
+}
+
+impl FromStr for Cheat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (separator, kind) = if s.contains('=') { ('=', CheatKind::Freeze) } else { (':', CheatKind::Patch) };
+        let mut parts = s.splitn(2, separator);
+        let address = parts.next().unwrap_or("").trim();
+        let value = parts.next().ok_or_else(|| format!("cheat {} should be ADDRESS=VALUE or ADDRESS:VALUE", s))?.trim();
+
+        let address = parse_hex(address, 0xFFF).ok_or_else(|| format!("bad cheat address {}", address))?;
+        let value = parse_hex(value, 0xFF).ok_or_else(|| format!("bad cheat value {}", value))? as u8;
+        Ok(Cheat { address, value, kind })
+    }
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = match self.kind {
+            CheatKind::Freeze => '=',
+            CheatKind::Patch => ':',
+        };
+        write!(f, "{:#05X}{}{:02X}", self.address, separator, self.value)
+    }
+}
+
+// Hex with or without 0x, up to `max`
+fn parse_hex(s: &str, max: usize) -> Option<usize> {
+    let digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+    usize::from_str_radix(digits, 16).ok().filter(|&value| value <= max)
+}
+
+// Hash of the ROM, so cheat lists follow the game rather than its file name
+pub fn rom_hash(rom: &[u8]) -> String {
+    format!("{:016x}", fnv1a(FNV_OFFSET, rom))
+}
+
+// 64-bit FNV-1a; start with FNV_OFFSET and feed the result back in to hash several slices
+pub const FNV_OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+
+pub fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01B3);
+    }
+    hash
+}
+
+#[derive(Default)]
+pub struct CheatList {
+    cheats: Vec<Cheat>,
+    path: Option<PathBuf>, // where save() writes the list
+}
+
+impl CheatList {
+    // The saved list for this ROM, if there is one, plus the cheats from the command line
+    pub fn load(rom: &[u8], extra: &[Cheat], config: &Config) -> Result<Self, String> {
+        let dir = Path::new(config.get("cheats.dir").unwrap_or("."));
+        let path = dir.join(format!("{}.cht", rom_hash(rom)));
+        let mut list = match fs::read_to_string(&path) {
+            Ok(text) => CheatList::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?,
+            Err(_) => CheatList::default(),
+        };
+        list.path = Some(path);
+        for &cheat in extra {
+            list.add(cheat);
+        }
+        Ok(list)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut list = CheatList::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            list.add(line.parse().map_err(|e| format!("line {}: {}", number + 1, e))?);
+        }
+        Ok(list)
+    }
+
+    pub fn save(&self) -> Result<PathBuf, String> {
+        let path = self.path.clone().ok_or("no file to save cheats to")?;
+        let mut text = String::from("# YARC cheats: ADDRESS=VALUE freezes, ADDRESS:VALUE patches once\n");
+        for cheat in &self.cheats {
+            text.push_str(&format!("{}\n", cheat));
+        }
+        fs::write(&path, text).map_err(|e| format!("Can't save cheats to {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    // A new cheat for an address replaces the old one
+    pub fn add(&mut self, cheat: Cheat) {
+        self.remove(cheat.address);
+        self.cheats.push(cheat);
+    }
+
+    pub fn remove(&mut self, address: usize) {
+        self.cheats.retain(|cheat| cheat.address != address);
+    }
+
+    // Once, after the ROM is loaded
+    pub fn apply_patches(&self, ram: &mut [u8]) {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.kind == CheatKind::Patch) {
+            ram[cheat.address] = cheat.value;
+        }
+    }
+
+    // After every tick
+    pub fn apply_freezes(&self, ram: &mut [u8]) {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.kind == CheatKind::Freeze) {
+            ram[cheat.address] = cheat.value;
+        }
+    }
+}
+
+// How RAM search narrows down candidates, comparing RAM now with the last snapshot
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SearchFilter {
+    Equal,
+    Changed,
+    Increased,
+    Decreased,
+    Value(u8), // equal to this value now
+}
+
+impl FromStr for SearchFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "equal" => Ok(SearchFilter::Equal),
+            "changed" => Ok(SearchFilter::Changed),
+            "increased" => Ok(SearchFilter::Increased),
+            "decreased" => Ok(SearchFilter::Decreased),
+            _ => Err(format!("unknown search filter {} (expected equal, changed, increased or decreased)", s)),
+        }
+    }
+}
+
+// Start with a snapshot, then filter over several frames until few addresses are left
+pub struct RamSearch {
+    snapshot: Vec<u8>,
+    candidates: Vec<usize>,
+}
+
+impl RamSearch {
+    pub fn new(ram: &[u8]) -> Self {
+        RamSearch { snapshot: ram.to_vec(), candidates: (0..ram.len()).collect() }
+    }
+
+    // Keeps the candidates passing the filter and takes a new snapshot. Returns how many are left.
+    pub fn filter(&mut self, ram: &[u8], filter: SearchFilter) -> usize {
+        let snapshot = &self.snapshot;
+        self.candidates.retain(|&address| {
+            let (before, now) = (snapshot[address], ram[address]);
+            match filter {
+                SearchFilter::Equal => now == before,
+                SearchFilter::Changed => now != before,
+                SearchFilter::Increased => now > before,
+                SearchFilter::Decreased => now < before,
+                SearchFilter::Value(value) => now == value,
+            }
+        });
+        self.snapshot.copy_from_slice(ram);
+        self.candidates.len()
+    }
+
+    pub fn candidates(&self) -> &[usize] {
+        &self.candidates
+    }
+}
+
+#[cfg(test)]
+#[path = "./cheats_test.rs"]
+mod tests;
@@ -0,0 +1,82 @@
+use super::*;
+
+#[test]
+fn test_parse_cheat() {
+    assert_eq!("0x2F4=09".parse(), Ok(Cheat { address: 0x2F4, value: 9, kind: CheatKind::Freeze }));
+    assert_eq!("300:ff".parse(), Ok(Cheat { address: 0x300, value: 0xFF, kind: CheatKind::Patch }));
+    assert!("0x2F4".parse::<Cheat>().is_err());
+    assert!("0x1000=1".parse::<Cheat>().is_err());
+    assert!("0x2F4=100".parse::<Cheat>().is_err());
+    assert!("zz=1".parse::<Cheat>().is_err());
+}
+
+#[test]
+fn test_cheat_round_trip() {
+    for text in ["0x2F4=09", "0x300:FF"].iter() {
+        let cheat: Cheat = text.parse().unwrap();
+        assert_eq!(cheat.to_string().parse(), Ok(cheat));
+    }
+}
+
+#[test]
+fn test_rom_hash() {
+    assert_eq!(rom_hash(&[]), "cbf29ce484222325");
+    assert_ne!(rom_hash(&[1, 2]), rom_hash(&[2, 1]));
+}
+
+#[test]
+fn test_cheat_list() {
+    let mut list = CheatList::parse("# lives\n0x2F4=09\n\n0x300:01\n").unwrap();
+    assert_eq!(list.cheats().len(), 2);
+    list.add("0x2F4=03".parse().unwrap());
+    assert_eq!(list.cheats().len(), 2);
+
+    let mut ram = [0u8; 0x400];
+    list.apply_patches(&mut ram);
+    assert_eq!((ram[0x2F4], ram[0x300]), (0, 1));
+    list.apply_freezes(&mut ram);
+    assert_eq!(ram[0x2F4], 3);
+
+    list.remove(0x2F4);
+    assert_eq!(list.cheats().len(), 1);
+    assert!(CheatList::parse("0x2F4\n").is_err());
+}
+
+#[test]
+fn test_cheat_list_per_rom() {
+    let dir = std::env::temp_dir().join("yarc-cheats-test");
+    fs::create_dir_all(&dir).unwrap();
+    let config = Config::parse(&format!("cheats.dir = {}", dir.display())).unwrap();
+    let rom = [0x12, 0x00];
+    let _ = fs::remove_file(dir.join(format!("{}.cht", rom_hash(&rom))));
+
+    let list = CheatList::load(&rom, &["0x2F4=09".parse().unwrap()], &config).unwrap();
+    let path = list.save().unwrap();
+    assert_eq!(path, dir.join(format!("{}.cht", rom_hash(&rom))));
+    assert_eq!(CheatList::load(&rom, &[], &config).unwrap().cheats(), list.cheats());
+    assert!(CheatList::load(&[0x13, 0x00], &[], &config).unwrap().cheats().is_empty());
+}
+
+#[test]
+fn test_ram_search() {
+    let mut ram = [0u8; 8];
+    ram[3] = 3;
+    ram[5] = 5;
+    let mut search = RamSearch::new(&ram);
+    assert_eq!(search.candidates().len(), 8);
+
+    ram[3] = 2;
+    ram[5] = 6;
+    assert_eq!(search.filter(&ram, SearchFilter::Changed), 2);
+    ram[5] = 7;
+    assert_eq!(search.filter(&ram, SearchFilter::Increased), 1);
+    assert_eq!(search.candidates(), [5]);
+    assert_eq!(search.filter(&ram, SearchFilter::Equal), 1);
+    assert_eq!(search.filter(&ram, SearchFilter::Value(8)), 0);
+
+    let mut search = RamSearch::new(&ram);
+    ram[3] = 1;
+    assert_eq!(search.filter(&ram, SearchFilter::Decreased), 1);
+    assert_eq!("increased".parse(), Ok(SearchFilter::Increased));
+    assert!("bigger".parse::<SearchFilter>().is_err());
+}
@@ -0,0 +1,328 @@
+// Command line options and the optional config file.
+//
+// The config file is plain text with one `key = value` setting per line.
+// Lines starting with # are comments. By default ./yarc.cfg is read if it exists.
+use std::collections::HashMap;
+use std::fs;
+use std::str::FromStr;
+
+use crate::cheats::Cheat;
+use crate::netplay::{self, Role};
+use crate::trace::{self, TraceFilter, TraceFormat};
+use yarc::font::{Font, FontSet};
+use yarc::platform::Platform;
+use yarc::quirks::{QuirkPreset, Quirks};
+use yarc::timing::Timing;
+
+pub const DEFAULT_CONFIG_FILE: &str = "yarc.cfg";
+
+// Where the game is shown and played
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Frontend {
+    Sdl, // a window, needs the sdl feature
+    Terminal, // Unicode block characters in the terminal, e.g. over SSH
+}
+
+impl Default for Frontend {
+    fn default() -> Self {
+        if cfg!(feature = "sdl") { Frontend::Sdl } else { Frontend::Terminal }
+    }
+}
+
+impl FromStr for Frontend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sdl" => Ok(Frontend::Sdl),
+            "terminal" => Ok(Frontend::Terminal),
+            _ => Err(format!("unknown frontend {} (expected sdl or terminal)", s)),
+        }
+    }
+}
+
+// Another game for the grid, from --tile ROM[,quirks=PRESET][,timing=TIMING]. Settings left out
+// come from the config, as for the main game.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Tile {
+    pub rom: String,
+    pub quirks: Option<QuirkPreset>,
+    pub timing: Option<Timing>,
+}
+
+impl FromStr for Tile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',');
+        let rom = parts.next().filter(|rom| !rom.is_empty()).ok_or_else(|| format!("tile {} has no ROM", s))?;
+        let mut tile = Tile { rom: rom.to_string(), quirks: None, timing: None };
+        for part in parts {
+            match part.split_once('=') {
+                Some(("quirks", preset)) => tile.quirks = Some(preset.parse()?),
+                Some(("timing", timing)) => tile.timing = Some(timing.parse()?),
+                _ => return Err(format!("unknown tile setting {} (expected quirks= or timing=)", part)),
+            }
+        }
+        Ok(tile)
+    }
+}
+
+pub struct Options {
+    pub rom: String,
+    pub config_file: Option<String>, // None: use DEFAULT_CONFIG_FILE if it exists
+    pub frontend: Frontend,
+    pub script: Option<String>, // Rhai script attached to the game
+    pub cheats: Vec<Cheat>, // from --cheat, on top of the game's saved cheat list
+    pub netplay: Option<Role>,
+    pub keys: Option<u16>, // keys this netplay peer owns, as a bit mask
+    pub gdb: Option<String>, // port or address for the GDB stub
+    pub rpc: Option<String>, // port or address for the control API
+    pub trace: Option<String>, // file to log executed instructions to
+    pub trace_filter: TraceFilter,
+    pub trace_format: TraceFormat,
+    pub profile: Option<String>, // name of the profile report files
+    pub quirks: Option<QuirkPreset>, // None: the quirks setting in the config
+    pub timing: Option<Timing>, // None: the timing setting in the config
+    pub font: Option<FontSet>, // None: the font settings in the config
+    pub font_file: Option<String>, // custom font, instead of a built-in one
+    pub platform: Option<Platform>, // None: the platform setting in the config
+    pub tiles: Vec<Tile>, // more games to run next to the main one, in a grid
+    #[cfg_attr(not(feature = "sdl"), allow(dead_code))] // read by the SDL front-end only
+    pub inspector: bool, // open the memory and screen inspector window
+}
+
+impl Options {
+    // Parses the command line, without the program name
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut rom = None;
+        let mut config_file = None;
+        let mut frontend = Frontend::default();
+        let mut script = None;
+        let mut cheats = Vec::new();
+        let mut netplay = None;
+        let mut keys = None;
+        let mut gdb = None;
+        let mut rpc = None;
+        let mut trace = None;
+        let mut trace_filter = TraceFilter::default();
+        let mut trace_format = None;
+        let mut profile = None;
+        let mut quirks = None;
+        let mut timing = None;
+        let mut font = None;
+        let mut font_file = None;
+        let mut platform = None;
+        let mut tiles = Vec::new();
+        let mut inspector = false;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => config_file = Some(value_of(arg, args.next())?),
+                "--frontend" => frontend = value_of(arg, args.next())?.parse()?,
+                "--script" => script = Some(value_of(arg, args.next())?),
+                "--cheat" => cheats.push(value_of(arg, args.next())?.parse()?),
+                "--host" | "--connect" if netplay.is_some() => return Err(String::from("give only one of --host and --connect")),
+                "--host" => netplay = Some(Role::Host(value_of(arg, args.next())?)),
+                "--connect" => netplay = Some(Role::Connect(value_of(arg, args.next())?)),
+                "--keys" => keys = Some(netplay::parse_keys(&value_of(arg, args.next())?)?),
+                "--gdb" => gdb = Some(value_of(arg, args.next())?),
+                "--rpc" => rpc = Some(value_of(arg, args.next())?),
+                "--trace" => trace = Some(value_of(arg, args.next())?),
+                "--trace-pc" => trace_filter.pcs = Some(trace::parse_pcs(&value_of(arg, args.next())?)?),
+                "--trace-ops" => trace_filter.classes = Some(trace::parse_classes(&value_of(arg, args.next())?)?),
+                "--trace-frames" => trace_filter.frames = Some(trace::parse_frames(&value_of(arg, args.next())?)?),
+                "--trace-format" => trace_format = Some(value_of(arg, args.next())?.parse()?),
+                "--profile" => profile = Some(value_of(arg, args.next())?),
+                "--quirks" => quirks = Some(value_of(arg, args.next())?.parse()?),
+                "--timing" => timing = Some(value_of(arg, args.next())?.parse()?),
+                "--font" => font = Some(value_of(arg, args.next())?.parse()?),
+                "--font-file" => font_file = Some(value_of(arg, args.next())?),
+                "--platform" => platform = Some(value_of(arg, args.next())?.parse()?),
+                "--tile" => tiles.push(value_of(arg, args.next())?.parse()?),
+                "--inspector" => inspector = true,
+                _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+                _ if rom.is_none() => rom = Some(arg.clone()),
+                _ => return Err(format!("unexpected argument {}", arg)),
+            }
+        }
+
+        if keys.is_some() && netplay.is_none() {
+            return Err(String::from("--keys needs --host or --connect"));
+        }
+        if trace.is_none() && (trace_filter != TraceFilter::default() || trace_format.is_some()) {
+            return Err(String::from("--trace-pc, --trace-ops, --trace-frames and --trace-format need --trace"));
+        }
+        if font.is_some() && font_file.is_some() {
+            return Err(String::from("give only one of --font and --font-file"));
+        }
+        if inspector {
+            if frontend != Frontend::Sdl || !tiles.is_empty() {
+                return Err(String::from("--inspector needs the SDL frontend, without --tile"));
+            }
+            // Edits would only happen on one side
+            if netplay.is_some() {
+                return Err(String::from("--inspector doesn't mix with netplay"));
+            }
+        }
+        if !tiles.is_empty() {
+            if frontend != Frontend::Sdl {
+                return Err(String::from("--tile needs the SDL frontend"));
+            }
+            // The tiles are plain CPUs, without a session around them
+            if netplay.is_some() || gdb.is_some() || rpc.is_some() || script.is_some() || trace.is_some() || profile.is_some() {
+                return Err(String::from("--tile doesn't mix with netplay, --gdb, --rpc, --script, --trace or --profile"));
+            }
+            // A cheat is for one game's RAM, and would poke the others wherever it points
+            if !cheats.is_empty() {
+                return Err(String::from("--tile doesn't mix with --cheat"));
+            }
+        }
+
+        Ok(Options {
+            rom: rom.ok_or("no ROM file given")?,
+            config_file,
+            frontend,
+            script,
+            cheats,
+            netplay,
+            keys,
+            gdb,
+            rpc,
+            trace,
+            trace_filter,
+            trace_format: trace_format.unwrap_or_default(),
+            profile,
+            quirks,
+            timing,
+            font,
+            font_file,
+            platform,
+            tiles,
+            inspector,
+        })
+    }
+}
+
+fn value_of(option: &str, value: Option<&String>) -> Result<String, String> {
+    value.cloned().ok_or_else(|| format!("{} needs a value", option))
+}
+
+#[derive(Default)]
+pub struct Config {
+    values: HashMap<String, String>,
+}
+
+impl Config {
+    // Reads the given config file, or the default one if it exists
+    pub fn load(path: Option<&str>) -> Result<Self, String> {
+        let (path, required) = match path {
+            Some(path) => (path, true),
+            None => (DEFAULT_CONFIG_FILE, false),
+        };
+
+        match fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text).map_err(|e| format!("{}: {}", path, e)),
+            Err(_) if !required => Ok(Config::default()),
+            Err(e) => Err(format!("{}: {}", path, e)),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut values = HashMap::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap().trim();
+            let value = parts.next().ok_or(format!("line {}: expected key = value", number + 1))?;
+            values.insert(key.to_string(), value.trim().to_string());
+        }
+
+        Ok(Config { values })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|value| value.as_str())
+    }
+
+    // Reads a setting, falling back to `default` when it is not set
+    pub fn get_or<T: FromStr>(&self, key: &str, default: T) -> Result<T, String> {
+        match self.get(key) {
+            Some(value) => value.parse().map_err(|_| format!("invalid value for {}: {}", key, value)),
+            None => Ok(default),
+        }
+    }
+}
+
+// The quirks preset from --quirks or the config, with any quirk overridden by `quirks.<quirk>` keys
+pub fn quirks(preset: Option<QuirkPreset>, config: &Config) -> Result<Quirks, String> {
+    let preset = match preset {
+        Some(preset) => preset,
+        None => config.get_or("quirks", QuirkPreset::Yarc)?,
+    };
+    let defaults = preset.quirks();
+
+    Ok(Quirks {
+        vf_reset: config.get_or("quirks.vf_reset", defaults.vf_reset)?,
+        shift_vy: config.get_or("quirks.shift_vy", defaults.shift_vy)?,
+        memory_increment: config.get_or("quirks.memory_increment", defaults.memory_increment)?,
+        jump_vx: config.get_or("quirks.jump_vx", defaults.jump_vx)?,
+        clip_sprites: config.get_or("quirks.clip_sprites", defaults.clip_sprites)?,
+        key_release: config.get_or("quirks.key_release", defaults.key_release)?,
+    })
+}
+
+// The timing from --timing or the config; fixed unless asked for
+pub fn timing(timing: Option<Timing>, config: &Config) -> Result<Timing, String> {
+    match timing {
+        Some(timing) => Ok(timing),
+        None => config.get_or("timing", Timing::Fixed),
+    }
+}
+
+// The platform from --platform or the config; CHIP-8 unless asked for
+pub fn platform(options: &Options, config: &Config) -> Result<Platform, String> {
+    let platform = match options.platform {
+        Some(platform) => platform,
+        None => config.get_or("platform", Platform::Chip8)?,
+    };
+    // Grid tiles are only drawn in the palette's colours, so keep them to CHIP-8
+    if platform != Platform::Chip8 && !options.tiles.is_empty() {
+        return Err(format!("--tile doesn't run {} games", platform));
+    }
+    Ok(platform)
+}
+
+// The font from --font or --font-file, or else the `font` or `font.file` setting, loaded at the
+// `font.address` setting (hex, 0 by default)
+pub fn font(set: Option<FontSet>, file: Option<&str>, config: &Config) -> Result<Font, String> {
+    let file = match (set, file) {
+        (Some(_), _) => None,
+        (None, Some(file)) => Some(file),
+        (None, None) => config.get("font.file"),
+    };
+    let mut font = match file {
+        Some(file) => {
+            let data = fs::read(file).map_err(|e| format!("{}: {}", file, e))?;
+            Font::from_bytes(&data).map_err(|e| format!("{}: {}", file, e))?
+        }
+        None => Font::from_set(match set {
+            Some(set) => set,
+            None => config.get_or("font", FontSet::Yarc)?,
+        }),
+    };
+    if let Some(address) = config.get("font.address") {
+        let digits = address.strip_prefix("0x").or_else(|| address.strip_prefix("0X")).unwrap_or(address);
+        font.address = u16::from_str_radix(digits, 16).map_err(|_| format!("invalid value for font.address: {}", address))?;
+    }
+    Ok(font)
+}
+
+#[cfg(test)]
+#[path = "./config_test.rs"]
+mod tests;
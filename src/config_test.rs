@@ -0,0 +1,215 @@
+use super::*;
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn test_options_rom_only() {
+    let options = Options::parse(&args(&["PONG"])).unwrap();
+    assert_eq!(options.rom, "PONG");
+    assert!(options.config_file.is_none());
+}
+
+#[test]
+fn test_options_config() {
+    let options = Options::parse(&args(&["--config", "my.cfg", "PONG"])).unwrap();
+    assert_eq!(options.rom, "PONG");
+    assert_eq!(options.config_file.as_deref(), Some("my.cfg"));
+}
+
+#[test]
+fn test_options_frontend() {
+    let options = Options::parse(&args(&["PONG", "--frontend", "terminal"])).unwrap();
+    assert_eq!(options.frontend, Frontend::Terminal);
+    assert!(Options::parse(&args(&["PONG", "--frontend", "vga"])).is_err());
+}
+
+#[test]
+fn test_options_script() {
+    let options = Options::parse(&args(&["--script", "bot.rhai", "TETRIS"])).unwrap();
+    assert_eq!(options.script.as_deref(), Some("bot.rhai"));
+    assert!(Options::parse(&args(&["TETRIS"])).unwrap().script.is_none());
+}
+
+#[test]
+fn test_options_cheats() {
+    let options = Options::parse(&args(&["--cheat", "0x2F4=09", "PONG", "--cheat", "300:1"])).unwrap();
+    assert_eq!(options.cheats.len(), 2);
+    assert_eq!(options.cheats[0].address, 0x2F4);
+    assert!(Options::parse(&args(&["PONG", "--cheat", "0x2F4"])).is_err());
+}
+
+#[test]
+fn test_options_netplay() {
+    let options = Options::parse(&args(&["PONG2", "--host", "0.0.0.0:7878", "--keys", "14"])).unwrap();
+    assert_eq!(options.netplay, Some(Role::Host(String::from("0.0.0.0:7878"))));
+    assert_eq!(options.keys, Some(0b1_0010));
+    let options = Options::parse(&args(&["PONG2", "--connect", "localhost:7878"])).unwrap();
+    assert_eq!(options.netplay, Some(Role::Connect(String::from("localhost:7878"))));
+    assert_eq!(options.keys, None);
+
+    assert!(Options::parse(&args(&["PONG2", "--host", "a:1", "--connect", "b:1"])).is_err());
+    assert!(Options::parse(&args(&["PONG2", "--keys", "14"])).is_err());
+    assert!(Options::parse(&args(&["PONG2", "--host", "a:1", "--keys", "1G"])).is_err());
+}
+
+#[test]
+fn test_options_gdb() {
+    let options = Options::parse(&args(&["BLINKY", "--gdb", "1234"])).unwrap();
+    assert_eq!(options.gdb.as_deref(), Some("1234"));
+    assert_eq!(Options::parse(&args(&["BLINKY"])).unwrap().gdb, None);
+    assert!(Options::parse(&args(&["BLINKY", "--gdb"])).is_err());
+}
+
+#[test]
+fn test_options_rpc() {
+    let options = Options::parse(&args(&["BLINKY", "--rpc", "0.0.0.0:9000"])).unwrap();
+    assert_eq!(options.rpc.as_deref(), Some("0.0.0.0:9000"));
+    assert_eq!(Options::parse(&args(&["BLINKY"])).unwrap().rpc, None);
+}
+
+#[test]
+fn test_options_errors() {
+    assert!(Options::parse(&args(&[])).is_err());
+    assert!(Options::parse(&args(&["PONG", "--config"])).is_err());
+    assert!(Options::parse(&args(&["PONG", "--script"])).is_err());
+    assert!(Options::parse(&args(&["--bogus", "PONG"])).is_err());
+    assert!(Options::parse(&args(&["PONG", "TETRIS"])).is_err());
+}
+
+#[test]
+fn test_config_parse() {
+    let config = Config::parse("# comment\n\ncrt.scanlines = 0.5\nshader=lcd\n").unwrap();
+    assert_eq!(config.get("shader"), Some("lcd"));
+    assert_eq!(config.get_or("crt.scanlines", 0.0).unwrap(), 0.5);
+    assert_eq!(config.get_or("crt.bloom", 0.25).unwrap(), 0.25);
+    assert!(config.get_or("shader", 0.0).is_err());
+}
+
+#[test]
+fn test_config_parse_error() {
+    assert!(Config::parse("shader lcd").is_err());
+}
+
+#[test]
+fn test_options_trace() {
+    let options = Options::parse(&args(&["PONG", "--trace", "pong.trace", "--trace-pc", "200-2ff", "--trace-ops", "D,F"])).unwrap();
+    assert_eq!(options.trace.as_deref(), Some("pong.trace"));
+    assert_eq!(options.trace_filter.pcs, Some(0x200..=0x2FF));
+    assert_eq!(options.trace_filter.classes, Some(1 << 0xD | 1 << 0xF));
+    assert_eq!(options.trace_filter.frames, None);
+    assert_eq!(options.trace_format, TraceFormat::Yarc);
+
+    let options = Options::parse(&args(&["PONG", "--trace", "t", "--trace-frames", "600-", "--trace-format", "full"])).unwrap();
+    assert_eq!(options.trace_filter.frames, Some(600..=u64::MAX));
+    assert_eq!(options.trace_format, TraceFormat::Full);
+
+    assert!(Options::parse(&args(&["PONG", "--trace-pc", "200-2FF"])).is_err());
+    assert!(Options::parse(&args(&["PONG", "--trace", "t", "--trace-pc", "2FF-200"])).is_err());
+    assert!(Options::parse(&args(&["PONG", "--trace", "t", "--trace-ops", "G"])).is_err());
+    assert!(Options::parse(&args(&["PONG", "--trace", "t", "--trace-format", "mame"])).is_err());
+}
+
+#[test]
+fn test_options_profile() {
+    assert_eq!(Options::parse(&args(&["PONG", "--profile", "pong"])).unwrap().profile.as_deref(), Some("pong"));
+    assert_eq!(Options::parse(&args(&["PONG"])).unwrap().profile, None);
+}
+
+#[test]
+fn test_quirks() {
+    assert_eq!(quirks(None, &Config::default()), Ok(QuirkPreset::Yarc.quirks()));
+    let config = Config::parse("quirks = vip\nquirks.clip_sprites = false").unwrap();
+    let expected = Quirks { clip_sprites: false, ..QuirkPreset::Vip.quirks() };
+    assert_eq!(quirks(None, &config), Ok(expected));
+    // --quirks wins over the config's preset, but not over single quirks
+    let expected = Quirks { clip_sprites: false, ..QuirkPreset::Schip.quirks() };
+    assert_eq!(quirks(Some(QuirkPreset::Schip), &config), Ok(expected));
+
+    assert_eq!(Options::parse(&args(&["PONG", "--quirks", "xochip"])).unwrap().quirks, Some(QuirkPreset::Xochip));
+    assert!(Options::parse(&args(&["PONG", "--quirks", "chip48"])).is_err());
+    assert!(quirks(None, &Config::parse("quirks.jump_vx = maybe").unwrap()).is_err());
+}
+
+#[test]
+fn test_timing() {
+    assert_eq!(timing(None, &Config::default()), Ok(Timing::Fixed));
+    let config = Config::parse("timing = vip").unwrap();
+    assert_eq!(timing(None, &config), Ok(Timing::Vip));
+    assert_eq!(timing(Some(Timing::Fixed), &config), Ok(Timing::Fixed));
+
+    assert_eq!(Options::parse(&args(&["PONG", "--timing", "vip"])).unwrap().timing, Some(Timing::Vip));
+    assert!(Options::parse(&args(&["PONG", "--timing", "eti660"])).is_err());
+}
+
+#[test]
+fn test_font() {
+    assert_eq!(font(None, None, &Config::default()), Ok(Font::default()));
+    let config = Config::parse("font = vip\nfont.address = 0x50").unwrap();
+    let expected = Font { address: 0x50, ..Font::from_set(FontSet::Vip) };
+    assert_eq!(font(None, None, &config), Ok(expected));
+    // --font wins over the config's font, but keeps its address
+    let expected = Font { address: 0x50, ..Font::from_set(FontSet::Eti660) };
+    assert_eq!(font(Some(FontSet::Eti660), None, &config), Ok(expected));
+    assert!(font(None, None, &Config::parse("font.address = 0x5G").unwrap()).is_err());
+
+    let path = std::env::temp_dir().join(format!("yarc-font-{}", std::process::id()));
+    fs::write(&path, [0x11; 80]).unwrap();
+    let path = path.to_string_lossy().into_owned();
+    let config = Config::parse(&format!("font = vip\nfont.file = {}\nfont.address = 1A0", path)).unwrap();
+    let loaded = font(None, None, &config).unwrap();
+    assert_eq!((loaded.small, loaded.address), ([0x11; 80], 0x1A0));
+    assert_eq!(font(None, Some(&path), &Config::default()).unwrap().small, [0x11; 80]);
+    fs::remove_file(&path).unwrap();
+    assert!(font(None, Some(&path), &Config::default()).is_err());
+
+    assert_eq!(Options::parse(&args(&["PONG", "--font", "dream6800"])).unwrap().font, Some(FontSet::Dream6800));
+    assert!(Options::parse(&args(&["PONG", "--font", "vip", "--font-file", "digits.bin"])).is_err());
+}
+
+#[test]
+fn test_options_tiles() {
+    let options = Options::parse(&args(&["PONG", "--frontend", "sdl", "--tile", "PONG,quirks=vip", "--tile", "TETRIS,timing=vip,quirks=schip"])).unwrap();
+    assert_eq!(options.tiles, [
+        Tile { rom: String::from("PONG"), quirks: Some(QuirkPreset::Vip), timing: None },
+        Tile { rom: String::from("TETRIS"), quirks: Some(QuirkPreset::Schip), timing: Some(Timing::Vip) },
+    ]);
+    assert!(Options::parse(&args(&["PONG"])).unwrap().tiles.is_empty());
+
+    assert!(Options::parse(&args(&["PONG", "--tile", ",quirks=vip"])).is_err());
+    assert!(Options::parse(&args(&["PONG", "--tile", "PONG,speed=2"])).is_err());
+    assert!(Options::parse(&args(&["PONG", "--tile", "PONG,quirks=chip48"])).is_err());
+    assert!(Options::parse(&args(&["PONG", "--tile", "PONG", "--frontend", "terminal"])).is_err());
+    assert!(Options::parse(&args(&["PONG", "--frontend", "sdl", "--tile", "PONG", "--gdb", "1234"])).is_err());
+    assert!(Options::parse(&args(&["PONG", "--frontend", "sdl", "--tile", "PONG", "--cheat", "300=01"])).is_err());
+}
+
+#[test]
+fn test_options_inspector() {
+    assert!(Options::parse(&args(&["PONG", "--frontend", "sdl", "--inspector"])).unwrap().inspector);
+    assert!(!Options::parse(&args(&["PONG"])).unwrap().inspector);
+    assert!(Options::parse(&args(&["PONG", "--inspector", "--frontend", "terminal"])).is_err());
+    assert!(Options::parse(&args(&["PONG", "--frontend", "sdl", "--inspector", "--tile", "PONG"])).is_err());
+    assert!(Options::parse(&args(&["PONG", "--frontend", "sdl", "--inspector", "--host", "4000"])).is_err());
+}
+
+#[test]
+fn test_platform() {
+    let options = Options::parse(&args(&["PONG", "--frontend", "sdl"])).unwrap();
+    assert_eq!(platform(&options, &Config::default()), Ok(Platform::Chip8));
+    let config = Config::parse("platform = hires").unwrap();
+    assert_eq!(platform(&options, &config), Ok(Platform::Hires));
+    let options = Options::parse(&args(&["PONG", "--frontend", "sdl", "--platform", "chip8x"])).unwrap();
+    assert_eq!(platform(&options, &config), Ok(Platform::Chip8x));
+    assert!(platform(&Options::parse(&args(&["PONG"])).unwrap(), &Config::parse("platform = chip48").unwrap()).is_err());
+    assert!(Options::parse(&args(&["PONG", "--platform", "chip48"])).is_err());
+
+    // Every frontend draws any screen size, but tiles are all CHIP-8
+    let options = Options::parse(&args(&["PONG", "--platform", "chip8x", "--frontend", "terminal"])).unwrap();
+    assert_eq!(platform(&options, &Config::default()), Ok(Platform::Chip8x));
+    let options = Options::parse(&args(&["PONG", "--platform", "chip10", "--frontend", "terminal"])).unwrap();
+    assert_eq!(platform(&options, &Config::default()), Ok(Platform::Chip10));
+    let options = Options::parse(&args(&["PONG", "--frontend", "sdl", "--tile", "PONG"])).unwrap();
+    assert!(platform(&options, &Config::parse("platform = hires").unwrap()).is_err());
+}
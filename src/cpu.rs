@@ -1,13 +1,17 @@
 use crate::CHIP8_WIDTH;
 use crate::CHIP8_HEIGHT;
 use crate::CHIP8_RAM;
-use crate::font::FONT_SET;
-
-use rand;
-use rand::Rng;
-
+use crate::font::{Font, FONT_SIZE, MAX_FONT_ADDRESS};
+use crate::framebuffer::Framebuffer;
+use crate::platform::{Colours, ColourModel, Platform};
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+use crate::timing::{self, Timing, FRAME_CYCLES};
+use std::convert::TryInto;
+use std::ops::Range;
+
+#[allow(clippy::upper_case_acronyms)]
 pub struct CPU {
-	opcode: u16,			// opcodes
 	v: [u8; 16],			// registers v0, v1, ..., vF; 8-bit
 	i: u16,					// register i; 16-bit for memory address
 	sound_timer: u8,		// Sound timer; 8-bit
@@ -16,18 +20,76 @@ pub struct CPU {
 	sp: usize,					// Stack pointer. Use usize because will only be used for indexing
 	ram: [u8; CHIP8_RAM],		// RAM, 4kB
 
-	vram: [[u8; CHIP8_WIDTH]; CHIP8_HEIGHT], // Video RAM
-	vram_changed: bool,
+	vram: Framebuffer, // Video RAM, the platform's screen; dirty where the last tick changed it
 	stack: [u16; 16],       
 	keypad: [bool; 16],
 	keypad_waiting: bool,
 	keypad_register: usize, // changed keypad register to usize
+	keypad_held: Option<usize>, // key pressed while Fx0A waits, with key_release: taken when let go
+	rng: Rng,               // random numbers for Cxkk
+	ram_write: Option<Range<usize>>, // RAM written by the last tick, for watchers
+	ram_read: Option<Range<usize>>, // RAM read as data by the last tick, for profilers
+	quirks: Quirks,
+	timing: Timing,
+	cycles: u64,            // VIP machine cycles run so far
+	tick_cycles: u32,       // of them, run by the last tick
+	frame_cycles: u32,      // run since the last vblank(), with VIP timing
+	drawn: bool,            // Dxyn has drawn since the last vblank(), with VIP timing
+	font: Font,             // digits Fx29 and Fx30 point at
+	platform: Platform,
+	colours: Colours,       // CHIP-8X's colours, set by 02A0 and BxyN
+}
+
+// Everything but memory and the screen, e.g. for debuggers and bindings
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Registers {
+	pub v: [u8; 16],
+	pub i: u16,
+	pub pc: u16,
+	pub sp: u8,
+	pub delay_timer: u8,
+	pub sound_timer: u8,
+	pub stack: [u16; 16],
+}
+
+const STACK_SIZE: usize = 16;
+
+// Save state layout: magic, version, registers, keypad wait, RNG, RAM, VRAM, then CHIP-8X's
+// background and strip colours on that platform. VRAM is just the platform's screen.
+// The keypad wait byte is 0 when not waiting, 1 when waiting and 2 + the key when it's held.
+const STATE_MAGIC: &[u8; 4] = b"YARC";
+const STATE_VERSION: u8 = 1;
+pub const STATE_SIZE: usize = 4 + 1 + 16 + 2 + 2 + 3 + 32 + 2 + 8 + CHIP8_RAM + CHIP8_WIDTH * CHIP8_HEIGHT; // on CHIP-8
+const COLOURS_SIZE: usize = 1 + 8 * 32;
+
+// Walks a save state whose length has already been checked
+struct StateReader<'a> {
+	state: &'a [u8],
+	position: usize,
+}
+
+impl<'a> StateReader<'a> {
+	fn bytes(&mut self, count: usize) -> &'a [u8] {
+		let bytes = &self.state[self.position..self.position + count];
+		self.position += count;
+		bytes
+	}
+
+	fn u8(&mut self) -> u8 {
+		self.bytes(1)[0]
+	}
+
+	fn u16(&mut self) -> u16 {
+		u16::from_be_bytes([self.u8(), self.u8()])
+	}
 }
 
 pub struct OutputState<'a> {
-	pub vram: &'a [[u8; CHIP8_WIDTH]; CHIP8_HEIGHT], // Check lifetimes
-	pub vram_changed: bool,
+	pub vram: &'a Framebuffer, // Check lifetimes
+	pub vram_changed: bool, // the tick drew, cleared or recoloured the screen; vram.dirty() says where, until the next tick
 	pub beep: bool,
+	pub cycles: u32, // VIP machine cycles the tick took; 0 if it ran no instruction
+	pub total_cycles: u64,
 }
 
 
@@ -39,86 +101,402 @@ enum ProgramCounter {
 }
 
 
+impl Default for CPU {
+	fn default() -> Self {
+		CPU::new()
+	}
+}
+
 impl CPU {
     pub fn new() -> Self {
-		let mut ram = [0u8; CHIP8_RAM];
+		CPU::with_rng(Rng::from_entropy())
+	}
 
-		// Load RAM with font_set
-		for i in 0..FONT_SET.len() {
-			ram[i] = FONT_SET[i];
-		}
+	// Same random numbers for the same seed, e.g. to replay a game exactly
+	pub fn with_seed(seed: u64) -> Self {
+		CPU::with_rng(Rng::new(seed))
+	}
 
-		CPU {
-	    	opcode: 0,
+	fn with_rng(rng: Rng) -> Self {
+		let ram = [0u8; CHIP8_RAM];
+
+		let mut cpu = CPU {
 	    	v: [0; 16],
 	    	i: 0x200,
 	    	sound_timer: 0,
 	    	delay_timer: 0,
 	    	pc: 0x200,
 	    	sp: 0,
-	    	ram: ram, // changed this from memory to ram
-	    	vram: [[0; CHIP8_WIDTH]; CHIP8_HEIGHT],
-			vram_changed: false,
+	    	ram, // changed this from memory to ram
+	    	vram: Framebuffer::new(CHIP8_WIDTH, CHIP8_HEIGHT, 1),
 			stack: [0; 16],
 			keypad: [false; 16],
 			keypad_waiting: false,
 			keypad_register: 0,
+			keypad_held: None,
+			rng,
+			ram_write: None,
+			ram_read: None,
+			quirks: Quirks::default(),
+			timing: Timing::default(),
+			cycles: 0,
+			tick_cycles: 0,
+			frame_cycles: 0,
+			drawn: false,
+			font: Font::default(),
+			platform: Platform::default(),
+			colours: Colours::default(),
+		};
+		cpu.load_font();
+		cpu
+	}
+
+	// Back to how the CPU started, ready for load_program(), with the same platform, quirks, timing
+	// and font. The random numbers carry on instead of being reseeded, so seeded games stay in step.
+	pub fn reset(&mut self) {
+		let rng = std::mem::replace(&mut self.rng, Rng::new(0));
+		let mut cpu = CPU::with_rng(rng);
+		cpu.set_platform(self.platform);
+		cpu.set_quirks(self.quirks);
+		cpu.set_timing(self.timing);
+		cpu.font = self.font.clone(); // checked when it was set
+		cpu.load_font();
+		*self = cpu;
+	}
+
+	pub fn font(&self) -> &Font {
+		&self.font
+	}
+
+	// YARC's digits at address 0 by default. The old ones are left in RAM, as the new ones may not
+	// cover them, and programs don't expect that memory to change under them.
+	pub fn set_font(&mut self, font: &Font) -> Result<(), String> {
+		if font.address > MAX_FONT_ADDRESS {
+			return Err(format!("A font can't be loaded above {:#05X}", MAX_FONT_ADDRESS));
 		}
+		self.font = font.clone();
+		self.load_font();
+		Ok(())
 	}
 
-	pub fn load_program(&mut self, program: &[u8]) { // Changed here
-		let mut data = Vec::new(); // changed from vec![0;0x200] to new Vec
-		for byte in program {
-			data.push(byte);
+	// Small digits at the font's address, big ones right after
+	fn load_font(&mut self) {
+		let small = self.font.address as usize;
+		let big = small + FONT_SIZE;
+		self.ram[small..big].copy_from_slice(&self.font.small);
+		self.ram[big..big + self.font.big.len()].copy_from_slice(&self.font.big);
+	}
+
+	pub fn platform(&self) -> Platform {
+		self.platform
+	}
+
+	// CHIP-8 by default. Call before load_program(): it moves the program counter to where the
+	// platform starts programs, and clears the screen.
+	pub fn set_platform(&mut self, platform: Platform) {
+		self.platform = platform;
+		self.pc = platform.start_address();
+		self.i = platform.load_address();
+		self.vram = Framebuffer::new(platform.width(), platform.height(), 1);
+		self.colours = Colours::default();
+	}
+
+	// Colours of the screen, on platforms that have them
+	pub fn colours(&self) -> Option<&Colours> {
+		match self.platform.colour_model() {
+			ColourModel::Mono => None,
+			ColourModel::Zones => Some(&self.colours),
 		}
-		for (i, &byte) in data.iter().enumerate() {
-			// TODO: Implement a check: address should be less than 0x1000
-			self.ram[0x200 + i] = *byte;  //programs start at 0x200
+	}
+
+	// Bytes that don't fit in RAM are left out
+	pub fn load_program(&mut self, program: &[u8]) { // Changed here
+		let start = self.platform.load_address() as usize;
+		for (address, &byte) in (start..CHIP8_RAM).zip(program) {
+			self.ram[address] = byte;  //programs start at 0x200 on most platforms
 		}
 	}
 
 	
     // Needs the most debugging
-	pub fn tick(&mut self, keypad: [bool; 16]) -> OutputState {
+	pub fn tick(&mut self, keypad: [bool; 16]) -> OutputState<'_> {
 		// Initialisation
 		self.keypad = keypad;
-		self.vram_changed = false;
-
-		// Each tick, either (input from keypad) or (decrement timer & do opcode)
+		self.vram.mark_clean();
+		self.ram_write = None;
+		self.ram_read = None;
+		self.tick_cycles = 0;
+
+		// The timers run even while Fx0A waits; with VIP timing they count down in vblank() instead
+		if self.timing == Timing::Fixed {
+			self.count_down_timers();
+		}
+		// Each tick, either (input from keypad) or (do opcode)
 		if self.keypad_waiting {
-            println!("Waiting for keyboard");
-			for i in 0..keypad.len() {
-				if keypad[i] {
-					self.keypad_waiting = false;				// Stop the keypad_waiting
-					self.v[self.keypad_register] = i as u8;		// Put the keypad entry into register vX
-					break;
-				}
-			}
+			self.wait_for_key();
+		} else if self.waiting_for_vblank() {
+			// Nothing more runs until the next vblank()
 		} else {
-			if self.delay_timer > 0 {
-				// If delay timer is not zero, decrement (until zero)
-				self.delay_timer -= 1
-			}
-			if self.sound_timer > 0 {
-				// If sound timer is not zero, decrement (until zero)
-				self.sound_timer -= 1
-			}
 			let opcode = self.get_opcode();
 			self.run_opcode(opcode);
 		}
 
 		OutputState {
 			vram: &self.vram,
-			vram_changed: self.vram_changed,
-			beep: self.sound_timer > 0
+			vram_changed: self.vram.dirty().is_some(),
+			beep: self.sound_timer > 0,
+			cycles: self.tick_cycles,
+			total_cycles: self.cycles,
 		}
 	}
 
+	// Fx0A takes the first key pressed, or with key_release the first one let go again
+	fn wait_for_key(&mut self) {
+		match self.keypad_held {
+			Some(key) if !self.keypad[key] => self.take_key(key),
+			Some(_) => {}
+			None => {
+				if let Some(key) = (0..16).find(|&key| self.keypad[key]) {
+					if self.quirks.key_release {
+						self.keypad_held = Some(key);
+					} else {
+						self.take_key(key);
+					}
+				}
+			}
+		}
+	}
+
+	fn take_key(&mut self, key: usize) {
+		self.v[self.keypad_register] = key as u8;		// Put the keypad entry into register vX
+		self.keypad_waiting = false;
+		self.keypad_held = None;
+	}
+
+	fn count_down_timers(&mut self) {
+		if self.delay_timer > 0 {
+			// If delay timer is not zero, decrement (until zero)
+			self.delay_timer -= 1
+		}
+		if self.sound_timer > 0 {
+			// If sound timer is not zero, decrement (until zero)
+			self.sound_timer -= 1
+		}
+	}
+
+	// The 60 Hz vertical blank, which only VIP timing needs told about: the timers count down,
+	// and the next frame's cycles and draw are handed out. Cycles overspent, e.g. by 00E0, come
+	// out of the next frame.
+	pub fn vblank(&mut self) {
+		if self.timing == Timing::Vip {
+			self.count_down_timers();
+			self.frame_cycles = self.frame_cycles.saturating_sub(FRAME_CYCLES);
+			self.drawn = false;
+		}
+	}
+
+	// True while VIP timing holds the CPU until the next vblank(), because the frame's cycles are
+	// spent or the next instruction is a second Dxyn this frame; the next tick will not run an
+	// instruction
+	pub fn waiting_for_vblank(&self) -> bool {
+		self.timing == Timing::Vip
+			&& (self.frame_cycles >= FRAME_CYCLES || (self.drawn && self.get_opcode() >> 12 == 0xD))
+	}
+
+	pub fn timing(&self) -> Timing {
+		self.timing
+	}
+
+	// Fixed timing by default
+	pub fn set_timing(&mut self, timing: Timing) {
+		self.timing = timing;
+		self.frame_cycles = 0;
+		self.drawn = false;
+	}
+
+	// Current screen, for front-ends that read it outside of tick()
+	pub fn vram(&self) -> &Framebuffer {
+		&self.vram
+	}
+
+	pub fn quirks(&self) -> Quirks {
+		self.quirks
+	}
+
+	// Which interpreter to behave like; YARC's own behaviour by default
+	pub fn set_quirks(&mut self, quirks: Quirks) {
+		self.quirks = quirks;
+	}
+
+	// VIP machine cycles the last tick took, counted whatever the timing; 0 if it ran no instruction
+	pub fn last_tick_cycles(&self) -> u32 {
+		self.tick_cycles
+	}
+
+	// Addresses written by the last tick (Fx33 and Fx55 are the only instructions writing RAM)
+	pub fn last_ram_write(&self) -> Option<Range<usize>> {
+		self.ram_write.clone()
+	}
+
+	// Addresses read as data by the last tick: sprites drawn by Dxyn and registers loaded by Fx65
+	pub fn last_ram_read(&self) -> Option<Range<usize>> {
+		self.ram_read.clone()
+	}
+
+	// True while Fx0A waits for a key, e.g. to show the player the game wants one; the next tick
+	// will not run an instruction, though the timers keep running
+	pub fn waiting_for_key(&self) -> bool {
+		self.keypad_waiting
+	}
+
+	pub fn ram(&self) -> &[u8; CHIP8_RAM] {
+		&self.ram
+	}
+
+	// For tools that poke memory, e.g. bindings or cheats
+	pub fn ram_mut(&mut self) -> &mut [u8; CHIP8_RAM] {
+		&mut self.ram
+	}
+
+	pub fn registers(&self) -> Registers {
+		Registers {
+			v: self.v,
+			i: self.i,
+			pc: self.pc,
+			sp: self.sp as u8,
+			delay_timer: self.delay_timer,
+			sound_timer: self.sound_timer,
+			stack: self.stack,
+		}
+	}
+
+	pub fn set_registers(&mut self, registers: &Registers) {
+		self.v = registers.v;
+		self.i = registers.i;
+		self.pc = registers.pc;
+		self.sp = (registers.sp as usize).min(self.stack.len());
+		self.delay_timer = registers.delay_timer;
+		self.sound_timer = registers.sound_timer;
+		self.stack = registers.stack;
+	}
+
+	// Length of a save state on this CPU's platform
+	pub fn state_size(&self) -> usize {
+		let (width, height) = (self.platform.width(), self.platform.height());
+		let colours = if self.colours().is_some() { COLOURS_SIZE } else { 0 };
+		STATE_SIZE - CHIP8_WIDTH * CHIP8_HEIGHT + width * height + colours
+	}
+
+	// Snapshot of the whole machine (apart from the keys held), state_size() bytes long. The
+	// platform isn't saved, so the state only loads on a CPU set to the same one.
+	pub fn save_state(&self) -> Vec<u8> {
+		let mut state = Vec::with_capacity(self.state_size());
+		state.extend_from_slice(STATE_MAGIC);
+		state.push(STATE_VERSION);
+		state.extend_from_slice(&self.v);
+		state.extend_from_slice(&self.i.to_be_bytes());
+		state.extend_from_slice(&self.pc.to_be_bytes());
+		state.push(self.sp as u8);
+		state.push(self.delay_timer);
+		state.push(self.sound_timer);
+		for address in self.stack.iter() {
+			state.extend_from_slice(&address.to_be_bytes());
+		}
+		state.push(match (self.keypad_waiting, self.keypad_held) {
+			(false, _) => 0,
+			(true, None) => 1,
+			(true, Some(key)) => 2 + key as u8,
+		});
+		state.push(self.keypad_register as u8);
+		state.extend_from_slice(&self.rng.state().to_be_bytes());
+		state.extend_from_slice(&self.ram);
+		state.extend_from_slice(self.vram.pixels());
+		if let Some(colours) = self.colours() {
+			state.push(colours.background);
+			for row in colours.strips.iter() {
+				state.extend_from_slice(row);
+			}
+		}
+		state
+	}
+
+	// Restores a save_state() snapshot. On error the CPU is left untouched.
+	pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+		if state.len() != self.state_size() {
+			return Err(format!("Save state should be {} bytes, not {}", self.state_size(), state.len()));
+		}
+		if &state[..STATE_MAGIC.len()] != STATE_MAGIC {
+			return Err("Not a YARC save state".to_string());
+		}
+		let mut reader = StateReader { state, position: STATE_MAGIC.len() };
+		let version = reader.u8();
+		if version != STATE_VERSION {
+			return Err(format!("Unsupported save state version {}", version));
+		}
+
+		let mut v = [0; 16];
+		v.copy_from_slice(reader.bytes(16));
+		let i = reader.u16();
+		let pc = reader.u16();
+		let sp = reader.u8() as usize;
+		let delay_timer = reader.u8();
+		let sound_timer = reader.u8();
+		let mut stack = [0; 16];
+		for address in stack.iter_mut() {
+			*address = reader.u16();
+		}
+		let (keypad_waiting, keypad_held) = match reader.u8() {
+			0 => (false, None),
+			1 => (true, None),
+			wait => (true, Some(wait as usize - 2)),
+		};
+		let keypad_register = reader.u8() as usize;
+		let rng = Rng::from_state(u64::from_be_bytes(reader.bytes(8).try_into().unwrap()));
+		if sp > stack.len() || keypad_register >= v.len() || keypad_held.is_some_and(|key| key >= 16) {
+			return Err("Corrupt save state".to_string());
+		}
+
+		self.v = v;
+		self.i = i;
+		self.pc = pc;
+		self.sp = sp;
+		self.delay_timer = delay_timer;
+		self.sound_timer = sound_timer;
+		self.stack = stack;
+		self.keypad_waiting = keypad_waiting;
+		self.keypad_register = keypad_register;
+		self.keypad_held = keypad_held;
+		self.rng = rng;
+		self.ram.copy_from_slice(reader.bytes(CHIP8_RAM));
+		let pixels = reader.bytes(self.vram.pixels().len());
+		self.vram.load(pixels); // all of it dirty: what was on screen is stale
+		if self.colours().is_some() {
+			self.colours.background = reader.u8() % 4;
+			for row in self.colours.strips.iter_mut() {
+				for (strip, &colour) in row.iter_mut().zip(reader.bytes(8)) {
+					*strip = colour & 7;
+				}
+			}
+		}
+		Ok(())
+	}
+
 	// Gets opcode from RAM; pc points to the opcode
 	// Function to merge 2 bytes into u16
 	fn get_opcode(&self) -> u16 {
 		// cast to u16 as ram[i] is u8
-		(self.ram[self.pc as usize] as u16) << 8 | (self.ram[(self.pc + 1) as usize] as u16)
+		(self.ram[ram_address(self.pc as usize)] as u16) << 8 | (self.ram[ram_address(self.pc as usize + 1)] as u16)
+	}
+
+	// Address `offset` bytes past I; like the program counter, it wraps around the end of RAM
+	fn i_address(&self, offset: usize) -> usize {
+		ram_address(self.i as usize + offset)
+	}
+
+	// RAM used at I by an instruction, for last_ram_write() and last_ram_read(); what wrapped round isn't included
+	fn i_range(&self, length: usize) -> Range<usize> {
+		let start = self.i_address(0);
+		start..(start + length).min(CHIP8_RAM)
 	}
 
 
@@ -134,9 +512,9 @@ impl CPU {
 		// Split the opcode into 4 parts of 4 bits
 		// u8 is the lowest in Rust
 		let parts = (
-			(opcode & 0xF000) >> 12 as u8,
-			(opcode & 0x0F00) >> 8 as u8,
-			(opcode & 0x00F0) >> 4 as u8,
+			(opcode & 0xF000) >> 12,
+			(opcode & 0x0F00) >> 8,
+			(opcode & 0x00F0) >> 4,
 			(opcode & 0x000F) as u8,
 		);
         
@@ -146,16 +524,21 @@ impl CPU {
 		let y = parts.2 as usize;
 		let n = parts.3 as usize;
 		let kk = (parts.2 << 4) as u8 | parts.3;
-		let nnn = ((parts.1 as u16) << 8) | ((parts.2 as u16) << 4) | (parts.3 as u16);
+		let nnn = opcode & 0x0FFF;
+		let vx = self.v[x];
 
+		let platform = self.platform;
 		let pc_change = match parts {
 			(0x00, 0x00, 0x0e, 0x00) => self.op_00e0(),
+			(0x00, 0x02, 0x03, 0x00) if platform == Platform::Hires => self.op_00e0(),
+			(0x00, 0x02, 0x0a, 0x00) if platform == Platform::Chip8x => self.op_02a0(),
             (0x00, 0x00, 0x0e, 0x0e) => self.op_00ee(),
             (0x01, _, _, _) => self.op_1nnn(nnn),
             (0x02, _, _, _) => self.op_2nnn(nnn),
             (0x03, _, _, _) => self.op_3xkk(x, kk),
             (0x04, _, _, _) => self.op_4xkk(x, kk),
             (0x05, _, _, 0x00) => self.op_5xy0(x, y),
+            (0x05, _, _, 0x01) if platform == Platform::Chip8x => self.op_5xy1(x, y),
             (0x06, _, _, _) => self.op_6xkk(x, kk),
             (0x07, _, _, _) => self.op_7xkk(x, kk),
             (0x08, _, _, 0x00) => self.op_8xy0(x, y),
@@ -164,12 +547,13 @@ impl CPU {
             (0x08, _, _, 0x03) => self.op_8xy3(x, y),
             (0x08, _, _, 0x04) => self.op_8xy4(x, y),
             (0x08, _, _, 0x05) => self.op_8xy5(x, y),
-            (0x08, _, _, 0x06) => self.op_8xy6(x),
+            (0x08, _, _, 0x06) => self.op_8xy6(x, y),
             (0x08, _, _, 0x07) => self.op_8xy7(x, y),
-            (0x08, _, _, 0x0e) => self.op_8xye(x),
+            (0x08, _, _, 0x0e) => self.op_8xye(x, y),
             (0x09, _, _, 0x00) => self.op_9xy0(x, y),
             (0x0a, _, _, _) => self.op_annn(nnn),
-            (0x0b, _, _, _) => self.op_bnnn(nnn),
+            (0x0b, _, _, _) if platform == Platform::Chip8x => self.op_bxyn(x, y, n),
+            (0x0b, _, _, _) => self.op_bnnn(x, nnn),
             (0x0c, _, _, _) => self.op_cxkk(x, kk),
             (0x0d, _, _, _) => self.op_dxyn(x, y, n),
             (0x0e, _, 0x09, 0x0e) => self.op_ex9e(x),
@@ -180,36 +564,49 @@ impl CPU {
             (0x0f, _, 0x01, 0x08) => self.op_fx18(x),
             (0x0f, _, 0x01, 0x0e) => self.op_fx1e(x),
             (0x0f, _, 0x02, 0x09) => self.op_fx29(x),
+            (0x0f, _, 0x03, 0x00) => self.op_fx30(x),
             (0x0f, _, 0x03, 0x03) => self.op_fx33(x),
             (0x0f, _, 0x05, 0x05) => self.op_fx55(x),
             (0x0f, _, 0x06, 0x05) => self.op_fx65(x),
             _ => ProgramCounter::Next,
 		};
 
+		let cycles = timing::vip_cycles(opcode, vx, matches!(pc_change, ProgramCounter::Skip));
+		self.tick_cycles = cycles;
+		self.cycles += cycles as u64;
+		if self.timing == Timing::Vip {
+			self.frame_cycles += cycles;
+			self.drawn |= parts.0 == 0x0d;
+		}
+
 		match pc_change {
 			// Opcode size: 2. Might want to change to 
-			ProgramCounter::Next => self.pc += 2,
-			ProgramCounter::Skip => self.pc += 4,
-			ProgramCounter::Jump(addr) => self.pc = addr,
+			ProgramCounter::Next => self.pc = ram_address(self.pc as usize + 2) as u16,
+			ProgramCounter::Skip => self.pc = ram_address(self.pc as usize + 4) as u16,
+			ProgramCounter::Jump(addr) => self.pc = ram_address(addr as usize) as u16,
         }
 	}
 
 	// OPCODES HERE
 	// OOEO: CLS -> Clear display
+	// 0230 on HIRES CHIP-8
 	fn op_00e0(&mut self) -> ProgramCounter {
-		for a in 0..CHIP8_HEIGHT {
-			for b in 0..CHIP8_WIDTH {
-				self.vram[a][b] = 0;
-			}
-		}
-		self.vram_changed = true;
+		self.vram.clear();
+		ProgramCounter::Next
+	}
+
+	// 02A0: CHIP-8X -> Step the background colour to the next one: blue, black, green, red
+	fn op_02a0(&mut self) -> ProgramCounter {
+		self.colours.step_background();
+		self.vram.mark_dirty();
 		ProgramCounter::Next
 	}
 
 	// 00EE: RET -> Return from subroutine
 	// The interpreter sets the program counter to the address at the top of the stack, then subtracts 1 from the stack pointer.
+	// The stack is circular, so returning with nothing on it pops the last of the 16 entries.
 	fn op_00ee(&mut self) -> ProgramCounter {
-		self.sp -= 1;
+		self.sp = (self.sp + STACK_SIZE - 1) % STACK_SIZE;
 		ProgramCounter::Jump(self.stack[self.sp])
 	}
 
@@ -221,9 +618,10 @@ impl CPU {
 
 	// 2nnn: CALL addr -> Call subroutine at nnn.
 	// The interpreter increments the stack pointer, then puts the current PC on the top of the stack. The PC is then set to nnn.
+	// Past 16 calls deep it overwrites the oldest return address.
 	fn op_2nnn(&mut self, nnn: u16) -> ProgramCounter {
-		self.stack[self.sp] = self.pc + 2; //OPCODE_SIZE
-		self.sp += 1;
+		self.stack[self.sp % STACK_SIZE] = ram_address(self.pc as usize + 2) as u16; //OPCODE_SIZE
+		self.sp = (self.sp + 1) % STACK_SIZE;
 		ProgramCounter::Jump(nnn)
 	}
 
@@ -257,6 +655,13 @@ impl CPU {
 		}
 	}
 
+	// 5xy1: CHIP-8X -> Set Vx = Vx + Vy, each nibble on its own and kept to 0-7, as for the
+	// column and row pairs BxyN takes
+	fn op_5xy1(&mut self, x: usize, y: usize) -> ProgramCounter {
+		self.v[x] = (self.v[x] & 0x77).wrapping_add(self.v[y] & 0x77) & 0x77;
+		ProgramCounter::Next
+	}
+
 	// 6xkk - LD Vx, byte -> Set Vx = kk.
 	// The interpreter puts the value kk into register Vx.
 	fn op_6xkk(&mut self, x: usize, kk: u8) -> ProgramCounter {
@@ -277,6 +682,9 @@ impl CPU {
         ProgramCounter::Next
 	}
 
+	// The 8xyN instructions read Vx and Vy before writing anything, and the ones setting VF write it
+	// after the result, as the COSMAC VIP does: with VF as Vx, VF ends up holding the flag.
+
 	// 8xy0 - LD Vx, Vy -> Set Vx = Vy.
 	// Stores the value of register Vy in register Vx.
 	fn op_8xy0(&mut self, x: usize, y: usize) -> ProgramCounter {
@@ -288,21 +696,30 @@ impl CPU {
 	// Performs a bitwise OR on the values of Vx and Vy, then stores the result in Vx. 
 	fn op_8xy1(&mut self, x: usize, y: usize) -> ProgramCounter {
 		// TODO: Might have error due to borrowing
-		self.v[x] = self.v[x] | self.v[y];
+		self.v[x] |= self.v[y];
+		if self.quirks.vf_reset {
+			self.v[0xF] = 0;
+		}
 		ProgramCounter::Next
 	}
 
 	// 8xy2 - AND Vx, Vy -> Set Vx = Vx AND Vy.
 	// Performs a bitwise AND on the values of Vx and Vy, then stores the result in Vx. 
 	fn op_8xy2(&mut self, x: usize, y: usize) -> ProgramCounter {
-		self.v[x] = self.v[x] & self.v[y];
+		self.v[x] &= self.v[y];
+		if self.quirks.vf_reset {
+			self.v[0xF] = 0;
+		}
 		ProgramCounter::Next
 	}
 
 	// 8xy3 - XOR Vx, Vy -> Set Vx = Vx XOR Vy.
 	// Performs a bitwise exclusive OR on the values of Vx and Vy, then stores the result in Vx. 
 	fn op_8xy3(&mut self, x: usize, y: usize) -> ProgramCounter {
-		self.v[x] = self.v[x] ^ self.v[y];
+		self.v[x] ^= self.v[y];
+		if self.quirks.vf_reset {
+			self.v[0xF] = 0;
+		}
 		ProgramCounter::Next
 	}
 
@@ -320,34 +737,38 @@ impl CPU {
 	}
 
 	// 8xy5 - SUB Vx, Vy -> Set Vx = Vx - Vy, set VF = NOT borrow.
-	// If Vx > Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from Vx, and the results stored in Vx.
+	// If Vx >= Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from Vx, and the results stored in Vx.
 	fn op_8xy5(&mut self, x: usize, y: usize) -> ProgramCounter {
-		self.v[0xF] = if self.v[x] > self.v[y] {1} else {0};
-		self.v[x] = self.v[x].wrapping_sub(self.v[y]);
+		let (vx, vy) = (self.v[x], self.v[y]);
+		self.v[x] = vx.wrapping_sub(vy);
+		self.v[0xF] = if vx >= vy {1} else {0};
 		ProgramCounter::Next
 	}
 
 	// 8xy6 - SHR Vx {, Vy} -> Set Vx = Vx SHR 1.
 	// If the least-significant bit of Vx is 1, then VF is set to 1, otherwise 0. Then Vx is divided by 2.
-	fn op_8xy6(&mut self, x: usize) -> ProgramCounter {
-		self.v[0xF] = self.v[x] & 1;
-		self.v[x] >>= 1;
+	fn op_8xy6(&mut self, x: usize, y: usize) -> ProgramCounter {
+		let value = if self.quirks.shift_vy { self.v[y] } else { self.v[x] };
+		self.v[x] = value >> 1;
+		self.v[0xF] = value & 1;
 		ProgramCounter::Next
 	}
 
 	// 8xy7 - SUBN Vx, Vy -> Set Vx = Vy - Vx, set VF = NOT borrow.
-	// If Vy > Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from Vy, and the results stored in Vx.
+	// If Vy >= Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from Vy, and the results stored in Vx.
 	fn op_8xy7(&mut self, x: usize, y: usize) -> ProgramCounter {
-		self.v[0xF] = if self.v[y] > self.v[x] {1} else {0};
-		self.v[x] = self.v[y].wrapping_sub(self.v[x]);
+		let (vx, vy) = (self.v[x], self.v[y]);
+		self.v[x] = vy.wrapping_sub(vx);
+		self.v[0xF] = if vy >= vx {1} else {0};
 		ProgramCounter::Next
 	}
 
 	// 8xyE - SHL Vx {, Vy} -> Set Vx = Vx SHL 1.
 	// If the most-significant bit of Vx is 1, then VF is set to 1, otherwise to 0. Then Vx is multiplied by 2.
-	fn op_8xye(&mut self, x: usize) -> ProgramCounter {
-		self.v[0xF] = (self.v[x] & 0x80) >> 7; // TODO: Change binary to Hexadecimal for uniformity
-		self.v[x] = self.v[x] << 1;
+	fn op_8xye(&mut self, x: usize, y: usize) -> ProgramCounter {
+		let value = if self.quirks.shift_vy { self.v[y] } else { self.v[x] };
+		self.v[x] = value << 1;
+		self.v[0xF] = (value & 0x80) >> 7; // TODO: Change binary to Hexadecimal for uniformity
 		ProgramCounter::Next
 	}
 
@@ -370,38 +791,65 @@ impl CPU {
 
 
 	// Bnnn - JP V0, addr -> Jump to location nnn + V0.
-	// The program counter is set to nnn plus the value of V0.
-	fn op_bnnn(&mut self, nnn: u16) -> ProgramCounter {
-		ProgramCounter::Jump(nnn + (self.v[0] as u16))
+	// The program counter is set to nnn plus the value of V0 (Vx with the jump_vx quirk).
+	fn op_bnnn(&mut self, x: usize, nnn: u16) -> ProgramCounter {
+		let offset = if self.quirks.jump_vx { self.v[x] } else { self.v[0] };
+		ProgramCounter::Jump(nnn + (offset as u16))
 	}
 
 
+	// BxyN: CHIP-8X -> Set the foreground colour of part of the screen to Vy.
+	// With N = 0, Vx and Vx+1 give the left column and the top row of 8x4 zones in their low
+	// nibbles, and one less than the width and height in their high ones.
+	// Otherwise N rows of pixels from row Vx+1 are coloured, in column Vx of 8 pixel wide ones.
+	fn op_bxyn(&mut self, x: usize, y: usize, n: usize) -> ProgramCounter {
+		let (horizontal, vertical) = (self.v[x] as usize, self.v[(x + 1) % 16] as usize);
+		let colour = self.v[y];
+		if n == 0 {
+			let (width, height) = ((horizontal >> 4) + 1, (vertical >> 4) + 1);
+			self.colours.paint(horizontal & 0xF, (vertical & 0xF) * 4, width, height * 4, colour);
+		} else {
+			self.colours.paint(horizontal & 0xF, vertical, 1, n, colour);
+		}
+		self.vram.mark_dirty();
+		ProgramCounter::Next
+	}
+
 	// Cxkk - RND Vx, byte
 	// Set Vx = random byte AND kk.
 	// The interpreter generates a random number from 0 to 255, 
 	// which is then ANDed with the value kk. The results are stored in Vx.
 	fn op_cxkk(&mut self, x: usize, kk: u8) -> ProgramCounter {
-		let mut rng = rand::thread_rng();
-		self.v[x] = rng.gen::<u8>() & kk;
+		self.v[x] = self.rng.next_u8() & kk;
 		ProgramCounter::Next
 	}
 
 	// Dxyn - DRW Vx, Vy, nibble
 	// Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
     fn op_dxyn(&mut self, x: usize, y: usize, n: usize) -> ProgramCounter {
+        // The sprite starts on screen either way; with clip_sprites the rest of it may not
+        let (width, height) = self.vram.size();
+        let (left, top) = (self.v[x] as usize % width, self.v[y] as usize % height);
         self.v[0x0f] = 0;
         for byte in 0..n { // check each byte in memory
+            if self.quirks.clip_sprites && top + byte >= height {
+                break;
+            }
             // % operator allows wrapping around screen
-            let y = (self.v[y] as usize + byte) % CHIP8_HEIGHT;
+            let y = (top + byte) % height;
             for bit in 0..8 { 
                 // Check each bit in a byte
-                let x = (self.v[x] as usize + bit) % CHIP8_WIDTH;
-                let color = (self.ram[self.i as usize + byte] >> (7 - bit)) & 1;
-                self.v[0x0f] |= color & self.vram[y][x];
-                self.vram[y][x] ^= color;
+                if self.quirks.clip_sprites && left + bit >= width {
+                    break;
+                }
+                let x = (left + bit) % width;
+                let color = (self.ram[self.i_address(byte)] >> (7 - bit)) & 1;
+                if self.vram.xor(x, y, color) {
+                    self.v[0x0f] = 1;
+                }
             }
         }
-        self.vram_changed = true;
+        self.ram_read = Some(self.i_range(n));
         ProgramCounter::Next
     }    
 
@@ -411,7 +859,7 @@ impl CPU {
 	// Ex9E - SKP Vx
 	// Skip next instruction if key with the value of Vx is pressed.
     fn op_ex9e(&mut self, x: usize) -> ProgramCounter { 
-        let key = self.v[x];
+        let key = self.v[x] & 0xF; // only the low digit counts
 
         if self.keypad[key as usize] {
             ProgramCounter::Skip
@@ -423,7 +871,7 @@ impl CPU {
 	// ExA1 - SKNP Vx
 	// Skip next instruction if key with the value of Vx is not pressed.
     fn op_exa1(&mut self, x: usize) -> ProgramCounter {
-        let key = self.v[x];
+        let key = self.v[x] & 0xF;
 
         if !self.keypad[key as usize] {
             ProgramCounter::Skip
@@ -444,6 +892,7 @@ impl CPU {
     fn op_fx0a(&mut self, x: usize) -> ProgramCounter {
         self.keypad_waiting = true;
         self.keypad_register = x;
+        self.keypad_held = None;
         ProgramCounter::Next    
     }
 
@@ -463,7 +912,7 @@ impl CPU {
     // Fx1E - ADD I, Vx
     // Set I = I + Vx.
     fn op_fx1e(&mut self, x: usize) -> ProgramCounter {
-        self.i += self.v[x] as u16;
+        self.i = self.i.wrapping_add(self.v[x] as u16);
         self.v[0x0f] = if self.i > 0x0F00 { 1 } else { 0 };
         ProgramCounter::Next
     }
@@ -471,7 +920,14 @@ impl CPU {
     // Fx29 - LD F, Vx
     // Set I = location of sprite for digit Vx.
     fn op_fx29(&mut self, x: usize) -> ProgramCounter {
-        self.i = (self.v[x] as u16) * 5; // position of any digit Vx lies at fontset[Vx * 5]
+        self.i = self.font.address + (self.v[x] as u16) * 5; // position of any digit Vx lies at fontset[Vx * 5]
+        ProgramCounter::Next
+    }
+
+    // Fx30 - LD HF, Vx
+    // Set I = location of SUPER-CHIP's 8x10 sprite for digit Vx.
+    fn op_fx30(&mut self, x: usize) -> ProgramCounter {
+        self.i = self.font.address + FONT_SIZE as u16 + (self.v[x] as u16) * 10;
         ProgramCounter::Next
     }
 
@@ -480,9 +936,10 @@ impl CPU {
     fn op_fx33(&mut self, x: usize) -> ProgramCounter {
         let vx = self.v[x];
 
-        self.ram[self.i as usize] = vx / 100; // hundreds digit
-        self.ram[(self.i + 1) as usize] = (vx / 10) % 10; // tens digit
-        self.ram[(self.i + 2) as usize] = vx % 10; // ones digit
+        self.ram[self.i_address(0)] = vx / 100; // hundreds digit
+        self.ram[self.i_address(1)] = (vx / 10) % 10; // tens digit
+        self.ram[self.i_address(2)] = vx % 10; // ones digit
+        self.ram_write = Some(self.i_range(3));
 
         ProgramCounter::Next
     }
@@ -491,7 +948,11 @@ impl CPU {
     // Store registers V0 through Vx in memory starting at location I.
     fn op_fx55(&mut self, x: usize) -> ProgramCounter {
         for i in 0..(x+1) {
-            self.ram[(self.i as usize) + i] = self.v[i];
+            self.ram[self.i_address(i)] = self.v[i];
+        }
+        self.ram_write = Some(self.i_range(x + 1));
+        if self.quirks.memory_increment {
+            self.i = self.i.wrapping_add(x as u16 + 1);
         }
 
         ProgramCounter::Next
@@ -503,13 +964,22 @@ impl CPU {
     // Read registers V0 through Vx from memory starting at location I.
     fn op_fx65(&mut self, x: usize) -> ProgramCounter {
         for i in 0..(x+1) {
-            self.v[i] = self.ram[self.i as usize + i];
+            self.v[i] = self.ram[self.i_address(i)];
+        }
+        self.ram_read = Some(self.i_range(x + 1));
+        if self.quirks.memory_increment {
+            self.i = self.i.wrapping_add(x as u16 + 1);
         }
 
         ProgramCounter::Next
     }
 }
 
+// Addresses past the end of RAM wrap around to the start
+fn ram_address(address: usize) -> usize {
+	address % CHIP8_RAM
+}
+
 #[cfg(test)]
 #[path = "./CPU_test.rs"]
 mod tests;
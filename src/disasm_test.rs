@@ -0,0 +1,35 @@
+use super::*;
+
+#[test]
+fn test_disassemble() {
+    assert_eq!(disassemble(0x00E0), "CLS");
+    assert_eq!(disassemble(0x00EE), "RET");
+    assert_eq!(disassemble(0x12A4), "JP 0x2A4");
+    assert_eq!(disassemble(0x2006), "CALL 0x006");
+    assert_eq!(disassemble(0x6A02), "LD VA, 0x02");
+    assert_eq!(disassemble(0x8124), "ADD V1, V2");
+    assert_eq!(disassemble(0x8ABE), "SHL VA, VB");
+    assert_eq!(disassemble(0xB300), "JP V0, 0x300");
+    assert_eq!(disassemble(0xD125), "DRW V1, V2, 5");
+    assert_eq!(disassemble(0xE39E), "SKP V3");
+    assert_eq!(disassemble(0xF00A), "LD V0, K");
+    assert_eq!(disassemble(0xF430), "LD HF, V4");
+    assert_eq!(disassemble(0xF233), "LD B, V2");
+    assert_eq!(disassemble(0xFF65), "LD VF, [I]");
+}
+
+#[test]
+fn test_data_words() {
+    assert_eq!(disassemble(0x5121), "DW 0x5121");
+    assert_eq!(disassemble(0x8128), "DW 0x8128");
+    assert_eq!(disassemble(0xE1FF), "DW 0xE1FF");
+    assert_eq!(disassemble(0xF1FF), "DW 0xF1FF");
+}
+
+#[test]
+fn test_opcode_at() {
+    let ram = [0x12, 0x34, 0x56];
+    assert_eq!(opcode_at(&ram, 0), 0x1234);
+    assert_eq!(opcode_at(&ram, 2), 0x5600);
+    assert_eq!(opcode_at(&ram, 3), 0);
+}
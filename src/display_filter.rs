@@ -0,0 +1,133 @@
+// Display filters sit between the CPU's vram and the GraphicDriver.
+// CHIP-8 games draw by XORing sprites, so most of them erase and redraw every frame.
+// Showing vram as-is makes moving sprites flicker; these filters smooth that out.
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+use yarc::framebuffer::Framebuffer;
+
+// Frames hold the brightness of every pixel, from 0 (off) to 255 (fully lit)
+const BRIGHTNESS_PLANES: u8 = 8;
+const LIT: u8 = 255;
+const PHOSPHOR_DECAY: u16 = 140; // out of 256: how much brightness a pixel keeps per frame
+const GHOST_FRAMES: usize = 3; // number of frames ORed together in ghosting mode
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FilterMode {
+    FrameBoundary, // show vram as it is at the 60 Hz frame boundary
+    Phosphor, // lit pixels fade out over a few frames, like a CRT
+    Ghosting, // OR of the last GHOST_FRAMES frames
+}
+
+impl FilterMode {
+    // Order the modes are cycled through at runtime
+    pub fn next(self) -> Self {
+        match self {
+            FilterMode::FrameBoundary => FilterMode::Phosphor,
+            FilterMode::Phosphor => FilterMode::Ghosting,
+            FilterMode::Ghosting => FilterMode::FrameBoundary,
+        }
+    }
+}
+
+impl fmt::Display for FilterMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FilterMode::FrameBoundary => "frame boundary",
+            FilterMode::Phosphor => "phosphor",
+            FilterMode::Ghosting => "ghosting",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for FilterMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "frame boundary" => Ok(FilterMode::FrameBoundary),
+            "phosphor" => Ok(FilterMode::Phosphor),
+            "ghosting" => Ok(FilterMode::Ghosting),
+            _ => Err(format!("unknown display filter {}", s)),
+        }
+    }
+}
+
+pub struct DisplayFilter {
+    mode: FilterMode,
+    frame: Framebuffer, // what was last handed to the renderer
+    history: VecDeque<Framebuffer>, // most recent vram samples, newest first (ghosting only)
+}
+
+impl DisplayFilter {
+    // `screen` is the platform's; apply() follows the vram if it changes size
+    pub fn new(mode: FilterMode, screen: (usize, usize)) -> Self {
+        DisplayFilter {
+            mode,
+            frame: Framebuffer::new(screen.0, screen.1, BRIGHTNESS_PLANES),
+            history: VecDeque::with_capacity(GHOST_FRAMES),
+        }
+    }
+
+    pub fn mode(&self) -> FilterMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: FilterMode) {
+        self.mode = mode;
+        self.history.clear();
+    }
+
+    // The same size as the vram last applied
+    pub fn frame(&self) -> &Framebuffer {
+        &self.frame
+    }
+
+    // Feed the vram sampled at a frame boundary. Returns true if the filtered frame changed
+    // and needs to be presented again; frame().dirty() says where.
+    pub fn apply(&mut self, vram: &Framebuffer) -> bool {
+        // A new screen size starts afresh, all of it dirty
+        if vram.size() != self.frame.size() {
+            self.frame = Framebuffer::new(vram.width(), vram.height(), BRIGHTNESS_PLANES);
+            self.history.clear();
+        } else {
+            self.frame.mark_clean();
+        }
+
+        match self.mode {
+            FilterMode::FrameBoundary => {
+                for (x, y, pixel) in vram.iter() {
+                    self.frame.set(x, y, lit(pixel));
+                }
+            }
+            FilterMode::Phosphor => {
+                for (x, y, pixel) in vram.iter() {
+                    let faded = ((self.frame.get(x, y) as u16 * PHOSPHOR_DECAY) >> 8) as u8;
+                    self.frame.set(x, y, lit(pixel).max(faded));
+                }
+            }
+            FilterMode::Ghosting => {
+                if self.history.len() == GHOST_FRAMES {
+                    self.history.pop_back();
+                }
+                self.history.push_front(vram.clone());
+                for (x, y, _) in vram.iter() {
+                    let ored = self.history.iter().fold(0, |ored, past| ored | lit(past.get(x, y)));
+                    self.frame.set(x, y, ored);
+                }
+            }
+        }
+
+        self.frame.dirty().is_some()
+    }
+}
+
+fn lit(pixel: u8) -> u8 {
+    if pixel == 0 { 0 } else { LIT }
+}
+
+#[cfg(test)]
+#[path = "./display_filter_test.rs"]
+mod tests;
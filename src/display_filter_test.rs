@@ -0,0 +1,90 @@
+use super::*;
+use crate::{CHIP8_HEIGHT, CHIP8_WIDTH};
+use yarc::framebuffer::Region;
+
+fn vram_with(x: usize, y: usize) -> Framebuffer {
+    let mut vram = blank();
+    vram.set(x, y, 1);
+    vram
+}
+
+fn blank() -> Framebuffer {
+    Framebuffer::new(CHIP8_WIDTH, CHIP8_HEIGHT, 1)
+}
+
+#[test]
+fn test_frame_boundary() {
+    let mut filter = DisplayFilter::new(FilterMode::FrameBoundary, (CHIP8_WIDTH, CHIP8_HEIGHT));
+    assert!(filter.apply(&vram_with(3, 4)));
+    assert_eq!(filter.frame().get(3, 4), 255);
+
+    // Same vram again: nothing to present
+    assert!(!filter.apply(&vram_with(3, 4)));
+
+    // Pixel switched off disappears straight away
+    assert!(filter.apply(&blank()));
+    assert_eq!(filter.frame().get(3, 4), 0);
+}
+
+#[test]
+fn test_phosphor_decays() {
+    let mut filter = DisplayFilter::new(FilterMode::Phosphor, (CHIP8_WIDTH, CHIP8_HEIGHT));
+    let blank = blank();
+    filter.apply(&vram_with(0, 0));
+    assert_eq!(filter.frame().get(0, 0), 255);
+
+    filter.apply(&blank);
+    let first = filter.frame().get(0, 0);
+    assert!(first > 0 && first < 255);
+
+    filter.apply(&blank);
+    assert!(filter.frame().get(0, 0) < first);
+
+    // Eventually fades out completely and stops changing
+    for _ in 0..32 {
+        filter.apply(&blank);
+    }
+    assert_eq!(filter.frame().get(0, 0), 0);
+    assert!(!filter.apply(&blank));
+}
+
+#[test]
+fn test_ghosting_ors_last_frames() {
+    let mut filter = DisplayFilter::new(FilterMode::Ghosting, (CHIP8_WIDTH, CHIP8_HEIGHT));
+    let blank = blank();
+    filter.apply(&vram_with(0, 0));
+    filter.apply(&vram_with(1, 0));
+    assert_eq!(filter.frame().get(0, 0), 255);
+    assert_eq!(filter.frame().get(1, 0), 255);
+
+    // Pixel stays lit while it is within the last GHOST_FRAMES frames
+    filter.apply(&blank);
+    assert_eq!(filter.frame().get(0, 0), 255);
+    filter.apply(&blank);
+    assert_eq!(filter.frame().get(0, 0), 0);
+    assert_eq!(filter.frame().get(1, 0), 255);
+}
+
+#[test]
+fn test_dirty_and_resize() {
+    let mut filter = DisplayFilter::new(FilterMode::FrameBoundary, (CHIP8_WIDTH, CHIP8_HEIGHT));
+    filter.apply(&blank());
+    assert!(filter.apply(&vram_with(3, 4)));
+    assert_eq!(filter.frame().dirty(), Some(Region { x: 3, y: 4, width: 1, height: 1 }));
+
+    // Another platform's screen: all of it is new
+    let mut vram = Framebuffer::new(128, 64, 1);
+    vram.mark_clean();
+    assert!(filter.apply(&vram));
+    assert_eq!(filter.frame().size(), (128, 64));
+    assert_eq!(filter.frame().dirty(), Some(Region { x: 0, y: 0, width: 128, height: 64 }));
+}
+
+#[test]
+fn test_mode_cycles() {
+    let mut mode = FilterMode::FrameBoundary;
+    for _ in 0..3 {
+        mode = mode.next();
+    }
+    assert_eq!(mode, FilterMode::FrameBoundary);
+}
@@ -41,7 +41,7 @@ impl AudioDriver {
 			.unwrap(); //TODO: can cause panic
 
 		AudioDriver {
-			device: device
+			device
 		}
 
 	}
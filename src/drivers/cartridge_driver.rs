@@ -11,11 +11,7 @@ impl CartridgeDriver {
 		let mut file = File::open(filename).expect("File not found!");
 		let mut buffer = [0u8; 3584];
 
-		let bytes_read = if let Ok(bytes_read) = file.read(&mut buffer) {
-			bytes_read
-		} else {
-			0
-		};
+		let bytes_read = file.read(&mut buffer).unwrap_or_default();
 
 		CartridgeDriver {
 			rom: buffer,
@@ -1,77 +1,168 @@
 use sdl2;
 use sdl2::pixels;
+use sdl2::pixels::PixelFormatEnum;
 use sdl2::rect::Rect;
-use sdl2::render::Canvas;
+use sdl2::render::{Canvas, Texture};
 use sdl2::video::Window;
 
-use crate::CHIP8_WIDTH;
-use crate::CHIP8_HEIGHT;
+use crate::post_process::PostProcessor;
+use crate::palette::Palette;
+use yarc::framebuffer::Framebuffer;
+use yarc::platform::{Colours, Platform};
 
 
-const SCALE_FACTOR: u32 = 20;
-const SCREEN_WIDTH: u32 = (CHIP8_WIDTH as u32) * SCALE_FACTOR;
-const SCREEN_HEIGHT: u32 = (CHIP8_HEIGHT as u32) * SCALE_FACTOR;
+// The window is as big as it can be up to this, with every chip8 pixel the same whole number of
+// screen pixels: 20 for CHIP-8's 64x32, 10 for the bigger screens
+const MAX_SCREEN_WIDTH: u32 = 1280;
+const MAX_SCREEN_HEIGHT: u32 = 640;
+const WINDOW_TITLE: &str = "Rust-chip8-window";
 // Screen is computer screen. This is larger than a chip8 screen
 
+const BYTES_PER_PIXEL: usize = 3; // RGB24
+
 pub struct GraphicDriver { //  graphic driver object with a window in it
     canvas: Canvas<Window>,
+    texture: Texture, // Streaming texture the size of the chip8 screen; the GPU scales it up
+    scaled_texture: Texture, // Streaming texture the size of the window, for post-processed frames
+    post_processor: Option<PostProcessor>,
+    palette: Palette,
+    platform: Platform, // the screen size and colours drawn
+    scale_factor: u32,
 }
 
 impl GraphicDriver {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Self {
+    pub fn new(sdl_context: &sdl2::Sdl, palette: Palette, platform: Platform) -> Self {
+        let (width, height) = (platform.width() as u32, platform.height() as u32);
+        let scale_factor = (MAX_SCREEN_WIDTH / width).min(MAX_SCREEN_HEIGHT / height);
+        let (screen_width, screen_height) = (width * scale_factor, height * scale_factor);
+
         // We first want to open a window as specified in sdl_context
         // sdl_context is just a sdl that has been initialized
         let video_subsystem = sdl_context.video().unwrap(); // .video() returns a VideoSubsystem, which can initialize a WindowBuilder.
-        let window = video_subsystem.window(
-                "Rust-chip8-window",
-                SCREEN_WIDTH,
-                SCREEN_HEIGHT,)
-                .position_centered() // returns a &mut WindowBuilder, with centered coordinates
-                .opengl() // add opengl flag so that rust can use window
-                .build() // returns a Result<Window, WindowBuildError>
-                .unwrap(); // Should return 
-
-        let mut canvas = window.into_canvas().build().unwrap();
+
+        // Prefer a GPU renderer synced to the display, but fall back to SDL's software renderer
+        // so the emulator still runs on machines without a GPU.
+        // Setting SDL_RENDER_DRIVER=software in the environment forces the fallback.
+        let build_window = || build_window(&video_subsystem, screen_width, screen_height);
+        let mut canvas = match build_window().into_canvas().accelerated().present_vsync().build() {
+            Ok(canvas) => canvas,
+            Err(_) => build_window().into_canvas().software().build().unwrap(),
+        };
+
+        let texture = canvas
+                .texture_creator()
+                .create_texture_streaming(PixelFormatEnum::RGB24, width, height)
+                .unwrap();
+        let scaled_texture = canvas
+                .texture_creator()
+                .create_texture_streaming(PixelFormatEnum::RGB24, screen_width, screen_height)
+                .unwrap();
         
         // Configure canvas
-        canvas.set_draw_color(pixels::Color::RGB(0,0,0));
+        let [r, g, b] = palette.off;
+        canvas.set_draw_color(pixels::Color::RGB(r, g, b));
         canvas.clear(); // clear canvas
         canvas.present(); // Bring canvas to front buffer
 
-        GraphicDriver{ canvas: canvas }
+        GraphicDriver{ canvas, texture, scaled_texture, post_processor: None, palette, platform, scale_factor }
     }
 
-    pub fn draw(&mut self, pixels: &[[u8; CHIP8_WIDTH]; CHIP8_HEIGHT]) {
-        for (y,row) in pixels.iter().enumerate() {
-            for (x,&col) in row.iter().enumerate() {
-
-                let x = (x as u32) * SCALE_FACTOR;
-                let y = (y as u32) * SCALE_FACTOR;
-
-                self.canvas.set_draw_color(color(col));
-                let _ = self.canvas
-                        .fill_rect(Rect::new(x as i32, y as i32, SCALE_FACTOR, SCALE_FACTOR)); // want to fill rectangle (pixel) at that particular address
-            }
+    // Uploads the whole frame to the texture in one go and presents it once. `colours` are the
+    // CPU's, on platforms that have them; otherwise pixels are in the palette's colours.
+    // Call this at most once per 60 Hz frame; with vsync on, present() waits for the display.
+    pub fn draw(&mut self, pixels: &Framebuffer, colours: Option<&Colours>) {
+        self.canvas.clear();
+
+        let palette = self.palette;
+        let color = |x: usize, y: usize, p: u8| match colours {
+            Some(colours) => colours.rgb(x, y, p),
+            None => palette.rgb(p),
+        };
+        if let Some(post_processor) = &self.post_processor {
+            // Effects are rendered at window size on the CPU, so no scaling is left for the GPU
+            let _ = self.scaled_texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+                post_processor.render(pixels, color, buffer, pitch);
+            });
+            let _ = self.canvas.copy(&self.scaled_texture, None, None);
+        } else {
+            let _ = self.texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+                fill_frame(buffer, pitch, pixels, color);
+            });
+            let _ = self.canvas.copy(&self.texture, None, None); // stretch the texture over the whole window
         }
 
         self.canvas.present(); // Bring canvas to front 
     }
-}
-        
-fn color(p: u8) -> pixels::Color { // There are only 2 colors: blank or non-blank
-    if p == 0 {
-        pixels::Color::RGB(0,0,0)
-    } else {
-        pixels::Color::RGB(0,250,0)
+
+    // Draws several frames at once, each stretched over its own part of the window, for grid
+    // mode. The effects are left out. `focused` gets an outline in the lit colour.
+    pub fn draw_tiles(&mut self, tiles: &[(&Framebuffer, Rect)], focused: Option<Rect>) {
+        self.canvas.clear();
+
+        let palette = self.palette;
+        for (pixels, rect) in tiles {
+            // Locking the texture again makes SDL draw the last tile before it changes
+            let _ = self.texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+                fill_frame(buffer, pitch, pixels, |_, _, p| palette.rgb(p));
+            });
+            let _ = self.canvas.copy(&self.texture, None, Some(*rect));
+        }
+        if let Some(rect) = focused {
+            let ([r, g, b], [off_r, off_g, off_b]) = (palette.on, palette.off);
+            self.canvas.set_draw_color(pixels::Color::RGB(r, g, b));
+            let _ = self.canvas.draw_rect(rect);
+            self.canvas.set_draw_color(pixels::Color::RGB(off_r, off_g, off_b));
+        }
+
+        self.canvas.present();
     }
-    // Can update this function if we want more color
-}
-        
-        
 
+    // For telling the game's window apart from others, e.g. the inspector's
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
 
+    // Window size in pixels, e.g. to lay out grid mode's tiles
+    pub fn size(&self) -> (u32, u32) {
+        (self.platform.width() as u32 * self.scale_factor, self.platform.height() as u32 * self.scale_factor)
+    }
 
+    // Scale a PostProcessor must be built with to fill the window
+    pub fn scale_factor(&self) -> usize {
+        self.scale_factor as usize
+    }
 
+    // CRT/LCD effects to apply to every frame, or None to draw plain pixels
+    pub fn set_post_processor(&mut self, post_processor: Option<PostProcessor>) {
+        self.post_processor = post_processor;
+    }
 
+    // Shows a short status message (e.g. the active display filter) in the window title
+    pub fn set_status(&mut self, status: &str) {
+        let _ = self.canvas.window_mut().set_title(&format!("{} - {}", WINDOW_TITLE, status));
+    }
+}
 
+fn build_window(video_subsystem: &sdl2::VideoSubsystem, width: u32, height: u32) -> Window {
+    video_subsystem.window(
+            WINDOW_TITLE,
+            width,
+            height,)
+            .position_centered() // returns a &mut WindowBuilder, with centered coordinates
+            .build() // returns a Result<Window, WindowBuildError>
+            .unwrap() // Should return 
+}
 
+// Writes the chip8 screen into an RGB24 pixel buffer whose rows are `pitch` bytes apart.
+// `color` maps a pixel's position and brightness to its RGB colour.
+fn fill_frame<F: Fn(usize, usize, u8) -> [u8; 3]>(buffer: &mut [u8], pitch: usize, pixels: &Framebuffer, color: F) {
+    for (x, y, col) in pixels.iter() {
+        let offset = y * pitch + x * BYTES_PER_PIXEL;
+        // col is the brightness of the pixel: 0 is blank, 255 is fully lit
+        buffer[offset..offset + BYTES_PER_PIXEL].copy_from_slice(&color(x, y, col));
+    }
+}
+        
+#[cfg(test)]
+#[path = "./graphic_driver_test.rs"]
+mod tests;
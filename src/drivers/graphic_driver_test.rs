@@ -0,0 +1,84 @@
+use super::*;
+use crate::{CHIP8_HEIGHT, CHIP8_WIDTH};
+
+fn blank() -> Framebuffer {
+    Framebuffer::new(CHIP8_WIDTH, CHIP8_HEIGHT, 8)
+}
+
+fn mono(_x: usize, _y: usize, p: u8) -> [u8; 3] {
+    Palette::default().rgb(p)
+}
+
+#[test]
+fn test_fill_frame() {
+    let mut pixels = blank();
+    pixels.set(0, 0, 255);
+    pixels.set(CHIP8_WIDTH - 1, CHIP8_HEIGHT - 1, 255);
+
+    let pitch = CHIP8_WIDTH * BYTES_PER_PIXEL;
+    let mut buffer = vec![0xAA; pitch * CHIP8_HEIGHT];
+    fill_frame(&mut buffer, pitch, &pixels, mono);
+
+    assert_eq!(buffer[0..3], [0, 250, 0]);
+    assert_eq!(buffer[3..6], [0, 0, 0]);
+    assert_eq!(buffer[buffer.len() - 3..], [0, 250, 0]);
+}
+
+// SDL may pad texture rows, so rows must start at multiples of pitch
+#[test]
+fn test_fill_frame_padded_pitch() {
+    let mut pixels = blank();
+    pixels.set(0, 1, 255);
+
+    let pitch = CHIP8_WIDTH * BYTES_PER_PIXEL + 4;
+    let mut buffer = vec![0xAA; pitch * CHIP8_HEIGHT];
+    fill_frame(&mut buffer, pitch, &pixels, mono);
+
+    assert_eq!(buffer[pitch..pitch + 3], [0, 250, 0]);
+    // Padding bytes are left alone
+    assert_eq!(buffer[pitch - 4..pitch], [0xAA; 4]);
+}
+
+// Partly faded pixels are drawn dimmer
+#[test]
+fn test_fill_frame_brightness() {
+    let mut pixels = blank();
+    pixels.set(0, 0, 51);
+
+    let pitch = CHIP8_WIDTH * BYTES_PER_PIXEL;
+    let mut buffer = vec![0; pitch * CHIP8_HEIGHT];
+    fill_frame(&mut buffer, pitch, &pixels, mono);
+
+    assert_eq!(buffer[0..3], [0, 50, 0]);
+}
+
+// CHIP-8X pixels are drawn in the colours asked for
+#[test]
+fn test_fill_frame_platform() {
+    let mut pixels = blank();
+    pixels.set(8, 0, 255);
+    let mut colours = Colours::default();
+    colours.paint(1, 0, 1, 1, 6);
+
+    let pitch = CHIP8_WIDTH * BYTES_PER_PIXEL;
+    let mut buffer = vec![0xAA; pitch * CHIP8_HEIGHT];
+    fill_frame(&mut buffer, pitch, &pixels, |x, y, p| colours.rgb(x, y, p));
+
+    // An aqua pixel on the blue background
+    assert_eq!(buffer[0..3], [0, 0, 0x80]);
+    assert_eq!(buffer[24..27], [0, 0xFF, 0xFF]);
+}
+
+// Bigger screens fill a bigger texture
+#[test]
+fn test_fill_frame_size() {
+    let mut pixels = Framebuffer::new(128, 64, 8);
+    pixels.set(127, 63, 255);
+
+    let pitch = 128 * BYTES_PER_PIXEL;
+    let mut buffer = vec![0xAA; pitch * 64];
+    fill_frame(&mut buffer, pitch, &pixels, mono);
+
+    assert_eq!(buffer[0..3], [0, 0, 0]);
+    assert_eq!(buffer[buffer.len() - 3..], [0, 250, 0]);
+}
@@ -1,25 +1,85 @@
 use sdl2;
-use sdl2::event::Event; // event: event handling library, Event is an enum of different event types
+use sdl2::event::{Event, WindowEvent}; // event: event handling library, Event is an enum of different event types
 use sdl2::keyboard::Keycode; // keyboard: input handling lib. Keycode is an enum for different keys
 
+// Emulator controls that live outside the CHIP-8 keypad
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Hotkey {
+    CycleDisplayFilter, // F2
+    CycleShader, // F3
+    ToggleRecording, // F11
+    Screenshot, // F12
+    // Grid mode only
+    NextTile, // Tab
+    ToggleBroadcast, // F4
+    Click(i32, i32), // mouse button, at these window coordinates
+}
+
 pub struct InputDriver {
     events: sdl2::EventPump,
+    keyboard: sdl2::keyboard::KeyboardUtil,
+    hotkeys: Vec<Hotkey>, // hotkeys pressed since the last call to hotkeys()
+    game_window: Option<u32>, // set when there are other windows, e.g. the inspector
+    other_events: Vec<Event>, // for the other windows, since the last call to other_window_events()
 }
 
 impl InputDriver {
     pub fn new(sdl_context: &sdl2::Sdl) -> Self {
         InputDriver { 
-            events: sdl_context.event_pump().unwrap() // event pump: return an Event pump (stream) 
+            events: sdl_context.event_pump().unwrap(), // event pump: return an Event pump (stream) 
+            keyboard: sdl_context.keyboard(),
+            hotkeys: Vec::new(),
+            game_window: None,
+            other_events: Vec::new(),
         }
     }
 
+    // With more than one window open, only the game's one plays: the keypad is read while it has
+    // the keyboard, and other windows' events are kept for other_window_events()
+    pub fn set_game_window(&mut self, window_id: u32) {
+        self.game_window = Some(window_id);
+    }
+
+    // Events for windows other than the game's, oldest first
+    pub fn other_window_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.other_events)
+    }
+
     pub fn poll(&mut self) -> Result<[bool; 16], ()> {
         // Will return a boolean representing the keypad for chip8
         for event in self.events.poll_iter() {
+            if let (Some(game_window), Some(window_id)) = (self.game_window, window_of(&event)) {
+                if window_id != game_window {
+                    self.other_events.push(event);
+                    continue;
+                }
+            }
             // handle if there is a quit event
-            if let Event::Quit{..} = event {
-                return Err(());
-            };
+            match event {
+                Event::Quit{..} => return Err(()),
+                // With other windows open, SDL doesn't quit when the game's is closed
+                Event::Window{ win_event: WindowEvent::Close, .. } => return Err(()),
+                Event::KeyDown{ keycode: Some(key), repeat: false, .. } => {
+                    let hotkey = match key {
+                        Keycode::F2 => Some(Hotkey::CycleDisplayFilter),
+                        Keycode::F3 => Some(Hotkey::CycleShader),
+                        Keycode::F4 => Some(Hotkey::ToggleBroadcast),
+                        Keycode::Tab => Some(Hotkey::NextTile),
+                        Keycode::F11 => Some(Hotkey::ToggleRecording),
+                        Keycode::F12 => Some(Hotkey::Screenshot),
+                        _ => None,
+                    };
+                    if let Some(hotkey) = hotkey {
+                        self.hotkeys.push(hotkey);
+                    }
+                }
+                Event::MouseButtonDown{ x, y, .. } => self.hotkeys.push(Hotkey::Click(x, y)),
+                _ => {}
+            }
+        }
+
+        if self.game_window.is_some() && self.keyboard.focused_window_id() != self.game_window {
+            return Ok([false; 16]);
         }
 
         let keys: Vec<Keycode> = self.events
@@ -57,4 +117,24 @@ impl InputDriver {
         
         Ok(keypad)
     }
+
+    // Returns the hotkeys pressed since the last call, oldest first
+    pub fn hotkeys(&mut self) -> Vec<Hotkey> {
+        std::mem::take(&mut self.hotkeys)
+    }
+}
+
+// The window an event happened in, for the events that say
+fn window_of(event: &Event) -> Option<u32> {
+    match *event {
+        Event::Window{ window_id, .. }
+        | Event::KeyDown{ window_id, .. }
+        | Event::KeyUp{ window_id, .. }
+        | Event::TextInput{ window_id, .. }
+        | Event::MouseMotion{ window_id, .. }
+        | Event::MouseButtonDown{ window_id, .. }
+        | Event::MouseButtonUp{ window_id, .. }
+        | Event::MouseWheel{ window_id, .. } => Some(window_id),
+        _ => None,
+    }
 }
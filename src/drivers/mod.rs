@@ -1,12 +1,18 @@
 // This is the module definition file
 // Enable those that you have done
+// Everything except the cartridge needs SDL
+#[cfg(feature = "sdl")]
 pub mod graphic_driver;
+#[cfg(feature = "sdl")]
 pub mod audio_driver;
+#[cfg(feature = "sdl")]
 pub mod input_driver;
 pub mod cartridge_driver;
 
+#[cfg(feature = "sdl")]
 pub use self::graphic_driver::GraphicDriver;
+#[cfg(feature = "sdl")]
 pub use self::audio_driver::AudioDriver;
-pub use self::input_driver::InputDriver;
+#[cfg(feature = "sdl")]
+pub use self::input_driver::{InputDriver, Hotkey};
 pub use self::cartridge_driver::CartridgeDriver;
-
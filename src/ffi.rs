@@ -0,0 +1,236 @@
+// C ABI for embedding YARC, built into the yarc cdylib. include/yarc.h is generated from
+// this file with cbindgen (see cbindgen.toml), so regenerate it after changing anything here.
+//
+// Every function takes the yarc_cpu* returned by yarc_cpu_create(). Pointers and lengths
+// passed in must be valid for the given length; NULL is rejected with an error return.
+#![allow(clippy::missing_safety_doc)]
+
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::cpu::{Registers, CPU, STATE_SIZE};
+use crate::{CHIP8_HEIGHT, CHIP8_RAM, CHIP8_WIDTH};
+
+pub const YARC_WIDTH: usize = 64;
+pub const YARC_HEIGHT: usize = 32;
+pub const YARC_RAM_SIZE: usize = 4096;
+pub const YARC_STATE_SIZE: usize = 6214;
+
+// Flags returned by yarc_cpu_step() and yarc_cpu_run_frame()
+pub const YARC_VRAM_CHANGED: i32 = 1;
+pub const YARC_BEEP: i32 = 2;
+
+pub const YARC_OK: i32 = 0;
+pub const YARC_ERROR: i32 = -1;
+
+// The header spells these out as numbers, so make sure they stay in sync
+const _: () = assert!(YARC_WIDTH == CHIP8_WIDTH && YARC_HEIGHT == CHIP8_HEIGHT);
+const _: () = assert!(YARC_RAM_SIZE == CHIP8_RAM && YARC_STATE_SIZE == STATE_SIZE);
+
+// Opaque to C
+pub struct YarcCpu {
+    cpu: CPU,
+    seed: u64,
+    keypad: [bool; 16],
+}
+
+#[repr(C)]
+pub struct YarcRegisters {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub sp: u8,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub stack: [u16; 16],
+}
+
+// A seed of 0 picks a random one; anything else replays the same random numbers
+#[no_mangle]
+pub extern "C" fn yarc_cpu_create(seed: u64) -> *mut YarcCpu {
+    let cpu = if seed == 0 { CPU::new() } else { CPU::with_seed(seed) };
+    Box::into_raw(Box::new(YarcCpu { cpu, seed, keypad: [false; 16] }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn yarc_cpu_destroy(cpu: *mut YarcCpu) {
+    if !cpu.is_null() {
+        drop(Box::from_raw(cpu));
+    }
+}
+
+// Resets the machine and loads a ROM at 0x200
+#[no_mangle]
+pub unsafe extern "C" fn yarc_cpu_load_rom(cpu: *mut YarcCpu, rom: *const u8, len: usize) -> i32 {
+    let handle = match cpu.as_mut() {
+        Some(handle) => handle,
+        None => return YARC_ERROR,
+    };
+    if rom.is_null() || len > CHIP8_RAM - 0x200 {
+        return YARC_ERROR;
+    }
+    handle.cpu = if handle.seed == 0 { CPU::new() } else { CPU::with_seed(handle.seed) };
+    handle.cpu.load_program(slice::from_raw_parts(rom, len));
+    YARC_OK
+}
+
+// Runs one instruction. Returns YARC_VRAM_CHANGED | YARC_BEEP flags, or YARC_ERROR if the
+// game crashed the interpreter.
+#[no_mangle]
+pub unsafe extern "C" fn yarc_cpu_step(cpu: *mut YarcCpu) -> i32 {
+    yarc_cpu_run_frame(cpu, 1)
+}
+
+// Runs `ticks` instructions; YARC_VRAM_CHANGED is set if any of them drew
+#[no_mangle]
+pub unsafe extern "C" fn yarc_cpu_run_frame(cpu: *mut YarcCpu, ticks: u32) -> i32 {
+    let handle = match cpu.as_mut() {
+        Some(handle) => handle,
+        None => return YARC_ERROR,
+    };
+    // Unwinding into C is undefined, so a bad opcode becomes an error code instead
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut flags = 0;
+        for _ in 0..ticks {
+            let output = handle.cpu.tick(handle.keypad);
+            if output.vram_changed {
+                flags |= YARC_VRAM_CHANGED;
+            }
+            if output.beep {
+                flags |= YARC_BEEP;
+            } else {
+                flags &= !YARC_BEEP;
+            }
+        }
+        flags
+    }));
+    result.unwrap_or(YARC_ERROR)
+}
+
+// Bit n of `keys` set means key n of the keypad is held
+#[no_mangle]
+pub unsafe extern "C" fn yarc_cpu_set_keypad(cpu: *mut YarcCpu, keys: u16) -> i32 {
+    let handle = match cpu.as_mut() {
+        Some(handle) => handle,
+        None => return YARC_ERROR,
+    };
+    for (n, key) in handle.keypad.iter_mut().enumerate() {
+        *key = keys & (1 << n) != 0;
+    }
+    YARC_OK
+}
+
+// Copies the screen, one byte per pixel (0 or 1), row by row. `len` must be at least
+// YARC_WIDTH * YARC_HEIGHT.
+#[no_mangle]
+pub unsafe extern "C" fn yarc_cpu_framebuffer(cpu: *const YarcCpu, out: *mut u8, len: usize) -> i32 {
+    let handle = match cpu.as_ref() {
+        Some(handle) => handle,
+        None => return YARC_ERROR,
+    };
+    if out.is_null() || len < CHIP8_WIDTH * CHIP8_HEIGHT {
+        return YARC_ERROR;
+    }
+    let pixels = handle.cpu.vram().pixels();
+    ptr::copy_nonoverlapping(pixels.as_ptr(), out, pixels.len());
+    YARC_OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn yarc_cpu_read_memory(cpu: *const YarcCpu, address: u16, out: *mut u8, len: usize) -> i32 {
+    let handle = match cpu.as_ref() {
+        Some(handle) => handle,
+        None => return YARC_ERROR,
+    };
+    let start = address as usize;
+    // A huge len would wrap round past the end of RAM
+    if out.is_null() || start.checked_add(len).is_none_or(|end| end > CHIP8_RAM) {
+        return YARC_ERROR;
+    }
+    ptr::copy_nonoverlapping(handle.cpu.ram()[start..].as_ptr(), out, len);
+    YARC_OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn yarc_cpu_write_memory(cpu: *mut YarcCpu, address: u16, data: *const u8, len: usize) -> i32 {
+    let handle = match cpu.as_mut() {
+        Some(handle) => handle,
+        None => return YARC_ERROR,
+    };
+    let start = address as usize;
+    // A huge len would wrap round past the end of RAM
+    if data.is_null() || start.checked_add(len).is_none_or(|end| end > CHIP8_RAM) {
+        return YARC_ERROR;
+    }
+    handle.cpu.ram_mut()[start..start + len].copy_from_slice(slice::from_raw_parts(data, len));
+    YARC_OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn yarc_cpu_get_registers(cpu: *const YarcCpu, out: *mut YarcRegisters) -> i32 {
+    let (handle, out) = match (cpu.as_ref(), out.as_mut()) {
+        (Some(handle), Some(out)) => (handle, out),
+        _ => return YARC_ERROR,
+    };
+    let registers = handle.cpu.registers();
+    *out = YarcRegisters {
+        v: registers.v,
+        i: registers.i,
+        pc: registers.pc,
+        sp: registers.sp,
+        delay_timer: registers.delay_timer,
+        sound_timer: registers.sound_timer,
+        stack: registers.stack,
+    };
+    YARC_OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn yarc_cpu_set_registers(cpu: *mut YarcCpu, registers: *const YarcRegisters) -> i32 {
+    let (handle, registers) = match (cpu.as_mut(), registers.as_ref()) {
+        (Some(handle), Some(registers)) => (handle, registers),
+        _ => return YARC_ERROR,
+    };
+    handle.cpu.set_registers(&Registers {
+        v: registers.v,
+        i: registers.i,
+        pc: registers.pc,
+        sp: registers.sp,
+        delay_timer: registers.delay_timer,
+        sound_timer: registers.sound_timer,
+        stack: registers.stack,
+    });
+    YARC_OK
+}
+
+// Writes YARC_STATE_SIZE bytes to `out`
+#[no_mangle]
+pub unsafe extern "C" fn yarc_cpu_save_state(cpu: *const YarcCpu, out: *mut u8, len: usize) -> i32 {
+    let handle = match cpu.as_ref() {
+        Some(handle) => handle,
+        None => return YARC_ERROR,
+    };
+    if out.is_null() || len < STATE_SIZE {
+        return YARC_ERROR;
+    }
+    let state = handle.cpu.save_state();
+    ptr::copy_nonoverlapping(state.as_ptr(), out, state.len());
+    YARC_OK
+}
+
+// Restores a yarc_cpu_save_state() snapshot; on error the machine is left as it was
+#[no_mangle]
+pub unsafe extern "C" fn yarc_cpu_load_state(cpu: *mut YarcCpu, data: *const u8, len: usize) -> i32 {
+    let handle = match cpu.as_mut() {
+        Some(handle) => handle,
+        None => return YARC_ERROR,
+    };
+    if data.is_null() {
+        return YARC_ERROR;
+    }
+    match handle.cpu.load_state(slice::from_raw_parts(data, len)) {
+        Ok(()) => YARC_OK,
+        Err(_) => YARC_ERROR,
+    }
+}
@@ -1,4 +1,16 @@
-pub const FONT_SET: [u8; 80] = [
+// Hex digit sprites, which Fx29 points I at. Interpreters drew the digits differently and kept
+// them in different places, so there are a few sets to pick from, or one can be read from a file.
+// SUPER-CHIP added 8x10 digits for Fx30, which are loaded right after the small ones.
+use std::fmt;
+use std::str::FromStr;
+
+pub const FONT_SIZE: usize = 80; // 16 digits of 5 bytes
+pub const BIG_FONT_SIZE: usize = 160; // 16 digits of 10 bytes
+// Highest address a font can be loaded at, so it stays clear of programs at 0x200
+pub const MAX_FONT_ADDRESS: u16 = (0x200 - FONT_SIZE - BIG_FONT_SIZE) as u16;
+
+// YARC's own digits, the ones most interpreters use
+pub const FONT_SET: [u8; FONT_SIZE] = [
     0xF0,
     0x90,
     0x90,
@@ -81,3 +93,161 @@ pub const FONT_SET: [u8; 80] = [
     0x80,
 ];
 
+// The COSMAC VIP's digits
+const VIP_FONT_SET: [u8; FONT_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+// The DREAM 6800's narrower digits
+const DREAM_6800_FONT_SET: [u8; FONT_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+// The ETI-660's digits
+const ETI_660_FONT_SET: [u8; FONT_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+// SUPER-CHIP 1.1's big digits, with A to F as in XO-CHIP
+pub const BIG_FONT_SET: [u8; BIG_FONT_SIZE] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FontSet {
+    Yarc,
+    Vip, // the COSMAC VIP
+    Dream6800,
+    Eti660,
+}
+
+impl FontSet {
+    pub const ALL: [FontSet; 4] = [FontSet::Yarc, FontSet::Vip, FontSet::Dream6800, FontSet::Eti660];
+
+    pub fn glyphs(self) -> &'static [u8; FONT_SIZE] {
+        match self {
+            FontSet::Yarc => &FONT_SET,
+            FontSet::Vip => &VIP_FONT_SET,
+            FontSet::Dream6800 => &DREAM_6800_FONT_SET,
+            FontSet::Eti660 => &ETI_660_FONT_SET,
+        }
+    }
+}
+
+impl fmt::Display for FontSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FontSet::Yarc => "yarc",
+            FontSet::Vip => "vip",
+            FontSet::Dream6800 => "dream6800",
+            FontSet::Eti660 => "eti660",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for FontSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FontSet::ALL
+            .iter()
+            .copied()
+            .find(|set| set.to_string() == s)
+            .ok_or_else(|| format!("unknown font {} (expected yarc, vip, dream6800 or eti660)", s))
+    }
+}
+
+// The digits a CPU is given, and where in RAM they go
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Font {
+    pub small: [u8; FONT_SIZE],
+    pub big: [u8; BIG_FONT_SIZE],
+    pub address: u16, // of the small digits; the big ones follow
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Font::from_set(FontSet::Yarc)
+    }
+}
+
+impl Font {
+    pub fn from_set(set: FontSet) -> Self {
+        Font { small: *set.glyphs(), big: BIG_FONT_SET, address: 0 }
+    }
+
+    // A font file: the 80 bytes of the small digits, then optionally the 160 of the big ones
+    pub fn from_bytes(data: &[u8]) -> Result<Self, String> {
+        let mut font = Font::default();
+        match data.len() {
+            FONT_SIZE => font.small.copy_from_slice(data),
+            length if length == FONT_SIZE + BIG_FONT_SIZE => {
+                font.small.copy_from_slice(&data[..FONT_SIZE]);
+                font.big.copy_from_slice(&data[FONT_SIZE..]);
+            }
+            length => return Err(format!("A font should be {} or {} bytes, not {}", FONT_SIZE, FONT_SIZE + BIG_FONT_SIZE, length)),
+        }
+        Ok(font)
+    }
+}
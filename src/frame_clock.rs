@@ -0,0 +1,28 @@
+// Keeps front-ends presenting at 60 Hz no matter how fast the CPU ticks
+use std::time::{Duration, Instant};
+
+pub const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60); // Screen refreshes at 60 Hz
+
+pub struct FrameClock {
+    next_frame: Instant,
+}
+
+impl FrameClock {
+    pub fn new() -> Self {
+        FrameClock { next_frame: Instant::now() + FRAME_DURATION }
+    }
+
+    // True once per 60 Hz frame: the caller should present now
+    pub fn frame_due(&mut self) -> bool {
+        let now = Instant::now();
+        if now < self.next_frame {
+            return false;
+        }
+        self.next_frame += FRAME_DURATION;
+        // Catch up instead of presenting a burst of frames after a stall
+        if self.next_frame < now {
+            self.next_frame = now + FRAME_DURATION;
+        }
+        true
+    }
+}
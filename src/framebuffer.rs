@@ -0,0 +1,166 @@
+// A screen whose width, height and number of bitplanes are chosen at runtime, so platforms with
+// other screens don't change the types everything passes around.
+// Pixels are stored row by row, a byte each with a bit per plane: 0 is off on every plane.
+// Front-ends keep what they work out from it, e.g. the display filters' brightness, in one with
+// 8 planes.
+// It tracks the rectangle changed since it was last marked clean, so renderers can tell whether
+// there is anything new to draw and where.
+use std::slice::ChunksExact;
+
+// A rectangle of pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    // The smallest region covering both
+    pub fn union(self, other: Region) -> Region {
+        let (left, top) = (self.x.min(other.x), self.y.min(other.y));
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Region { x: left, y: top, width: right - left, height: bottom - top }
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    planes: u8,
+    pixels: Vec<u8>, // row-major
+    dirty: Option<Region>, // changed since mark_clean()
+}
+
+// Same size and pixels; what is dirty doesn't matter
+impl PartialEq for Framebuffer {
+    fn eq(&self, other: &Self) -> bool {
+        (self.width, self.height, self.planes) == (other.width, other.height, other.planes) && self.pixels == other.pixels
+    }
+}
+
+impl Eq for Framebuffer {}
+
+impl Framebuffer {
+    // All pixels off, and all of it dirty as none of it has been drawn yet. 1 to 8 planes.
+    pub fn new(width: usize, height: usize, planes: u8) -> Self {
+        assert!((1..=8).contains(&planes), "a framebuffer has 1 to 8 planes, not {}", planes);
+        Framebuffer {
+            width,
+            height,
+            planes,
+            pixels: vec![0; width * height],
+            dirty: Some(Region { x: 0, y: 0, width, height }),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub fn planes(&self) -> u8 {
+        self.planes
+    }
+
+    // Bits a pixel can have set
+    fn mask(&self) -> u8 {
+        (((1u16) << self.planes) - 1) as u8
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    // Bits beyond the planes are dropped
+    pub fn set(&mut self, x: usize, y: usize, value: u8) {
+        let value = value & self.mask();
+        let pixel = &mut self.pixels[y * self.width + x];
+        if *pixel != value {
+            *pixel = value;
+            self.touch(Region { x, y, width: 1, height: 1 });
+        }
+    }
+
+    // XORs the bits into the pixel, the way sprites are drawn. Returns true if that turned any off.
+    pub fn xor(&mut self, x: usize, y: usize, bits: u8) -> bool {
+        let bits = bits & self.mask();
+        let pixel = &mut self.pixels[y * self.width + x];
+        let collision = *pixel & bits != 0;
+        *pixel ^= bits;
+        if bits != 0 {
+            self.touch(Region { x, y, width: 1, height: 1 });
+        }
+        collision
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.fill(0);
+        self.mark_dirty();
+    }
+
+    // All the pixels, row-major
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    // Replaces all the pixels with width * height row-major ones, e.g. from a save state
+    pub fn load(&mut self, pixels: &[u8]) {
+        assert_eq!(pixels.len(), self.pixels.len(), "{}x{} pixels", self.width, self.height);
+        let mask = self.mask();
+        for (pixel, &value) in self.pixels.iter_mut().zip(pixels) {
+            *pixel = value & mask;
+        }
+        self.mark_dirty();
+    }
+
+    // Each row as a slice, from the top
+    pub fn rows(&self) -> ChunksExact<'_, u8> {
+        self.pixels.chunks_exact(self.width.max(1))
+    }
+
+    // Each pixel as (x, y, value), row by row
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, u8)> + '_ {
+        let width = self.width.max(1);
+        self.pixels.iter().enumerate().map(move |(index, &pixel)| (index % width, index / width, pixel))
+    }
+
+    // What has changed since mark_clean(), if anything
+    pub fn dirty(&self) -> Option<Region> {
+        self.dirty
+    }
+
+    pub fn mark_clean(&mut self) {
+        self.dirty = None;
+    }
+
+    // All of it needs drawing again, e.g. because the colours it is drawn in changed
+    pub fn mark_dirty(&mut self) {
+        self.dirty = Some(Region { x: 0, y: 0, width: self.width, height: self.height });
+    }
+
+    fn touch(&mut self, region: Region) {
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(region),
+            None => region,
+        });
+    }
+}
+
+#[cfg(test)]
+#[path = "./framebuffer_test.rs"]
+mod tests;
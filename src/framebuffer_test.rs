@@ -0,0 +1,61 @@
+use super::*;
+
+#[test]
+fn test_new_is_blank_and_dirty() {
+    let framebuffer = Framebuffer::new(128, 64, 1);
+    assert_eq!(framebuffer.size(), (128, 64));
+    assert!(framebuffer.pixels().iter().all(|&pixel| pixel == 0));
+    assert_eq!(framebuffer.dirty(), Some(Region { x: 0, y: 0, width: 128, height: 64 }));
+}
+
+#[test]
+fn test_xor_and_dirty_region() {
+    let mut framebuffer = Framebuffer::new(64, 32, 1);
+    framebuffer.mark_clean();
+    assert!(!framebuffer.xor(3, 4, 1));
+    assert!(!framebuffer.xor(10, 2, 1));
+    assert_eq!(framebuffer.dirty(), Some(Region { x: 3, y: 2, width: 8, height: 3 }));
+    assert!(framebuffer.xor(3, 4, 1));
+    assert_eq!(framebuffer.get(3, 4), 0);
+
+    // Drawing nothing changes nothing
+    framebuffer.mark_clean();
+    assert!(!framebuffer.xor(5, 5, 0));
+    framebuffer.set(10, 2, 1);
+    assert_eq!(framebuffer.dirty(), None);
+}
+
+#[test]
+fn test_planes() {
+    let mut framebuffer = Framebuffer::new(4, 2, 2);
+    framebuffer.set(0, 0, 0xFF);
+    assert_eq!(framebuffer.get(0, 0), 3);
+    assert!(framebuffer.xor(0, 0, 0b110));
+    assert_eq!(framebuffer.get(0, 0), 1);
+}
+
+#[test]
+fn test_rows_and_iter() {
+    let mut framebuffer = Framebuffer::new(3, 2, 1);
+    framebuffer.load(&[1, 0, 0, 0, 0, 1]);
+    assert_eq!(framebuffer.rows().collect::<Vec<_>>(), [&[1, 0, 0][..], &[0, 0, 1][..]]);
+    let lit = framebuffer.iter().filter(|&(_, _, pixel)| pixel != 0).map(|(x, y, _)| (x, y)).collect::<Vec<_>>();
+    assert_eq!(lit, [(0, 0), (2, 1)]);
+}
+
+#[test]
+fn test_equality_ignores_dirty() {
+    let mut framebuffer = Framebuffer::new(8, 8, 1);
+    let other = framebuffer.clone();
+    framebuffer.mark_clean();
+    assert_eq!(framebuffer, other);
+    assert_ne!(framebuffer, Framebuffer::new(8, 4, 1));
+}
+
+#[test]
+fn test_region_union() {
+    let region = Region { x: 2, y: 2, width: 1, height: 1 }.union(Region { x: 0, y: 5, width: 2, height: 2 });
+    assert_eq!(region, Region { x: 0, y: 2, width: 3, height: 5 });
+    assert!(region.contains(1, 6));
+    assert!(!region.contains(3, 2));
+}
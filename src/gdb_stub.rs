@@ -0,0 +1,436 @@
+// GDB remote serial protocol stub, started with --gdb PORT. Attach with:
+//   (gdb) set endian big
+//   (gdb) target remote localhost:PORT
+//
+// The game keeps running until GDB connects, then stops. Registers are v0-vF, i, pc, sp and the
+// delay and sound timers (dt, st), described to GDB in target.xml. Memory is the 4 KB of RAM.
+// Breakpoints are checked against pc before each tick, so the ROM in RAM is never patched,
+// and stepping runs exactly one tick.
+use std::collections::HashSet;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use crate::hex;
+use yarc::cpu::{Registers, CPU};
+use yarc::CHIP8_RAM;
+
+// Name, size in bytes
+const REGISTERS: [(&str, usize); 21] = [
+    ("v0", 1), ("v1", 1), ("v2", 1), ("v3", 1), ("v4", 1), ("v5", 1), ("v6", 1), ("v7", 1),
+    ("v8", 1), ("v9", 1), ("va", 1), ("vb", 1), ("vc", 1), ("vd", 1), ("ve", 1), ("vf", 1),
+    ("i", 2), ("pc", 2), ("sp", 1), ("dt", 1), ("st", 1),
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum State {
+    Running,
+    Halted,
+    Stepping, // run one tick, then stop
+}
+
+// Without --gdb this never stops the game
+pub struct GdbStub {
+    server: Option<Server>,
+}
+
+struct Server {
+    listener: TcpListener,
+    client: Option<TcpStream>,
+    input: Vec<u8>,
+    no_ack: bool, // after QStartNoAckMode
+    state: State,
+    breakpoints: HashSet<u16>,
+    resumed_at: Option<u16>, // don't stop again on the breakpoint we continued from
+}
+
+impl GdbStub {
+    pub fn none() -> Self {
+        GdbStub { server: None }
+    }
+
+    // `address` is a port on localhost, or a full address to listen on
+    pub fn listen(address: Option<&str>) -> Result<Self, String> {
+        let address = match address {
+            Some(address) if address.parse::<u16>().is_ok() => format!("127.0.0.1:{}", address),
+            Some(address) => address.to_string(),
+            None => return Ok(GdbStub::none()),
+        };
+        let listener = TcpListener::bind(&address).map_err(|e| format!("Can't listen for GDB on {}: {}", address, e))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        println!("Listening for GDB on {}", listener.local_addr().map_err(|e| e.to_string())?);
+        Ok(GdbStub {
+            server: Some(Server {
+                listener,
+                client: None,
+                input: Vec::new(),
+                no_ack: false,
+                state: State::Running,
+                breakpoints: HashSet::new(),
+                resumed_at: None,
+            }),
+        })
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.server.as_ref().is_some_and(|server| server.state == State::Halted)
+    }
+
+    // Handles whatever GDB sent. Returns false if the CPU must not tick now.
+    pub fn before_tick(&mut self, cpu: &mut CPU) -> Result<bool, String> {
+        let server = match self.server.as_mut() {
+            Some(server) => server,
+            None => return Ok(true),
+        };
+        server.poll(cpu)?;
+
+        match server.state {
+            State::Halted => Ok(false),
+            State::Stepping => Ok(true),
+            State::Running => {
+                let pc = cpu.registers().pc;
+                if server.client.is_some() && server.breakpoints.contains(&pc) && server.resumed_at != Some(pc) {
+                    server.state = State::Halted;
+                    server.send("T05swbreak:;")?;
+                    return Ok(false);
+                }
+                server.resumed_at = None;
+                Ok(true)
+            }
+        }
+    }
+
+    pub fn after_tick(&mut self, _cpu: &CPU) -> Result<(), String> {
+        match self.server.as_mut() {
+            Some(server) if server.state == State::Stepping => {
+                server.state = State::Halted;
+                server.send("S05")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Server {
+    fn poll(&mut self, cpu: &mut CPU) -> Result<(), String> {
+        if self.client.is_none() {
+            match self.listener.accept() {
+                Ok((client, _)) => {
+                    client.set_nonblocking(true).map_err(|e| e.to_string())?;
+                    let _ = client.set_nodelay(true);
+                    self.client = Some(client);
+                    self.input.clear();
+                    self.no_ack = false;
+                    // GDB expects the target to be stopped when it attaches
+                    self.state = State::Halted;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+
+        let mut buffer = [0u8; 4096];
+        loop {
+            let client = match self.client.as_mut() {
+                Some(client) => client,
+                None => return Ok(()),
+            };
+            match client.read(&mut buffer) {
+                Ok(0) => {
+                    self.disconnect();
+                    return Ok(());
+                }
+                Ok(count) => self.input.extend_from_slice(&buffer[..count]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    self.disconnect();
+                    return Ok(());
+                }
+            }
+        }
+
+        while let Some(packet) = self.next_packet()? {
+            self.handle(&packet, cpu)?;
+        }
+        Ok(())
+    }
+
+    // The game runs on without breakpoints once GDB goes away
+    fn disconnect(&mut self) {
+        self.client = None;
+        self.state = State::Running;
+        self.breakpoints.clear();
+    }
+
+    // Takes the next complete $packet#xx out of the input, acknowledging it
+    fn next_packet(&mut self) -> Result<Option<String>, String> {
+        loop {
+            match self.input.first() {
+                None => return Ok(None),
+                // Ctrl-C while the game runs
+                Some(0x03) => {
+                    self.input.remove(0);
+                    if self.state != State::Halted {
+                        self.state = State::Halted;
+                        self.send("S02")?;
+                    }
+                }
+                Some(b'$') => break,
+                Some(_) => {
+                    self.input.remove(0); // acks and noise
+                }
+            }
+        }
+        let end = match self.input.iter().position(|&byte| byte == b'#') {
+            Some(end) if self.input.len() >= end + 3 => end,
+            _ => return Ok(None),
+        };
+        let packet: Vec<u8> = self.input.drain(..end + 3).collect();
+        let payload = &packet[1..end];
+        let checksum = std::str::from_utf8(&packet[end + 1..]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        let valid = checksum == Some(payload.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)));
+        if !self.no_ack {
+            self.write(if valid { b"+" } else { b"-" })?;
+        }
+        if !valid {
+            return Ok(None);
+        }
+        Ok(Some(String::from_utf8_lossy(payload).into_owned()))
+    }
+
+    fn handle(&mut self, packet: &str, cpu: &mut CPU) -> Result<(), String> {
+        let reply = match packet.as_bytes().first() {
+            Some(b'?') => String::from("S05"),
+            Some(b'g') => encode_registers(&cpu.registers(), 0..REGISTERS.len()),
+            Some(b'G') => match decode_registers(&packet[1..], cpu.registers(), 0..REGISTERS.len()) {
+                Some(registers) => {
+                    cpu.set_registers(&registers);
+                    String::from("OK")
+                }
+                None => String::from("E01"),
+            },
+            Some(b'p') => match usize::from_str_radix(&packet[1..], 16) {
+                Ok(number) if number < REGISTERS.len() => encode_registers(&cpu.registers(), number..number + 1),
+                _ => String::from("E01"),
+            },
+            Some(b'P') => {
+                let mut parts = packet[1..].splitn(2, '=');
+                let number = parts.next().and_then(|number| usize::from_str_radix(number, 16).ok());
+                let registers = match (number, parts.next()) {
+                    (Some(number), Some(value)) if number < REGISTERS.len() => {
+                        decode_registers(value, cpu.registers(), number..number + 1)
+                    }
+                    _ => None,
+                };
+                match registers {
+                    Some(registers) => {
+                        cpu.set_registers(&registers);
+                        String::from("OK")
+                    }
+                    None => String::from("E01"),
+                }
+            }
+            Some(b'm') => match parse_range(&packet[1..]) {
+                Some((start, end)) => hex::encode(&cpu.ram()[start..end]),
+                None => String::from("E01"),
+            },
+            Some(b'M') => {
+                let mut parts = packet[1..].splitn(2, ':');
+                let range = parts.next().and_then(parse_range);
+                let data = parts.next().and_then(hex::decode);
+                match (range, data) {
+                    (Some((start, end)), Some(data)) if data.len() == end - start => {
+                        cpu.ram_mut()[start..end].copy_from_slice(&data);
+                        String::from("OK")
+                    }
+                    _ => String::from("E01"),
+                }
+            }
+            Some(b'c') => {
+                self.state = State::Running;
+                self.resumed_at = Some(cpu.registers().pc);
+                return Ok(()); // the reply comes when the game stops
+            }
+            Some(b's') => {
+                self.state = State::Stepping;
+                return Ok(());
+            }
+            Some(b'Z') | Some(b'z') => match parse_breakpoint(&packet[1..]) {
+                Some(address) => {
+                    if packet.starts_with('Z') {
+                        self.breakpoints.insert(address);
+                    } else {
+                        self.breakpoints.remove(&address);
+                    }
+                    String::from("OK")
+                }
+                None => String::new(), // only software breakpoints
+            },
+            Some(b'H') => String::from("OK"),
+            Some(b'D') => {
+                self.send("OK")?;
+                self.disconnect();
+                return Ok(());
+            }
+            Some(b'k') => {
+                self.disconnect();
+                return Ok(());
+            }
+            _ => self.query(packet),
+        };
+        self.send(&reply)
+    }
+
+    fn query(&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            String::from("PacketSize=1000;qXfer:features:read+;swbreak+;QStartNoAckMode+")
+        } else if packet == "QStartNoAckMode" {
+            self.no_ack = true;
+            String::from("OK")
+        } else if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let xml = target_xml();
+            let mut parts = range.splitn(2, ',');
+            let offset = parts.next().and_then(|offset| usize::from_str_radix(offset, 16).ok());
+            let length = parts.next().and_then(|length| usize::from_str_radix(length, 16).ok());
+            match (offset, length) {
+                (Some(offset), Some(length)) if offset <= xml.len() => {
+                    let end = (offset + length).min(xml.len());
+                    let more = if end < xml.len() { 'm' } else { 'l' };
+                    format!("{}{}", more, &xml[offset..end])
+                }
+                _ => String::from("E01"),
+            }
+        } else if packet == "qAttached" {
+            String::from("1")
+        } else if packet == "qC" {
+            String::from("QC1")
+        } else if packet == "qfThreadInfo" {
+            String::from("m1")
+        } else if packet == "qsThreadInfo" {
+            String::from("l")
+        } else {
+            String::new() // not supported
+        }
+    }
+
+    fn send(&mut self, payload: &str) -> Result<(), String> {
+        let checksum = payload.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        self.write(format!("${}#{:02x}", payload, checksum).as_bytes())
+    }
+
+    // The socket doesn't block, so wait out a full send buffer
+    fn write(&mut self, mut bytes: &[u8]) -> Result<(), String> {
+        while !bytes.is_empty() {
+            let client = match self.client.as_mut() {
+                Some(client) => client,
+                None => return Ok(()),
+            };
+            match client.write(bytes) {
+                Ok(count) => bytes = &bytes[count..],
+                Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(1)),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => {
+                    self.disconnect();
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn target_xml() -> String {
+    let mut xml = String::from(concat!(
+        "<?xml version=\"1.0\"?>\n<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n",
+        "<target version=\"1.0\">\n<feature name=\"org.yarc.chip8\">\n"
+    ));
+    for (number, &(name, size)) in REGISTERS.iter().enumerate() {
+        let kind = match name {
+            "pc" => "code_ptr",
+            "i" => "data_ptr",
+            _ => "uint8",
+        };
+        xml.push_str(&format!("<reg name=\"{}\" bitsize=\"{}\" type=\"{}\" regnum=\"{}\"/>\n", name, size * 8, kind, number));
+    }
+    xml.push_str("</feature>\n</target>\n");
+    xml
+}
+
+fn register_value(registers: &Registers, number: usize) -> u16 {
+    match number {
+        0..=15 => registers.v[number] as u16,
+        16 => registers.i,
+        17 => registers.pc,
+        18 => registers.sp as u16,
+        19 => registers.delay_timer as u16,
+        _ => registers.sound_timer as u16,
+    }
+}
+
+fn set_register_value(registers: &mut Registers, number: usize, value: u16) {
+    match number {
+        0..=15 => registers.v[number] = value as u8,
+        16 => registers.i = value,
+        17 => registers.pc = value,
+        18 => registers.sp = value as u8,
+        19 => registers.delay_timer = value as u8,
+        _ => registers.sound_timer = value as u8,
+    }
+}
+
+// Registers in target (big-endian) byte order, as hex
+fn encode_registers(registers: &Registers, numbers: std::ops::Range<usize>) -> String {
+    numbers
+        .map(|number| {
+            let value = register_value(registers, number);
+            match REGISTERS[number].1 {
+                1 => format!("{:02x}", value),
+                _ => format!("{:04x}", value),
+            }
+        })
+        .collect()
+}
+
+fn decode_registers(hex: &str, mut registers: Registers, numbers: std::ops::Range<usize>) -> Option<Registers> {
+    let bytes = hex::decode(hex)?;
+    let mut bytes = bytes.iter();
+    for number in numbers {
+        let value = match REGISTERS[number].1 {
+            1 => *bytes.next()? as u16,
+            _ => u16::from_be_bytes([*bytes.next()?, *bytes.next()?]),
+        };
+        set_register_value(&mut registers, number, value);
+    }
+    Some(registers)
+}
+
+// "addr,length" in hex, inside RAM
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let mut parts = range.splitn(2, ',');
+    let start = usize::from_str_radix(parts.next()?, 16).ok()?;
+    let length = usize::from_str_radix(parts.next()?, 16).ok()?;
+    let end = start.checked_add(length)?;
+    if end > CHIP8_RAM {
+        return None;
+    }
+    Some((start, end))
+}
+
+// "0,addr,kind" for a software breakpoint
+fn parse_breakpoint(arguments: &str) -> Option<u16> {
+    let mut parts = arguments.split(',');
+    if parts.next()? != "0" {
+        return None;
+    }
+    let address = u16::from_str_radix(parts.next()?, 16).ok()?;
+    if address as usize >= CHIP8_RAM {
+        return None;
+    }
+    Some(address)
+}
+
+#[cfg(test)]
+#[path = "./gdb_stub_test.rs"]
+mod tests;
@@ -0,0 +1,148 @@
+use super::*;
+use std::time::Instant;
+
+// V0 = 5, then V0 += 1 forever
+const ROM: [u8; 6] = [0x60, 0x05, 0x70, 0x01, 0x12, 0x02];
+
+// A stub with GDB played by a plain socket
+struct Harness {
+    stub: GdbStub,
+    cpu: CPU,
+    client: TcpStream,
+    input: Vec<u8>,
+}
+
+impl Harness {
+    fn new() -> Self {
+        let stub = GdbStub::listen(Some("127.0.0.1:0")).unwrap();
+        let address = stub.server.as_ref().unwrap().listener.local_addr().unwrap();
+        let client = TcpStream::connect(address).unwrap();
+        client.set_nonblocking(true).unwrap();
+        let mut cpu = CPU::new();
+        cpu.load_program(&ROM);
+        let mut harness = Harness { stub, cpu, client, input: Vec::new() };
+        assert_eq!(harness.request("QStartNoAckMode"), "OK");
+        harness
+    }
+
+    fn send(&mut self, payload: &str) {
+        let checksum = payload.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        self.client.write_all(format!("${}#{:02x}", payload, checksum).as_bytes()).unwrap();
+    }
+
+    // Ticks like Session does until the stub replies
+    fn reply(&mut self) -> String {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if self.stub.before_tick(&mut self.cpu).unwrap() {
+                self.cpu.tick([false; 16]);
+                self.stub.after_tick(&self.cpu).unwrap();
+            }
+            let mut buffer = [0u8; 4096];
+            if let Ok(count) = self.client.read(&mut buffer) {
+                self.input.extend_from_slice(&buffer[..count]);
+            }
+            let start = self.input.iter().position(|&byte| byte == b'$');
+            let end = self.input.iter().position(|&byte| byte == b'#');
+            if let (Some(start), Some(end)) = (start, end) {
+                if self.input.len() >= end + 3 {
+                    let packet: Vec<u8> = self.input.drain(..end + 3).collect();
+                    return String::from_utf8(packet[start + 1..end].to_vec()).unwrap();
+                }
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("no reply from the stub");
+    }
+
+    fn request(&mut self, payload: &str) -> String {
+        self.send(payload);
+        self.reply()
+    }
+}
+
+#[test]
+fn test_halts_when_gdb_attaches() {
+    let mut harness = Harness::new();
+    assert!(harness.stub.is_halted());
+    assert_eq!(harness.request("?"), "S05");
+    assert!(harness.request("qSupported:swbreak+").contains("qXfer:features:read+"));
+    assert_eq!(harness.cpu.registers().pc, 0x200);
+}
+
+#[test]
+fn test_registers() {
+    let mut harness = Harness::new();
+    assert_eq!(harness.request("s"), "S05");
+    // v0 = 5, the rest 0, i = 0x200 as the CPU starts, pc = 0x202, sp, dt and st 0
+    let expected = format!("05{}02000202000000", "00".repeat(15));
+    assert_eq!(harness.request("g"), expected);
+    assert_eq!(harness.request("p11"), "0202");
+
+    assert_eq!(harness.request("P3=2a"), "OK");
+    assert_eq!(harness.request("P10=0345"), "OK");
+    let registers = harness.cpu.registers();
+    assert_eq!(registers.v[3], 0x2A);
+    assert_eq!(registers.i, 0x345);
+    assert_eq!(harness.request("p15"), "E01");
+}
+
+#[test]
+fn test_memory() {
+    let mut harness = Harness::new();
+    assert_eq!(harness.request("m200,6"), "600570011202");
+    assert_eq!(harness.request("M300,2:beef"), "OK");
+    assert_eq!(&harness.cpu.ram()[0x300..0x302], &[0xBE, 0xEF]);
+    assert_eq!(harness.request("mfff,2"), "E01");
+    assert_eq!(harness.request("M300,2:be"), "E01");
+}
+
+#[test]
+fn test_breakpoints() {
+    let mut harness = Harness::new();
+    assert_eq!(harness.request("Z0,202,2"), "OK");
+    assert_eq!(harness.request("c"), "T05swbreak:;");
+    assert_eq!(harness.cpu.registers().pc, 0x202);
+    assert_eq!(harness.cpu.registers().v[0], 5);
+
+    // Continuing leaves the breakpoint behind, then comes back round the loop to it
+    assert_eq!(harness.request("c"), "T05swbreak:;");
+    assert_eq!(harness.cpu.registers().v[0], 6);
+
+    assert_eq!(harness.request("z0,202,2"), "OK");
+    assert_eq!(harness.request("Z1,202,2"), ""); // no hardware breakpoints
+}
+
+#[test]
+fn test_interrupt_and_detach() {
+    let mut harness = Harness::new();
+    harness.send("c");
+    harness.client.write_all(&[0x03]).unwrap();
+    assert_eq!(harness.reply(), "S02");
+    assert!(harness.stub.is_halted());
+
+    assert_eq!(harness.request("D"), "OK");
+    assert!(!harness.stub.is_halted());
+    assert!(harness.stub.before_tick(&mut harness.cpu).unwrap());
+}
+
+#[test]
+fn test_target_description() {
+    let mut harness = Harness::new();
+    let reply = harness.request("qXfer:features:read:target.xml:0,1000");
+    assert!(reply.starts_with('l'));
+    assert!(reply.contains("<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\" regnum=\"17\"/>"));
+    assert!(reply.contains("<reg name=\"vf\" bitsize=\"8\""));
+
+    // Read in pieces
+    let first = harness.request("qXfer:features:read:target.xml:0,10");
+    assert_eq!(first, format!("m{}", &reply[1..17]));
+}
+
+#[test]
+fn test_without_gdb() {
+    let mut stub = GdbStub::listen(None).unwrap();
+    let mut cpu = CPU::new();
+    assert!(stub.before_tick(&mut cpu).unwrap());
+    assert!(!stub.is_halted());
+}
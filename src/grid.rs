@@ -0,0 +1,75 @@
+// Grid mode, started with --tile: several games side by side in one window, e.g. to compare quirk
+// presets or watch bots play. Each tile runs its own CPU. The keypad goes to the focused tile, or
+// to every tile while broadcasting. This is the layout and the input routing; the SDL front-end
+// runs the games and draws them.
+use sdl2::rect::Rect;
+
+pub struct Grid {
+    count: usize,
+    screen: (usize, usize), // each tile's game screen, in pixels
+    columns: usize,
+    rows: usize,
+    focused: usize,
+    broadcast: bool,
+}
+
+impl Grid {
+    // As square as it goes: 2 tiles side by side, 3 or 4 in a 2x2 grid, up to 6 in a 3x2 one...
+    pub fn new(count: usize, screen: (usize, usize)) -> Self {
+        let count = count.max(1);
+        let columns = (1..).find(|columns| columns * columns >= count).unwrap();
+        let rows = count.div_ceil(columns);
+        Grid { count, screen, columns, rows, focused: 0, broadcast: false }
+    }
+
+    // Where tile `index` goes in a window `width` by `height`. Tiles are scaled up by a whole
+    // number so pixels stay square and sharp, and the grid is centred.
+    pub fn tile_rect(&self, index: usize, width: u32, height: u32) -> Rect {
+        let (screen_width, screen_height) = self.screen;
+        let scale = (width as usize / (self.columns * screen_width)).min(height as usize / (self.rows * screen_height)).max(1);
+        let (tile_width, tile_height) = (screen_width * scale, screen_height * scale);
+        let left = (width as usize).saturating_sub(self.columns * tile_width) / 2;
+        let top = (height as usize).saturating_sub(self.rows * tile_height) / 2;
+        let (column, row) = (index % self.columns, index / self.columns);
+        Rect::new((left + column * tile_width) as i32, (top + row * tile_height) as i32, tile_width as u32, tile_height as u32)
+    }
+
+    // The tile under a point in the window, e.g. one clicked on
+    pub fn tile_at(&self, x: i32, y: i32, width: u32, height: u32) -> Option<usize> {
+        (0..self.count).find(|&index| self.tile_rect(index, width, height).contains_point((x, y)))
+    }
+
+    pub fn focused(&self) -> usize {
+        self.focused
+    }
+
+    pub fn focus(&mut self, index: usize) {
+        self.focused = index % self.count;
+    }
+
+    pub fn focus_next(&mut self) {
+        self.focus(self.focused + 1);
+    }
+
+    pub fn is_broadcasting(&self) -> bool {
+        self.broadcast
+    }
+
+    pub fn toggle_broadcast(&mut self) {
+        self.broadcast = !self.broadcast;
+    }
+
+    // Whether tile `index` gets the keys, and is heard when it beeps
+    pub fn has_input(&self, index: usize) -> bool {
+        self.broadcast || index == self.focused
+    }
+
+    // The keys tile `index` sees
+    pub fn keypad(&self, index: usize, keypad: [bool; 16]) -> [bool; 16] {
+        if self.has_input(index) { keypad } else { [false; 16] }
+    }
+}
+
+#[cfg(test)]
+#[path = "./grid_test.rs"]
+mod tests;
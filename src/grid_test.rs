@@ -0,0 +1,74 @@
+use super::*;
+
+const WIDTH: u32 = 1280;
+const HEIGHT: u32 = 640;
+const SCREEN: (usize, usize) = (64, 32);
+
+#[test]
+fn test_layout() {
+    for (count, columns, rows) in [(1, 1, 1), (2, 2, 1), (3, 2, 2), (4, 2, 2), (5, 3, 2), (9, 3, 3), (10, 4, 3)] {
+        let grid = Grid::new(count, SCREEN);
+        assert_eq!((grid.columns, grid.rows), (columns, rows), "{} tiles", count);
+    }
+}
+
+#[test]
+fn test_tile_rects() {
+    // 4 tiles fill the window exactly
+    let grid = Grid::new(4, SCREEN);
+    assert_eq!(grid.tile_rect(0, WIDTH, HEIGHT), Rect::new(0, 0, 640, 320));
+    assert_eq!(grid.tile_rect(3, WIDTH, HEIGHT), Rect::new(640, 320, 640, 320));
+
+    // 2 tiles are as wide as they can be, and centred top to bottom
+    let grid = Grid::new(2, SCREEN);
+    assert_eq!(grid.tile_rect(0, WIDTH, HEIGHT), Rect::new(0, 160, 640, 320));
+    assert_eq!(grid.tile_rect(1, WIDTH, HEIGHT), Rect::new(640, 160, 640, 320));
+
+    // 5 tiles in a 3x2 grid, scaled 6 times, centred
+    let grid = Grid::new(5, SCREEN);
+    assert_eq!(grid.tile_rect(0, WIDTH, HEIGHT), Rect::new(64, 128, 384, 192));
+    assert_eq!(grid.tile_rect(4, WIDTH, HEIGHT), Rect::new(448, 320, 384, 192));
+
+    // Tiles are the platform's screen scaled up, e.g. 64x64 for hires
+    let grid = Grid::new(2, (64, 64));
+    assert_eq!(grid.tile_rect(0, WIDTH, HEIGHT), Rect::new(0, 0, 640, 640));
+    assert_eq!(grid.tile_rect(1, WIDTH, HEIGHT), Rect::new(640, 0, 640, 640));
+}
+
+#[test]
+fn test_tile_at() {
+    let grid = Grid::new(2, SCREEN);
+    assert_eq!(grid.tile_at(10, 200, WIDTH, HEIGHT), Some(0));
+    assert_eq!(grid.tile_at(700, 400, WIDTH, HEIGHT), Some(1));
+    // Above the tiles
+    assert_eq!(grid.tile_at(10, 10, WIDTH, HEIGHT), None);
+}
+
+#[test]
+fn test_focus() {
+    let mut grid = Grid::new(3, SCREEN);
+    assert_eq!(grid.focused(), 0);
+    grid.focus_next();
+    grid.focus_next();
+    assert_eq!(grid.focused(), 2);
+    grid.focus_next();
+    assert_eq!(grid.focused(), 0);
+    grid.focus(1);
+    assert_eq!(grid.focused(), 1);
+}
+
+#[test]
+fn test_keypad_routing() {
+    let mut grid = Grid::new(3, SCREEN);
+    grid.focus(1);
+    let keypad = [true; 16];
+    assert_eq!(grid.keypad(0, keypad), [false; 16]);
+    assert_eq!(grid.keypad(1, keypad), keypad);
+    assert!(!grid.has_input(2));
+
+    grid.toggle_broadcast();
+    assert!(grid.is_broadcasting());
+    assert!((0..3).all(|index| grid.keypad(index, keypad) == keypad));
+    grid.toggle_broadcast();
+    assert_eq!(grid.keypad(2, keypad), [false; 16]);
+}
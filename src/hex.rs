@@ -0,0 +1,16 @@
+// Bytes as lowercase hex text, two digits a byte, as the GDB stub and the RPC server send them
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// None unless every pair of characters is a hex byte
+pub fn decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+#[cfg(test)]
+#[path = "./hex_test.rs"]
+mod tests;
@@ -0,0 +1,11 @@
+use super::*;
+
+#[test]
+fn test_round_trip() {
+    assert_eq!(encode(&[0x00, 0x1f, 0xA0]), "001fa0");
+    assert_eq!(decode("001fA0"), Some(vec![0x00, 0x1f, 0xA0]));
+    assert_eq!(decode(""), Some(vec![]));
+    assert_eq!(decode("abc"), None);
+    assert_eq!(decode("zz"), None);
+    assert_eq!(decode("é1"), None);
+}
@@ -0,0 +1,285 @@
+// Memory and screen inspector, opened with --inspector: a second window with a live hexdump of
+// RAM, the sprite at I and the screen on a pixel grid, redrawn every 60 Hz frame. Bytes that
+// change light up for a second. Click a byte and type two hex digits to change it; Escape or a
+// click outside the hexdump stops editing. The mouse wheel and Page Up/Down scroll.
+//
+// Everything is written with the CHIP-8 font, which has just the hex digits needed.
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+
+use crate::palette::Palette;
+use yarc::cpu::CPU;
+use yarc::font::FONT_SET;
+use yarc::CHIP8_RAM;
+
+const WINDOW_TITLE: &str = "YARC inspector";
+const SCALE: usize = 2; // window pixels per panel pixel
+const WIDTH: usize = 272;
+const HEIGHT: usize = 372;
+const BYTES_PER_PIXEL: usize = 3; // RGB24
+
+// The hexdump: an address, then 16 bytes a row
+const ROWS: usize = 32;
+const DUMP_X: usize = 4;
+const DUMP_Y: usize = 4;
+const BYTES_X: usize = DUMP_X + 20;
+const CELL_WIDTH: usize = 12; // two digits and a gap
+const ROW_HEIGHT: usize = 7;
+// I, and under it the 15 bytes there drawn as a sprite, the most Dxyn draws
+const SPRITE_X: usize = 232;
+const SPRITE_Y: usize = 4;
+const SPRITE_ROWS: usize = 15;
+const SPRITE_SCALE: usize = 4;
+// The screen, each pixel a square with a grid line round it, in at most this much room
+const VRAM_X: usize = 4;
+const VRAM_Y: usize = 236;
+const VRAM_WIDTH: usize = 256;
+const VRAM_HEIGHT: usize = 128;
+
+const HIGHLIGHT_FRAMES: u8 = 60;
+
+const BACKGROUND: [u8; 3] = [0x20, 0x20, 0x20];
+const TEXT: [u8; 3] = [0xC0, 0xC0, 0xC0];
+const DIM: [u8; 3] = [0x70, 0x70, 0x70];
+const CHANGED: [u8; 3] = [0xFF, 0x90, 0x20];
+const GRID: [u8; 3] = [0x38, 0x38, 0x38];
+
+pub struct Inspector {
+    canvas: Canvas<Window>,
+    texture: Texture,
+    panel: Panel,
+    palette: Palette,
+    open: bool, // closing the window only hides it
+}
+
+impl Inspector {
+    pub fn open(sdl_context: &sdl2::Sdl, palette: Palette, cpu: &CPU) -> Result<Self, String> {
+        let video_subsystem = sdl_context.video()?;
+        let window = video_subsystem
+            .window(WINDOW_TITLE, (WIDTH * SCALE) as u32, (HEIGHT * SCALE) as u32)
+            .build()
+            .map_err(|e| e.to_string())?;
+        // No vsync, or presenting here would hold up the game's window too
+        let canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+        let texture = canvas
+            .texture_creator()
+            .create_texture_streaming(PixelFormatEnum::RGB24, WIDTH as u32, HEIGHT as u32)
+            .map_err(|e| e.to_string())?;
+        Ok(Inspector { canvas, texture, panel: Panel::new(cpu.ram()), palette, open: true })
+    }
+
+    // An event for the inspector's window; edits go straight into the CPU's RAM
+    pub fn handle(&mut self, event: &Event, cpu: &mut CPU) {
+        match *event {
+            Event::Window { win_event: WindowEvent::Close, .. } => {
+                self.canvas.window_mut().hide();
+                self.open = false;
+            }
+            Event::MouseButtonDown { x, y, .. } => self.panel.click(x.max(0) as usize / SCALE, y.max(0) as usize / SCALE),
+            Event::MouseWheel { y, .. } => self.panel.scroll(-2 * y),
+            Event::KeyDown { keycode: Some(key), .. } => match key {
+                Keycode::PageUp => self.panel.scroll(-(ROWS as i32)),
+                Keycode::PageDown => self.panel.scroll(ROWS as i32),
+                Keycode::Escape => self.panel.deselect(),
+                _ => {
+                    if let Some(digit) = hex_digit(key) {
+                        self.panel.type_digit(digit, cpu.ram_mut());
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+
+    // Call once per 60 Hz frame
+    pub fn frame(&mut self, cpu: &CPU) {
+        self.panel.update(cpu.ram());
+        if !self.open {
+            return;
+        }
+        let (panel, palette) = (&self.panel, &self.palette);
+        let _ = self.texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+            panel.render(cpu, palette, buffer, pitch);
+        });
+        self.canvas.clear();
+        let _ = self.canvas.copy(&self.texture, None, None);
+        self.canvas.present();
+    }
+}
+
+fn hex_digit(key: Keycode) -> Option<u8> {
+    let digits = [
+        Keycode::Num0, Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4, Keycode::Num5, Keycode::Num6, Keycode::Num7,
+        Keycode::Num8, Keycode::Num9, Keycode::A, Keycode::B, Keycode::C, Keycode::D, Keycode::E, Keycode::F,
+    ];
+    digits.iter().position(|&digit| digit == key).map(|digit| digit as u8)
+}
+
+// What the window shows, drawn in panel pixels that the GPU scales up
+struct Panel {
+    start: usize, // first address shown, a multiple of 16
+    selected: Option<usize>, // byte being edited
+    typed: Option<u8>, // first digit typed into it
+    last_ram: Vec<u8>, // RAM at the last update()
+    changed: Vec<u8>, // frames left to highlight each byte
+}
+
+impl Panel {
+    fn new(ram: &[u8]) -> Self {
+        Panel { start: 0x200, selected: None, typed: None, last_ram: ram.to_vec(), changed: vec![0; CHIP8_RAM] }
+    }
+
+    // Bytes changed since the last update light up, and fade out over a second
+    fn update(&mut self, ram: &[u8]) {
+        for ((&byte, last), changed) in ram.iter().zip(self.last_ram.iter_mut()).zip(self.changed.iter_mut()) {
+            if byte != *last {
+                *last = byte;
+                *changed = HIGHLIGHT_FRAMES;
+            } else {
+                *changed = changed.saturating_sub(1);
+            }
+        }
+    }
+
+    // Moves the hexdump by `rows` rows, down for positive
+    fn scroll(&mut self, rows: i32) {
+        let last = (CHIP8_RAM / 16 - ROWS) as i32;
+        self.start = ((self.start / 16) as i32 + rows).clamp(0, last) as usize * 16;
+    }
+
+    // The byte drawn at (x, y), if any
+    fn byte_at(&self, x: usize, y: usize) -> Option<usize> {
+        if x < BYTES_X || y < DUMP_Y {
+            return None;
+        }
+        let (column, row) = ((x - BYTES_X) / CELL_WIDTH, (y - DUMP_Y) / ROW_HEIGHT);
+        if column < 16 && row < ROWS { Some(self.start + row * 16 + column) } else { None }
+    }
+
+    // Selects the byte clicked on, or nothing when the click misses
+    fn click(&mut self, x: usize, y: usize) {
+        self.deselect();
+        self.selected = self.byte_at(x, y);
+    }
+
+    fn deselect(&mut self) {
+        self.selected = None;
+        self.typed = None;
+    }
+
+    // The second digit typed writes the byte, and moves on to the next one
+    fn type_digit(&mut self, digit: u8, ram: &mut [u8]) {
+        let address = match self.selected {
+            Some(address) => address,
+            None => return,
+        };
+        match self.typed.take() {
+            None => self.typed = Some(digit),
+            Some(high) => {
+                ram[address] = high << 4 | digit;
+                let next = (address + 1) % CHIP8_RAM;
+                self.selected = Some(next);
+                if next < self.start || next >= self.start + ROWS * 16 {
+                    self.start = 0;
+                    self.scroll((next / 16) as i32);
+                }
+            }
+        }
+    }
+
+    // Draws into an RGB24 buffer WIDTH by HEIGHT, with rows `pitch` bytes apart
+    fn render(&self, cpu: &CPU, palette: &Palette, buffer: &mut [u8], pitch: usize) {
+        let mut pixels = Pixels { buffer, pitch };
+        pixels.fill(0, 0, WIDTH, HEIGHT, BACKGROUND);
+        let ram = cpu.ram();
+
+        for row in 0..ROWS {
+            let y = DUMP_Y + row * ROW_HEIGHT;
+            let row_address = self.start + row * 16;
+            pixels.hex(DUMP_X, y, row_address, 3, DIM);
+            for column in 0..16 {
+                let address = row_address + column;
+                let x = BYTES_X + column * CELL_WIDTH;
+                let selected = self.selected == Some(address);
+                let (background, text) = if selected { (TEXT, BACKGROUND) } else { (blend(BACKGROUND, CHANGED, self.changed[address]), TEXT) };
+                pixels.fill(x - 1, y - 1, CELL_WIDTH - 1, ROW_HEIGHT, background);
+                match self.typed {
+                    Some(high) if selected => pixels.hex(x, y, high as usize, 1, text),
+                    _ => pixels.hex(x, y, ram[address] as usize, 2, text),
+                }
+            }
+        }
+
+        let i = cpu.registers().i as usize;
+        pixels.hex(SPRITE_X, SPRITE_Y, i, 4, TEXT);
+        for row in 0..SPRITE_ROWS {
+            let byte = ram[(i + row) % CHIP8_RAM];
+            for bit in 0..8 {
+                let lit = byte >> (7 - bit) & 1 == 1;
+                let color = if lit { palette.on } else { palette.off };
+                pixels.fill(SPRITE_X + bit * SPRITE_SCALE, SPRITE_Y + 8 + row * SPRITE_SCALE, SPRITE_SCALE, SPRITE_SCALE, color);
+            }
+        }
+
+        // 4 panel pixels a screen pixel for CHIP-8, 2 for the bigger screens, in the screen's
+        // colours on CHIP-8X
+        let (width, height) = cpu.vram().size();
+        let cell = (VRAM_WIDTH / width).min(VRAM_HEIGHT / height);
+        pixels.fill(VRAM_X, VRAM_Y, width * cell + 1, height * cell + 1, GRID);
+        for (x, y, pixel) in cpu.vram().iter() {
+            let color = match cpu.colours() {
+                Some(colours) => colours.rgb(x, y, if pixel != 0 { 255 } else { 0 }),
+                None if pixel != 0 => palette.on,
+                None => palette.off,
+            };
+            pixels.fill(VRAM_X + 1 + x * cell, VRAM_Y + 1 + y * cell, cell - 1, cell - 1, color);
+        }
+    }
+}
+
+// `from` turning into `to` as `frames` goes up to HIGHLIGHT_FRAMES
+fn blend(from: [u8; 3], to: [u8; 3], frames: u8) -> [u8; 3] {
+    let (frames, total) = (frames as u32, HIGHLIGHT_FRAMES as u32);
+    let mut color = [0; 3];
+    for (channel, value) in color.iter_mut().enumerate() {
+        *value = ((from[channel] as u32 * (total - frames) + to[channel] as u32 * frames) / total) as u8;
+    }
+    color
+}
+
+struct Pixels<'a> {
+    buffer: &'a mut [u8],
+    pitch: usize,
+}
+
+impl Pixels<'_> {
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: [u8; 3]) {
+        for row in y..y + height {
+            for column in x..x + width {
+                let offset = row * self.pitch + column * BYTES_PER_PIXEL;
+                self.buffer[offset..offset + BYTES_PER_PIXEL].copy_from_slice(&color);
+            }
+        }
+    }
+
+    // `value` as `digits` hex digits, each 4 by 5 pixels with a pixel between them
+    fn hex(&mut self, x: usize, y: usize, value: usize, digits: usize, color: [u8; 3]) {
+        for place in 0..digits {
+            let digit = value >> (4 * (digits - 1 - place)) & 0xF;
+            for (row, &bits) in FONT_SET[digit * 5..digit * 5 + 5].iter().enumerate() {
+                for column in 0..4 {
+                    if bits & (0x80 >> column) != 0 {
+                        self.fill(x + place * 5 + column, y + row, 1, 1, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "./inspector_test.rs"]
+mod tests;
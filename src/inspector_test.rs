@@ -0,0 +1,118 @@
+use super::*;
+use yarc::platform::Platform;
+
+const PITCH: usize = WIDTH * BYTES_PER_PIXEL;
+
+fn render(panel: &Panel, cpu: &CPU) -> Vec<u8> {
+    let mut buffer = vec![0; PITCH * HEIGHT];
+    panel.render(cpu, &Palette::default(), &mut buffer, PITCH);
+    buffer
+}
+
+fn pixel(buffer: &[u8], x: usize, y: usize) -> [u8; 3] {
+    let offset = y * PITCH + x * BYTES_PER_PIXEL;
+    [buffer[offset], buffer[offset + 1], buffer[offset + 2]]
+}
+
+#[test]
+fn test_byte_at() {
+    let mut panel = Panel::new(&[0; CHIP8_RAM]);
+    assert_eq!(panel.byte_at(BYTES_X, DUMP_Y), Some(0x200));
+    assert_eq!(panel.byte_at(BYTES_X + 15 * CELL_WIDTH + 3, DUMP_Y + 2 * ROW_HEIGHT), Some(0x22F));
+    // The address column and past the last column
+    assert_eq!(panel.byte_at(DUMP_X, DUMP_Y), None);
+    assert_eq!(panel.byte_at(BYTES_X + 16 * CELL_WIDTH, DUMP_Y), None);
+
+    panel.scroll(-100);
+    assert_eq!(panel.byte_at(BYTES_X, DUMP_Y), Some(0));
+    panel.scroll(1000);
+    assert_eq!(panel.byte_at(BYTES_X, DUMP_Y), Some(CHIP8_RAM - ROWS * 16));
+}
+
+#[test]
+fn test_edit() {
+    let mut ram = [0; CHIP8_RAM];
+    let mut panel = Panel::new(&ram);
+    // Nothing selected
+    panel.type_digit(0xA, &mut ram);
+    panel.type_digit(0xB, &mut ram);
+    assert_eq!(ram, [0; CHIP8_RAM]);
+
+    panel.click(BYTES_X + CELL_WIDTH, DUMP_Y);
+    panel.type_digit(0xA, &mut ram);
+    assert_eq!(ram[0x201], 0);
+    panel.type_digit(0xB, &mut ram);
+    assert_eq!(ram[0x201], 0xAB);
+    // On to the next byte
+    panel.type_digit(0x1, &mut ram);
+    panel.type_digit(0x2, &mut ram);
+    assert_eq!(ram[0x202], 0x12);
+
+    // A click elsewhere drops the digit typed
+    panel.type_digit(0xF, &mut ram);
+    panel.click(0, 0);
+    panel.type_digit(0xF, &mut ram);
+    assert_eq!((ram[0x203], panel.selected), (0, None));
+}
+
+#[test]
+fn test_edit_scrolls() {
+    let mut ram = [0; CHIP8_RAM];
+    let mut panel = Panel::new(&ram);
+    panel.click(BYTES_X + 15 * CELL_WIDTH, DUMP_Y + (ROWS - 1) * ROW_HEIGHT);
+    assert_eq!(panel.selected, Some(0x3FF));
+    panel.type_digit(0, &mut ram);
+    panel.type_digit(1, &mut ram);
+    assert_eq!(panel.selected, Some(0x400));
+    assert_eq!(panel.start, 0x400);
+}
+
+#[test]
+fn test_changes_fade() {
+    let mut ram = [0; CHIP8_RAM];
+    let mut panel = Panel::new(&ram);
+    ram[0x300] = 1;
+    panel.update(&ram);
+    assert_eq!(panel.changed[0x300], HIGHLIGHT_FRAMES);
+    assert_eq!(panel.changed[0x301], 0);
+    for _ in 0..HIGHLIGHT_FRAMES {
+        panel.update(&ram);
+    }
+    assert_eq!(panel.changed[0x300], 0);
+}
+
+#[test]
+fn test_render() {
+    let mut cpu = CPU::with_seed(1);
+    // DRW V0, V0, 1 draws its own first byte, 0xD0
+    cpu.load_program(&[0xD0, 0x01]);
+    cpu.tick([false; 16]);
+    let mut panel = Panel::new(cpu.ram());
+    panel.click(BYTES_X, DUMP_Y);
+    let buffer = render(&panel, &cpu);
+
+    // The selected byte's cell is drawn in the text colour
+    assert_eq!(pixel(&buffer, BYTES_X - 1, DUMP_Y - 1), TEXT);
+    assert_eq!(pixel(&buffer, BYTES_X + CELL_WIDTH - 1, DUMP_Y - 1), BACKGROUND);
+    // The sprite at I, and the screen with its grid
+    let on = Palette::default().on;
+    assert_eq!(pixel(&buffer, SPRITE_X, SPRITE_Y + 8), on);
+    assert_eq!(pixel(&buffer, SPRITE_X + 2 * SPRITE_SCALE, SPRITE_Y + 8), Palette::default().off);
+    assert_eq!(pixel(&buffer, VRAM_X, VRAM_Y), GRID);
+    assert_eq!(pixel(&buffer, VRAM_X + 1, VRAM_Y + 1), on);
+}
+
+// CHIP-8X screens are shown in their own colours
+#[test]
+fn test_render_colours() {
+    let mut cpu = CPU::with_seed(1);
+    cpu.set_platform(Platform::Chip8x);
+    cpu.load_program(&[0xD0, 0x01]);
+    cpu.tick([false; 16]);
+    let buffer = render(&Panel::new(cpu.ram()), &cpu);
+
+    let colours = cpu.colours().unwrap();
+    let cell = (VRAM_WIDTH / 64).min(VRAM_HEIGHT / 32);
+    assert_eq!(pixel(&buffer, VRAM_X + 1, VRAM_Y + 1), colours.rgb(0, 0, 255));
+    assert_eq!(pixel(&buffer, VRAM_X + 1 + 2 * cell, VRAM_Y + 1), colours.rgb(2, 0, 0));
+}
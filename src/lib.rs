@@ -0,0 +1,18 @@
+// The CHIP-8 core: everything needed to run a game, without any front-end.
+// The yarc binary, the WebAssembly build and anything else embedding YARC share it.
+pub mod cpu;
+pub mod disasm;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ffi;
+pub mod font; // also used to write hex digits, e.g. in the inspector
+pub mod framebuffer;
+pub mod platform;
+pub mod quirks;
+mod rng;
+pub mod timing;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+
+pub const CHIP8_WIDTH: usize = 64;
+pub const CHIP8_HEIGHT: usize = 32;
+pub const CHIP8_RAM: usize = 4096;
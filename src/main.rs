@@ -1,53 +1,79 @@
-extern crate rand;
+#[cfg(feature = "sdl")]
 extern crate sdl2;
 
 mod drivers; // import all the files we wrote
-mod cpu;
-mod font;
+mod display_filter;
+mod config;
+#[cfg(feature = "sdl")]
+mod post_process; // only the window has room for the effects
+mod palette;
+mod profiler;
+mod capture;
+mod cheats;
+mod frame_clock;
+mod gdb_stub;
+mod hex;
+mod netplay;
+mod rpc;
+mod scripting;
+#[cfg(feature = "sdl")]
+mod inspector;
+#[cfg(feature = "sdl")]
+mod grid; // only the window has room for several games
+#[cfg(feature = "sdl")]
+mod sdl_frontend;
+mod session;
+#[cfg(unix)]
+mod terminal;
+mod trace;
 
-use std::thread; // for concurrency
-use std::time::Duration; // Duration is an enum that supports timing For timing of clock
 use std::env; // for input
-use cpu::CPU; //  added
+use std::process;
 use crate::drivers::*;
+use config::{Config, Frontend, Options};
+use session::Session;
 
-pub const CHIP8_WIDTH: usize = 64;
-pub const CHIP8_HEIGHT: usize = 32;
-pub const CHIP8_RAM: usize = 4096;
+pub use yarc::{CHIP8_WIDTH, CHIP8_HEIGHT};
 
 fn main() {
-    let sleep_duration = Duration::from_millis(1);
-
-    let sdl_context = sdl2::init().unwrap();
-    // Handle error
-
     let args: Vec<String> = env::args().collect(); // Command collect all command line arguments into a vector.
-    let cartridge_filename = &args[1]; // &args[0] is just program name
-
-    let cartridge_driver = CartridgeDriver::new(cartridge_filename);
-    let audio_driver = AudioDriver::new(&sdl_context);
-    let mut graphic_driver = GraphicDriver::new(&sdl_context);
-    let mut input_driver = InputDriver::new(&sdl_context);
-    let mut cpu = CPU::new();
+    let options = Options::parse(&args[1..]).unwrap_or_else(|e| exit_with(&e)); // &args[0] is just program name
+    let config = Config::load(options.config_file.as_deref()).unwrap_or_else(|e| exit_with(&e));
 
-    cpu.load_program(&cartridge_driver.rom);
-
-    while let Ok(keypad) = input_driver.poll() {
+    // Grid mode runs its own CPUs, one per game
+    if !options.tiles.is_empty() {
+        #[cfg(feature = "sdl")]
+        let result = sdl_frontend::run_grid(&options, &config);
+        #[cfg(not(feature = "sdl"))]
+        let result: Result<(), String> = Err(String::from("YARC was built without SDL, which --tile needs"));
+        if let Err(e) = result {
+            exit_with(&e);
+        }
+        return;
+    }
 
-        let output = cpu.tick(keypad);
+    let cartridge_driver = CartridgeDriver::new(&options.rom);
+    let rom = &cartridge_driver.rom[..cartridge_driver.size];
 
-        if output.vram_changed {
-            graphic_driver.draw(output.vram);
-        }
+    let (session, cpu) = Session::start(&options, &config, rom).unwrap_or_else(|e| exit_with(&e));
 
-        if output.beep {
-            audio_driver.start_beep();
-        } else {
-            audio_driver.stop_beep();
-        }
+    let result = match options.frontend {
+        #[cfg(feature = "sdl")]
+        Frontend::Sdl => sdl_frontend::run(cpu, session, &options, &config),
+        #[cfg(not(feature = "sdl"))]
+        Frontend::Sdl => Err(String::from("YARC was built without SDL; use --frontend terminal")),
+        #[cfg(unix)]
+        Frontend::Terminal => terminal::run(cpu, session, &options, &config),
+        #[cfg(not(unix))]
+        Frontend::Terminal => Err(String::from("the terminal frontend needs a Unix terminal")),
+    };
 
-        thread::sleep(sleep_duration);
+    if let Err(e) = result {
+        exit_with(&e);
     }
-
 }
 
+fn exit_with(error: &str) -> ! {
+    eprintln!("{}", error);
+    process::exit(1);
+}
@@ -0,0 +1,304 @@
+// Two-player netplay over TCP: one YARC hosts with --host ADDRESS, the other joins with --connect ADDRESS.
+//
+// Both peers run the same game in lockstep. Each owns some of the 16 keys (--keys, e.g. 14 for the
+// left paddle in PONG2) and sends what it holds for frame f + delay at the start of frame f, so the
+// network has `delay` frames to deliver it. A frame is TICKS_PER_FRAME ticks. The host picks the
+// random seed, the peers check they run the same ROM on the same machine, and every input carries
+// a checksum of RAM and the screen to catch desyncs.
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::cheats::{fnv1a, rom_hash, FNV_OFFSET};
+use crate::config::Config;
+use yarc::cpu::CPU;
+use yarc::font::Font;
+use yarc::platform::Platform;
+use yarc::quirks::Quirks;
+
+pub const TICKS_PER_FRAME: u32 = 16;
+pub const DEFAULT_INPUT_DELAY: u8 = 2;
+pub const DEFAULT_HOST_KEYS: u16 = 0x00FF; // keys 0-7
+pub const DEFAULT_CLIENT_KEYS: u16 = 0xFF00; // keys 8-F
+
+const MAGIC: &[u8; 8] = b"YARCNET2";
+const HELLO_SIZE: usize = 45;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Role {
+    Host(String), // address to listen on
+    Connect(String), // address of the host
+}
+
+// Parses --keys: the hex digits of the keys this player owns, e.g. 14CD
+pub fn parse_keys(digits: &str) -> Result<u16, String> {
+    let mut keys = 0;
+    for digit in digits.chars() {
+        let key = digit.to_digit(16).ok_or_else(|| format!("bad key {} in --keys (expected hex digits)", digit))?;
+        keys |= 1 << key;
+    }
+    Ok(keys)
+}
+
+// Without --host or --connect this passes the keypad straight through
+pub struct Netplay {
+    session: Option<Session>,
+}
+
+struct Session {
+    stream: TcpStream,
+    seed: u64,
+    delay: u32,
+    local_keys: u16, // the keys each peer owns
+    remote_keys: u16,
+    frame: u32,
+    tick: u32, // ticks into the frame
+    keypad: [bool; 16], // both players' keys for this frame
+    local_inputs: VecDeque<u16>, // sent but not played yet
+    checksums: VecDeque<(u32, u64)>, // our checksums of the last few frames
+}
+
+// What both CPUs must have besides the seed. Timing isn't here, as netplay only runs fixed timing.
+pub struct Machine<'a> {
+    pub rom: &'a [u8],
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub font: Font,
+}
+
+struct Hello {
+    rom_hash: String,
+    seed: u64,
+    delay: u8,
+    keys: u16,
+    platform: u8, // index in Platform::ALL
+    quirks: u8, // one bit each
+    font_hash: u64,
+}
+
+impl Netplay {
+    pub fn none() -> Self {
+        Netplay { session: None }
+    }
+
+    // Waits for the other player, or connects to them
+    pub fn start(role: Option<&Role>, keys: Option<u16>, machine: &Machine, config: &Config) -> Result<Self, String> {
+        match role {
+            None => Ok(Netplay::none()),
+            Some(Role::Host(address)) => {
+                let listener = TcpListener::bind(address).map_err(|e| format!("Can't listen on {}: {}", address, e))?;
+                println!("Waiting for the other player on {}", address);
+                let delay = config.get_or("netplay.input_delay", DEFAULT_INPUT_DELAY)?;
+                Netplay::host(&listener, keys.unwrap_or(DEFAULT_HOST_KEYS), machine, delay, clock_seed())
+            }
+            Some(Role::Connect(address)) => {
+                let stream = TcpStream::connect(address).map_err(|e| format!("Can't connect to {}: {}", address, e))?;
+                Netplay::connect(stream, keys.unwrap_or(DEFAULT_CLIENT_KEYS), machine)
+            }
+        }
+    }
+
+    pub fn host(listener: &TcpListener, keys: u16, machine: &Machine, delay: u8, seed: u64) -> Result<Self, String> {
+        let (mut stream, _) = listener.accept().map_err(|e| e.to_string())?;
+        setup(&stream)?;
+        let hello = Hello::new(machine, seed, delay, keys);
+        send_hello(&mut stream, &hello)?;
+        let reply = receive_hello(&mut stream)?;
+        hello.check(&reply)?;
+        Ok(Netplay { session: Some(Session::new(stream, &hello, reply.keys)) })
+    }
+
+    pub fn connect(mut stream: TcpStream, keys: u16, machine: &Machine) -> Result<Self, String> {
+        setup(&stream)?;
+        let hello = receive_hello(&mut stream)?;
+        let reply = Hello::new(machine, hello.seed, hello.delay, keys);
+        send_hello(&mut stream, &reply)?;
+        reply.check(&hello)?;
+        Ok(Netplay { session: Some(Session::new(stream, &reply, hello.keys)) })
+    }
+
+    // Both peers must build their CPU with this seed
+    pub fn seed(&self) -> Option<u64> {
+        self.session.as_ref().map(|session| session.seed)
+    }
+
+    // Call before every tick: returns the keypad to tick with. At the start of each frame
+    // this sends our keys and waits for the other player's.
+    pub fn sync(&mut self, cpu: &CPU, keypad: [bool; 16]) -> Result<[bool; 16], String> {
+        let session = match self.session.as_mut() {
+            Some(session) => session,
+            None => return Ok(keypad),
+        };
+        if session.tick == 0 {
+            session.start_frame(cpu, keypad)?;
+        }
+        session.tick = (session.tick + 1) % TICKS_PER_FRAME;
+        Ok(session.keypad)
+    }
+}
+
+impl Hello {
+    fn new(machine: &Machine, seed: u64, delay: u8, keys: u16) -> Self {
+        let quirks = machine.quirks;
+        let quirks = [quirks.vf_reset, quirks.shift_vy, quirks.memory_increment, quirks.jump_vx, quirks.clip_sprites, quirks.key_release];
+        let font = machine.font.small.iter().chain(machine.font.big.iter()).copied().chain(machine.font.address.to_be_bytes());
+        Hello {
+            rom_hash: rom_hash(machine.rom),
+            seed,
+            delay,
+            keys,
+            platform: Platform::ALL.iter().position(|&platform| platform == machine.platform).unwrap() as u8,
+            quirks: quirks.iter().enumerate().filter(|&(_, &quirk)| quirk).fold(0, |mask, (bit, _)| mask | 1 << bit),
+            font_hash: fnv1a(FNV_OFFSET, &font.collect::<Vec<u8>>()),
+        }
+    }
+
+    // Whether the other player's game would run the same as ours
+    fn check(&self, other: &Hello) -> Result<(), String> {
+        let different = if other.rom_hash != self.rom_hash {
+            "a different ROM"
+        } else if other.platform != self.platform {
+            "a different platform"
+        } else if other.quirks != self.quirks {
+            "different quirks"
+        } else if other.font_hash != self.font_hash {
+            "a different font"
+        } else {
+            return Ok(());
+        };
+        Err(format!("The other player is running {}", different))
+    }
+}
+
+impl Session {
+    fn new(stream: TcpStream, hello: &Hello, remote_keys: u16) -> Self {
+        let delay = hello.delay as u32;
+        Session {
+            stream,
+            seed: hello.seed,
+            delay,
+            local_keys: hello.keys,
+            remote_keys,
+            frame: 0,
+            tick: 0,
+            keypad: [false; 16],
+            // Nobody presses anything during the first `delay` frames
+            local_inputs: (0..delay).map(|_| 0).collect(),
+            checksums: VecDeque::new(),
+        }
+    }
+
+    fn start_frame(&mut self, cpu: &CPU, keypad: [bool; 16]) -> Result<(), String> {
+        let checksum = checksum(cpu);
+        let local = to_mask(&keypad) & self.local_keys;
+
+        let mut message = [0u8; 14];
+        message[..4].copy_from_slice(&(self.frame + self.delay).to_be_bytes());
+        message[4..6].copy_from_slice(&local.to_be_bytes());
+        message[6..].copy_from_slice(&checksum.to_be_bytes());
+        self.stream.write_all(&message).map_err(disconnected)?;
+
+        self.local_inputs.push_back(local);
+        self.checksums.push_back((self.frame, checksum));
+        if self.checksums.len() > self.delay as usize + 1 {
+            self.checksums.pop_front();
+        }
+
+        // The other player sent their keys for this frame at the start of frame - delay
+        let mut remote = 0;
+        if self.frame >= self.delay {
+            let mut message = [0u8; 14];
+            self.stream.read_exact(&mut message).map_err(disconnected)?;
+            let frame = u32::from_be_bytes([message[0], message[1], message[2], message[3]]);
+            if frame != self.frame {
+                return Err(format!("Netplay out of step: got input for frame {} at frame {}", frame, self.frame));
+            }
+            remote = u16::from_be_bytes([message[4], message[5]]) & self.remote_keys;
+
+            let mut remote_checksum = [0u8; 8];
+            remote_checksum.copy_from_slice(&message[6..]);
+            let checked = self.frame - self.delay;
+            let ours = self.checksums.iter().find(|&&(frame, _)| frame == checked).map(|&(_, checksum)| checksum);
+            if ours != Some(u64::from_be_bytes(remote_checksum)) {
+                return Err(format!("Desync: the games differ at frame {}", checked));
+            }
+        }
+
+        let local = self.local_inputs.pop_front().unwrap_or(0);
+        self.keypad = from_mask(local | remote);
+        self.frame += 1;
+        Ok(())
+    }
+}
+
+fn setup(stream: &TcpStream) -> Result<(), String> {
+    // Inputs are tiny and late ones stall both players
+    stream.set_nodelay(true).map_err(|e| e.to_string())?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())
+}
+
+fn send_hello(stream: &mut TcpStream, hello: &Hello) -> Result<(), String> {
+    let mut message = Vec::with_capacity(HELLO_SIZE);
+    message.extend_from_slice(MAGIC);
+    message.extend_from_slice(hello.rom_hash.as_bytes());
+    message.extend_from_slice(&hello.seed.to_be_bytes());
+    message.push(hello.delay);
+    message.extend_from_slice(&hello.keys.to_be_bytes());
+    message.push(hello.platform);
+    message.push(hello.quirks);
+    message.extend_from_slice(&hello.font_hash.to_be_bytes());
+    stream.write_all(&message).map_err(disconnected)
+}
+
+fn receive_hello(stream: &mut TcpStream) -> Result<Hello, String> {
+    let mut message = [0u8; HELLO_SIZE];
+    stream.read_exact(&mut message).map_err(disconnected)?;
+    if &message[..8] != MAGIC {
+        return Err(String::from("The other side is not a YARC netplay peer"));
+    }
+    let mut seed = [0u8; 8];
+    seed.copy_from_slice(&message[24..32]);
+    let mut font_hash = [0u8; 8];
+    font_hash.copy_from_slice(&message[37..45]);
+    Ok(Hello {
+        rom_hash: String::from_utf8_lossy(&message[8..24]).into_owned(),
+        seed: u64::from_be_bytes(seed),
+        delay: message[32],
+        keys: u16::from_be_bytes([message[33], message[34]]),
+        platform: message[35],
+        quirks: message[36],
+        font_hash: u64::from_be_bytes(font_hash),
+    })
+}
+
+fn disconnected(e: std::io::Error) -> String {
+    format!("Lost the other player: {}", e)
+}
+
+// Same game state, same checksum
+fn checksum(cpu: &CPU) -> u64 {
+    let hash = fnv1a(FNV_OFFSET, cpu.ram());
+    fnv1a(hash, cpu.vram().pixels())
+}
+
+fn to_mask(keypad: &[bool; 16]) -> u16 {
+    keypad.iter().enumerate().filter(|&(_, &pressed)| pressed).fold(0, |mask, (key, _)| mask | 1 << key)
+}
+
+fn from_mask(mask: u16) -> [bool; 16] {
+    let mut keypad = [false; 16];
+    for (key, pressed) in keypad.iter_mut().enumerate() {
+        *pressed = mask & (1 << key) != 0;
+    }
+    keypad
+}
+
+fn clock_seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_nanos() as u64).unwrap_or(1)
+}
+
+#[cfg(test)]
+#[path = "./netplay_test.rs"]
+mod tests;
@@ -0,0 +1,101 @@
+use super::*;
+use std::thread;
+use yarc::quirks::QuirkPreset;
+
+// Counts frames with key 0 held in V2, keeps a random number in V0 and stores V0-V3 at 0x300
+const ROM: [u8; 14] = [0xC0, 0xFF, 0xE1, 0x9E, 0x72, 0x01, 0x73, 0x01, 0xA3, 0x00, 0xF3, 0x55, 0x12, 0x00];
+
+// Plays `frames` frames with key 0 held every third frame, optionally changing RAM at one frame.
+// Returns the connection too: closing it while the other peer still reads would reset it.
+fn play(mut netplay: Netplay, frames: u32, tamper_at: Option<u32>) -> Result<(Vec<u8>, Netplay), String> {
+    let mut cpu = CPU::with_seed(netplay.seed().unwrap());
+    cpu.load_program(&ROM);
+    for tick in 0..frames * TICKS_PER_FRAME {
+        let frame = tick / TICKS_PER_FRAME;
+        if tamper_at == Some(frame) {
+            cpu.ram_mut()[0x400] += 1;
+        }
+        let mut keypad = [false; 16];
+        keypad[0] = frame.is_multiple_of(3);
+        let keypad = netplay.sync(&cpu, keypad)?;
+        cpu.tick(keypad);
+    }
+    Ok((cpu.ram().to_vec(), netplay))
+}
+
+// A CHIP-8 with YARC's quirks and font
+fn machine(rom: &[u8]) -> Machine<'_> {
+    Machine { rom, platform: Platform::Chip8, quirks: Quirks::default(), font: Font::default() }
+}
+
+// Runs a host and a client on loopback, each in its own thread
+fn run_pair(client: Machine<'static>, frames: u32, tamper_at: Option<u32>) -> (Result<Vec<u8>, String>, Result<Vec<u8>, String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let host = thread::spawn(move || {
+        let netplay = Netplay::host(&listener, DEFAULT_HOST_KEYS, &machine(&ROM), DEFAULT_INPUT_DELAY, 1234)?;
+        play(netplay, frames, None)
+    });
+    let client = thread::spawn(move || {
+        let netplay = Netplay::connect(TcpStream::connect(address).unwrap(), DEFAULT_CLIENT_KEYS, &client)?;
+        play(netplay, frames, tamper_at)
+    });
+    let (host, client) = (host.join().unwrap(), client.join().unwrap());
+    (host.map(|(ram, _)| ram), client.map(|(ram, _)| ram))
+}
+
+#[test]
+fn test_lockstep() {
+    let (host, client) = run_pair(machine(&ROM), 60, None);
+    let (host, client) = (host.unwrap(), client.unwrap());
+    assert_eq!(host, client);
+    // The host's key 0 reached the client: V2 counted the frames it was not held
+    assert!(host[0x302] > 0 && host[0x302] < host[0x303], "{:?}", &host[0x300..0x304]);
+}
+
+#[test]
+fn test_desync() {
+    let (host, client) = run_pair(machine(&ROM), 60, Some(5));
+    let errors = [host.unwrap_err(), client.unwrap_err()];
+    assert!(errors.iter().any(|e| e.contains("Desync")), "{:?}", errors);
+}
+
+#[test]
+fn test_different_rom() {
+    const OTHER_ROM: [u8; 2] = [0x12, 0x00];
+    let (host, client) = run_pair(machine(&OTHER_ROM), 1, None);
+    assert!(host.unwrap_err().contains("different ROM"));
+    assert!(client.unwrap_err().contains("different ROM"));
+}
+
+#[test]
+fn test_different_machine() {
+    let quirks = QuirkPreset::Vip.quirks();
+    let font = Font { address: 0x50, ..Font::default() };
+    let others = [
+        (Machine { platform: Platform::Hires, ..machine(&ROM) }, "different platform"),
+        (Machine { quirks, ..machine(&ROM) }, "different quirks"),
+        (Machine { font, ..machine(&ROM) }, "different font"),
+    ];
+    for (other, message) in others {
+        let (host, client) = run_pair(other, 1, None);
+        assert!(host.unwrap_err().contains(message), "{}", message);
+        assert!(client.unwrap_err().contains(message), "{}", message);
+    }
+}
+
+#[test]
+fn test_without_netplay() {
+    let mut netplay = Netplay::none();
+    let mut keypad = [false; 16];
+    keypad[3] = true;
+    assert_eq!(netplay.sync(&CPU::with_seed(1), keypad), Ok(keypad));
+    assert_eq!(netplay.seed(), None);
+}
+
+#[test]
+fn test_keys() {
+    assert_eq!(parse_keys("14cD"), Ok(0b0011_0000_0001_0010));
+    assert!(parse_keys("x").is_err());
+    assert_eq!(from_mask(to_mask(&from_mask(0xA5A5))), from_mask(0xA5A5));
+}
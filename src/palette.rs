@@ -0,0 +1,60 @@
+// Colours the screen is drawn with. Shared by the window and by screenshots,
+// so captures look exactly like what is on screen.
+use crate::config::Config;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Palette {
+    pub off: [u8; 3], // colour of a blank pixel
+    pub on: [u8; 3], // colour of a fully lit pixel
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette { off: [0, 0, 0], on: [0, 250, 0] }
+    }
+}
+
+impl Palette {
+    // Reads palette.off and palette.on from the config as RRGGBB hex colours
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let default = Palette::default();
+        Ok(Palette {
+            off: parse_color(config, "palette.off", default.off)?,
+            on: parse_color(config, "palette.on", default.on)?,
+        })
+    }
+
+    // Colour of a pixel with the given brightness, 0 is blank and 255 is fully lit
+    pub fn rgb(&self, brightness: u8) -> [u8; 3] {
+        let mut rgb = [0; 3];
+        for (channel, value) in rgb.iter_mut().enumerate() {
+            let off = self.off[channel] as u32;
+            let on = self.on[channel] as u32;
+            let lit = brightness as u32;
+            *value = ((off * (255 - lit) + on * lit) / 255) as u8;
+        }
+        rgb
+    }
+}
+
+fn parse_color(config: &Config, key: &str, default: [u8; 3]) -> Result<[u8; 3], String> {
+    let value = match config.get(key) {
+        Some(value) => value.trim_start_matches('#'),
+        None => return Ok(default),
+    };
+    let invalid = || format!("invalid colour for {}: {} (expected RRGGBB)", key, value);
+    // Checked as ASCII first, as the channels are sliced by byte
+    if !value.is_ascii() || value.len() != 6 {
+        return Err(invalid());
+    }
+
+    let mut rgb = [0; 3];
+    for (channel, byte) in rgb.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&value[channel * 2..channel * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(rgb)
+}
+
+#[cfg(test)]
+#[path = "./palette_test.rs"]
+mod tests;
@@ -0,0 +1,14 @@
+use super::*;
+
+#[test]
+fn test_parse_color() {
+    let config = Config::parse("palette.on = #FF8000\npalette.off = 102030").unwrap();
+    assert_eq!(parse_color(&config, "palette.on", [0; 3]), Ok([0xFF, 0x80, 0x00]));
+    assert_eq!(parse_color(&config, "palette.off", [0; 3]), Ok([0x10, 0x20, 0x30]));
+    assert_eq!(parse_color(&config, "palette.missing", [1, 2, 3]), Ok([1, 2, 3]));
+
+    for value in ["FF80", "FF800G", "aé€"] {
+        let config = Config::parse(&format!("palette.on = {}", value)).unwrap();
+        assert!(parse_color(&config, "palette.on", [0; 3]).is_err(), "{}", value);
+    }
+}
@@ -0,0 +1,155 @@
+// The machines CHIP-8 ran on, or was stretched to: where programs go, how big the screen is,
+// the instructions they added and whether the screen has colour.
+//
+// - chip8: the COSMAC VIP's CHIP-8, 64x32, programs at 0x200
+// - hires: the 64x64 "HIRES" CHIP-8. ROMs start with 1260 and a patch to the interpreter, so
+//   they are loaded at 0x200 but run from 0x2C0. 0230 clears the screen.
+// - chip10: CHIP-10, 128x64
+// - chip8x: CHIP-8X for the VIP's colour board, programs at 0x300. 02A0 steps the background
+//   colour, 5xy1 adds nibble by nibble and BxyN colours parts of the screen, in place of Bnnn.
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Platform {
+    #[default]
+    Chip8,
+    Hires,
+    Chip10,
+    Chip8x,
+}
+
+// How pixels get their colours
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColourModel {
+    Mono, // the palette's two colours
+    Zones, // CHIP-8X: a background colour, and a foreground colour for each 8x1 strip
+}
+
+impl Platform {
+    pub const ALL: [Platform; 4] = [Platform::Chip8, Platform::Hires, Platform::Chip10, Platform::Chip8x];
+
+    pub fn width(self) -> usize {
+        match self {
+            Platform::Chip10 => 128,
+            _ => 64,
+        }
+    }
+
+    pub fn height(self) -> usize {
+        match self {
+            Platform::Hires | Platform::Chip10 => 64,
+            _ => 32,
+        }
+    }
+
+    // Where ROMs are loaded
+    pub fn load_address(self) -> u16 {
+        match self {
+            Platform::Chip8x => 0x300,
+            _ => 0x200,
+        }
+    }
+
+    // Where they start running
+    pub fn start_address(self) -> u16 {
+        match self {
+            Platform::Hires => 0x2C0,
+            _ => self.load_address(),
+        }
+    }
+
+    pub fn colour_model(self) -> ColourModel {
+        match self {
+            Platform::Chip8x => ColourModel::Zones,
+            _ => ColourModel::Mono,
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Platform::Chip8 => "chip8",
+            Platform::Hires => "hires",
+            Platform::Chip10 => "chip10",
+            Platform::Chip8x => "chip8x",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Platform::ALL
+            .iter()
+            .copied()
+            .find(|platform| platform.to_string() == s)
+            .ok_or_else(|| format!("unknown platform {} (expected chip8, hires, chip10 or chip8x)", s))
+    }
+}
+
+// The VIP colour board's backgrounds, in the order 02A0 steps through them
+const BACKGROUNDS: [[u8; 3]; 4] = [[0x00, 0x00, 0x80], [0x00, 0x00, 0x00], [0x00, 0x80, 0x00], [0x80, 0x00, 0x00]];
+// Its foregrounds: black, red, blue, violet, green, yellow, aqua and white
+const FOREGROUNDS: [[u8; 3]; 8] = [
+    [0x00, 0x00, 0x00], [0xFF, 0x00, 0x00], [0x00, 0x00, 0xFF], [0xFF, 0x00, 0xFF],
+    [0x00, 0xFF, 0x00], [0xFF, 0xFF, 0x00], [0x00, 0xFF, 0xFF], [0xFF, 0xFF, 0xFF],
+];
+const RED: u8 = 1;
+
+// CHIP-8X's colours: the background, and the foreground of each 8x1 strip of the 64x32 screen
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Colours {
+    pub background: u8, // 0 to 3
+    pub strips: [[u8; 8]; 32], // 0 to 7, by row then column
+}
+
+impl Default for Colours {
+    fn default() -> Self {
+        Colours { background: 0, strips: [[RED; 8]; 32] }
+    }
+}
+
+impl Colours {
+    pub fn step_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUNDS.len() as u8;
+    }
+
+    // Colours `width` by `height` strips from (column, row), wrapping round the screen
+    pub fn paint(&mut self, column: usize, row: usize, width: usize, height: usize, colour: u8) {
+        for y in row..row + height {
+            for x in column..column + width {
+                self.strips[y % 32][x % 8] = colour & 7;
+            }
+        }
+    }
+
+    // Every colour a blank or fully lit pixel can have: the backgrounds, then the foregrounds
+    pub fn palette() -> Vec<[u8; 3]> {
+        BACKGROUNDS.iter().chain(FOREGROUNDS.iter()).copied().collect()
+    }
+
+    // Where the colour of the pixel at (x, y), blank or fully lit, is in palette()
+    pub fn palette_index(&self, x: usize, y: usize, lit: bool) -> u8 {
+        if lit {
+            BACKGROUNDS.len() as u8 + self.strips[y % 32][x / 8 % 8]
+        } else {
+            self.background
+        }
+    }
+
+    // The colour of the pixel at (x, y), `brightness` of the way from the background to the foreground
+    pub fn rgb(&self, x: usize, y: usize, brightness: u8) -> [u8; 3] {
+        let background = BACKGROUNDS[self.background as usize];
+        let foreground = FOREGROUNDS[self.strips[y % 32][x / 8 % 8] as usize];
+        let mut rgb = [0; 3];
+        for (channel, value) in rgb.iter_mut().enumerate() {
+            let (from, to) = (background[channel] as u32, foreground[channel] as u32);
+            *value = ((from * (255 - brightness as u32) + to * brightness as u32) / 255) as u8;
+        }
+        rgb
+    }
+}
@@ -0,0 +1,218 @@
+// CRT and LCD effects applied on the CPU to the scaled-up screen.
+// Everything that only depends on the settings (curvature, scanlines, pixel grid, RGB mask)
+// is baked into a lookup table once, so each frame only costs a table walk plus the bloom blur.
+use std::fmt;
+use std::str::FromStr;
+
+use crate::config::Config;
+use yarc::framebuffer::Framebuffer;
+
+const BYTES_PER_PIXEL: usize = 3; // RGB24
+const NO_SOURCE: u32 = u32::MAX; // texel falls outside the curved screen and stays black
+const UNITY_GAIN: u16 = 256;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ShaderMode {
+    Off,
+    Crt,
+    Lcd,
+}
+
+impl ShaderMode {
+    // Order the modes are cycled through at runtime
+    pub fn next(self) -> Self {
+        match self {
+            ShaderMode::Off => ShaderMode::Crt,
+            ShaderMode::Crt => ShaderMode::Lcd,
+            ShaderMode::Lcd => ShaderMode::Off,
+        }
+    }
+}
+
+impl fmt::Display for ShaderMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ShaderMode::Off => "off",
+            ShaderMode::Crt => "crt",
+            ShaderMode::Lcd => "lcd",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ShaderMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(ShaderMode::Off),
+            "crt" => Ok(ShaderMode::Crt),
+            "lcd" => Ok(ShaderMode::Lcd),
+            _ => Err(format!("unknown shader {}", s)),
+        }
+    }
+}
+
+// Strength of each effect, from 0.0 (off) to 1.0 (strongest)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ShaderSettings {
+    pub scanlines: f32, // darkens the edges between pixel rows
+    pub pixel_grid: f32, // dark lines around every pixel, like an LCD
+    pub bloom: f32, // lit pixels glow onto their neighbours
+    pub curvature: f32, // barrel distortion of the whole screen
+    pub rgb_mask: f32, // aperture grille: alternating red, green and blue columns
+}
+
+impl ShaderSettings {
+    pub fn crt() -> Self {
+        ShaderSettings { scanlines: 0.6, pixel_grid: 0.0, bloom: 0.4, curvature: 0.3, rgb_mask: 0.3 }
+    }
+
+    pub fn lcd() -> Self {
+        ShaderSettings { scanlines: 0.0, pixel_grid: 0.5, bloom: 0.1, curvature: 0.0, rgb_mask: 0.0 }
+    }
+
+    // Settings for `mode`, with any of them overridden by `<mode>.<setting>` keys in the config
+    pub fn from_config(mode: ShaderMode, config: &Config) -> Result<Self, String> {
+        let defaults = match mode {
+            ShaderMode::Lcd => ShaderSettings::lcd(),
+            _ => ShaderSettings::crt(),
+        };
+        let key = |setting: &str| format!("{}.{}", mode, setting);
+
+        Ok(ShaderSettings {
+            scanlines: config.get_or(&key("scanlines"), defaults.scanlines)?,
+            pixel_grid: config.get_or(&key("pixel_grid"), defaults.pixel_grid)?,
+            bloom: config.get_or(&key("bloom"), defaults.bloom)?,
+            curvature: config.get_or(&key("curvature"), defaults.curvature)?,
+            rgb_mask: config.get_or(&key("rgb_mask"), defaults.rgb_mask)?,
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Texel {
+    source: u32, // index of the chip8 pixel this texel shows, row-major
+    gain: [u16; 3], // per channel brightness, UNITY_GAIN is unchanged
+}
+
+pub struct PostProcessor {
+    width: usize, // of the chip8 screen, the top left of each frame
+    height: usize,
+    scale: usize,
+    bloom: u16, // out of 256
+    texels: Vec<Texel>, // one per output pixel, row-major
+}
+
+impl PostProcessor {
+    // The output is the `screen_width` by `screen_height` chip8 screen scaled up `scale` times
+    // in both directions
+    pub fn new(settings: &ShaderSettings, screen_width: usize, screen_height: usize, scale: usize) -> Self {
+        let width = screen_width * scale;
+        let height = screen_height * scale;
+        let curvature = settings.curvature.max(0.0) * 0.25;
+        let mut texels = Vec::with_capacity(width * height);
+
+        for out_y in 0..height {
+            for out_x in 0..width {
+                // Position on the screen from -1.0 to 1.0, bent outwards towards the corners
+                let u = (out_x as f32 + 0.5) / width as f32 * 2.0 - 1.0;
+                let v = (out_y as f32 + 0.5) / height as f32 * 2.0 - 1.0;
+                let (u, v) = (u * (1.0 + curvature * v * v), v * (1.0 + curvature * u * u));
+                if u.abs() >= 1.0 || v.abs() >= 1.0 {
+                    texels.push(Texel { source: NO_SOURCE, gain: [0; 3] });
+                    continue;
+                }
+
+                // Which chip8 pixel we land in, and where inside it (0.0 to 1.0)
+                let x = (u + 1.0) / 2.0 * screen_width as f32;
+                let y = (v + 1.0) / 2.0 * screen_height as f32;
+                let (inside_x, inside_y) = (x.fract(), y.fract());
+
+                let mut gain = 1.0;
+                // Scanlines: brightest in the middle of a row, darkest at its edges
+                let from_centre = (inside_y - 0.5) * 2.0;
+                gain *= 1.0 - settings.scanlines * from_centre * from_centre;
+                // Pixel grid: one output pixel wide line on the top and left of every chip8 pixel
+                let line = 1.0 / scale as f32;
+                if inside_x < line || inside_y < line {
+                    gain *= 1.0 - settings.pixel_grid;
+                }
+
+                let mut channels = [gain; 3];
+                // RGB mask: each output column favours one channel
+                for (channel, value) in channels.iter_mut().enumerate() {
+                    if channel != out_x % 3 {
+                        *value *= 1.0 - settings.rgb_mask;
+                    }
+                }
+
+                texels.push(Texel {
+                    source: (y as usize * screen_width + x as usize) as u32,
+                    gain: [to_gain(channels[0]), to_gain(channels[1]), to_gain(channels[2])],
+                });
+            }
+        }
+
+        PostProcessor {
+            width: screen_width,
+            height: screen_height,
+            scale,
+            bloom: to_gain(settings.bloom),
+            texels,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width * self.scale
+    }
+
+    // Renders `frame`, of the screen size given to new(), into an RGB24 buffer of width() x (screen height * scale) pixels whose rows are `pitch` bytes apart.
+    // `color` maps a pixel's position and brightness to its RGB colour.
+    pub fn render<F: Fn(usize, usize, u8) -> [u8; 3]>(&self, frame: &Framebuffer, color: F, buffer: &mut [u8], pitch: usize) {
+        // Colour of every chip8 pixel once bloom has been added
+        let mut colors = vec![[0u8; 3]; self.width * self.height];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let glow = (glow(frame, x, y) * self.bloom) >> 8;
+                let brightness = (frame.get(x, y) as u16 + glow).min(255) as u8;
+                colors[y * self.width + x] = color(x, y, brightness);
+            }
+        }
+
+        let width = self.width();
+        for (out_y, row) in self.texels.chunks(width).enumerate() {
+            for (out_x, texel) in row.iter().enumerate() {
+                let offset = out_y * pitch + out_x * BYTES_PER_PIXEL;
+                let rgb = if texel.source == NO_SOURCE {
+                    [0; 3]
+                } else {
+                    colors[texel.source as usize]
+                };
+                for channel in 0..3 {
+                    let value = (rgb[channel] as u32 * texel.gain[channel] as u32) >> 8;
+                    buffer[offset + channel] = value.min(255) as u8;
+                }
+            }
+        }
+    }
+}
+
+// Average brightness of the 3x3 block around (x, y)
+fn glow(frame: &Framebuffer, x: usize, y: usize) -> u16 {
+    let rows = frame.rows().skip(y.saturating_sub(1)).take(if y == 0 { 2 } else { 3 });
+    let columns = x.saturating_sub(1)..(x + 2).min(frame.width());
+    let total: u16 = rows
+        .flat_map(|row| row[columns.clone()].iter())
+        .map(|&pixel| pixel as u16)
+        .sum();
+    total / 9
+}
+
+fn to_gain(value: f32) -> u16 {
+    (value.clamp(0.0, 1.0) * UNITY_GAIN as f32) as u16
+}
+
+#[cfg(test)]
+#[path = "./post_process_test.rs"]
+mod tests;
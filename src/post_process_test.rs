@@ -0,0 +1,120 @@
+use super::*;
+use crate::{CHIP8_HEIGHT, CHIP8_WIDTH};
+
+const SCALE: usize = 4;
+
+fn off() -> ShaderSettings {
+    ShaderSettings { scanlines: 0.0, pixel_grid: 0.0, bloom: 0.0, curvature: 0.0, rgb_mask: 0.0 }
+}
+
+fn white(_x: usize, _y: usize, p: u8) -> [u8; 3] {
+    [p, p, p]
+}
+
+// A CHIP-8 screen with every pixel at `brightness`
+fn frame_of(brightness: u8) -> Framebuffer {
+    let mut frame = Framebuffer::new(CHIP8_WIDTH, CHIP8_HEIGHT, 8);
+    frame.load(&[brightness; CHIP8_WIDTH * CHIP8_HEIGHT]);
+    frame
+}
+
+fn render(settings: &ShaderSettings, frame: &Framebuffer) -> Vec<u8> {
+    let processor = PostProcessor::new(settings, CHIP8_WIDTH, CHIP8_HEIGHT, SCALE);
+    let pitch = processor.width() * BYTES_PER_PIXEL;
+    let mut buffer = vec![0; pitch * CHIP8_HEIGHT * SCALE];
+    processor.render(frame, white, &mut buffer, pitch);
+    buffer
+}
+
+fn pixel(buffer: &[u8], x: usize, y: usize) -> [u8; 3] {
+    let offset = (y * CHIP8_WIDTH * SCALE + x) * BYTES_PER_PIXEL;
+    [buffer[offset], buffer[offset + 1], buffer[offset + 2]]
+}
+
+#[test]
+fn test_no_effects_is_plain_scaling() {
+    let mut frame = frame_of(0);
+    frame.set(1, 0, 255);
+    let buffer = render(&off(), &frame);
+
+    for y in 0..SCALE {
+        assert_eq!(pixel(&buffer, 0, y), [0; 3]);
+        assert_eq!(pixel(&buffer, SCALE, y), [255; 3]);
+        assert_eq!(pixel(&buffer, 2 * SCALE - 1, y), [255; 3]);
+    }
+}
+
+#[test]
+fn test_scanlines_darken_row_edges() {
+    let frame = frame_of(255);
+    let settings = ShaderSettings { scanlines: 1.0, ..off() };
+    let buffer = render(&settings, &frame);
+
+    let edge = pixel(&buffer, 0, 0)[0];
+    let middle = pixel(&buffer, 0, SCALE / 2)[0];
+    assert!(edge < middle);
+}
+
+#[test]
+fn test_pixel_grid() {
+    let frame = frame_of(255);
+    let settings = ShaderSettings { pixel_grid: 1.0, ..off() };
+    let buffer = render(&settings, &frame);
+
+    assert_eq!(pixel(&buffer, SCALE, 1), [0; 3]);
+    assert_eq!(pixel(&buffer, SCALE + 1, 1), [255; 3]);
+}
+
+#[test]
+fn test_bloom_spreads_to_neighbours() {
+    let mut frame = frame_of(0);
+    frame.set(5, 5, 255);
+    let settings = ShaderSettings { bloom: 1.0, ..off() };
+    let buffer = render(&settings, &frame);
+
+    assert!(pixel(&buffer, 6 * SCALE, 5 * SCALE)[0] > 0);
+    assert_eq!(pixel(&buffer, 8 * SCALE, 5 * SCALE), [0; 3]);
+}
+
+#[test]
+fn test_curvature_blacks_out_corners() {
+    let frame = frame_of(255);
+    let settings = ShaderSettings { curvature: 1.0, ..off() };
+    let buffer = render(&settings, &frame);
+
+    assert_eq!(pixel(&buffer, 0, 0), [0; 3]);
+    assert_eq!(pixel(&buffer, CHIP8_WIDTH * SCALE / 2, CHIP8_HEIGHT * SCALE / 2), [255; 3]);
+}
+
+#[test]
+fn test_rgb_mask() {
+    let frame = frame_of(255);
+    let settings = ShaderSettings { rgb_mask: 1.0, ..off() };
+    let buffer = render(&settings, &frame);
+
+    assert_eq!(pixel(&buffer, 0, 0), [255, 0, 0]);
+    assert_eq!(pixel(&buffer, 1, 0), [0, 255, 0]);
+    assert_eq!(pixel(&buffer, 2, 0), [0, 0, 255]);
+}
+
+#[test]
+fn test_other_screen_sizes() {
+    // CHIP-10's 128x64 screen, with a pixel past where CHIP-8's ends
+    let mut frame = Framebuffer::new(128, 64, 8);
+    frame.set(100, 40, 255);
+    let processor = PostProcessor::new(&off(), 128, 64, 1);
+    assert_eq!(processor.width(), 128);
+    let mut buffer = vec![0; 128 * 64 * BYTES_PER_PIXEL];
+    processor.render(&frame, |x, y, p| [p, x as u8, y as u8], &mut buffer, 128 * BYTES_PER_PIXEL);
+
+    let offset = (40 * 128 + 100) * BYTES_PER_PIXEL;
+    assert_eq!(&buffer[offset..offset + 3], &[255, 100, 40]);
+}
+
+#[test]
+fn test_settings_from_config() {
+    let config = Config::parse("lcd.pixel_grid = 0.9\n").unwrap();
+    let settings = ShaderSettings::from_config(ShaderMode::Lcd, &config).unwrap();
+    assert_eq!(settings.pixel_grid, 0.9);
+    assert_eq!(settings.bloom, ShaderSettings::lcd().bloom);
+}
@@ -0,0 +1,277 @@
+// Profiler for ROM developers, started with --profile NAME. When the game quits it writes
+// NAME.txt, a report of the hottest addresses, the subroutines (found from the 2nnn calls
+// made) and the cycles run per frame, and NAME.html, a heatmap over the disassembly. Cycles are
+// the VIP machine cycles --timing vip charges, whichever timing the game runs with.
+// Both show which ROM bytes were executed, read as data (sprites drawn, Fx65 loads) or never
+// touched; a byte both executed and read counts as executed.
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+
+use yarc::cpu::CPU;
+use yarc::disasm::{disassemble, opcode_at};
+use yarc::CHIP8_RAM;
+
+const HOTTEST: usize = 20;
+const MAX_CALL_DEPTH: usize = 64; // more than the CPU's stack, in case a ROM never returns
+
+const EXECUTED: u8 = 1;
+const READ: u8 = 2;
+
+#[derive(Clone, Copy, Default)]
+struct Subroutine {
+    calls: u64,
+    cycles: u64, // spent in it, not counting the subroutines it calls
+}
+
+// Without --profile this does nothing
+pub struct Profiler {
+    profile: Option<Profile>,
+}
+
+struct Profile {
+    name: String, // the report goes to <name>.txt and <name>.html
+    rom: Vec<u8>,
+    rom_start: usize, // where the ROM was loaded
+    counts: Vec<u64>, // executions per address
+    uses: Vec<u8>, // EXECUTED and READ per byte of RAM
+    subroutines: BTreeMap<u16, Subroutine>,
+    main_cycles: u64, // outside any subroutine
+    calls: Vec<u16>, // subroutines running now, innermost last
+    dropped_calls: usize, // calls made deeper than MAX_CALL_DEPTH, still to return
+    frames: Vec<u64>, // cycles per frame, the one running now last
+    before: Option<(u16, u16)>, // pc and opcode of the instruction running now
+}
+
+impl Profiler {
+    pub fn new(name: Option<&str>, rom: &[u8], rom_start: u16) -> Self {
+        let profile = name.map(|name| Profile {
+            name: name.to_string(),
+            rom: rom.to_vec(),
+            rom_start: rom_start as usize,
+            counts: vec![0; CHIP8_RAM],
+            uses: vec![0; CHIP8_RAM],
+            subroutines: BTreeMap::new(),
+            main_cycles: 0,
+            calls: Vec::new(),
+            dropped_calls: 0,
+            frames: vec![0],
+            before: None,
+        });
+        Profiler { profile }
+    }
+
+    pub fn before_tick(&mut self, cpu: &CPU) {
+        if let Some(profile) = self.profile.as_mut() {
+            // Waiting for a key runs no instruction
+            profile.before = if cpu.waiting_for_key() {
+                None
+            } else {
+                let pc = cpu.registers().pc;
+                Some((pc, opcode_at(cpu.ram(), pc as usize)))
+            };
+        }
+    }
+
+    // `ran` is false if the tick didn't happen after all, e.g. because a script paused the game
+    pub fn after_tick(&mut self, cpu: &CPU, ran: bool) {
+        let profile = match self.profile.as_mut() {
+            Some(profile) => profile,
+            None => return,
+        };
+        let (pc, opcode) = match profile.before.take() {
+            Some(before) if ran => before,
+            _ => return,
+        };
+        profile.count(pc, opcode, cpu.last_ram_read(), cpu.last_tick_cycles());
+    }
+
+    pub fn frame(&mut self) {
+        if let Some(profile) = self.profile.as_mut() {
+            profile.frames.push(0);
+        }
+    }
+
+    // Writes the report; call when the game quits
+    pub fn finish(&self) -> Result<(), String> {
+        let profile = match self.profile.as_ref() {
+            Some(profile) => profile,
+            None => return Ok(()),
+        };
+        for (extension, text) in [("txt", profile.report()), ("html", profile.heatmap())] {
+            let path = format!("{}.{}", profile.name, extension);
+            fs::write(&path, text).map_err(|e| format!("Can't write the profile to {}: {}", path, e))?;
+        }
+        println!("Saved profile {}.txt and {}.html", profile.name, profile.name);
+        Ok(())
+    }
+}
+
+impl Profile {
+    fn count(&mut self, pc: u16, opcode: u16, read: Option<Range<usize>>, cycles: u32) {
+        let cycles = cycles as u64;
+        let address = pc as usize;
+        self.counts[address] += 1;
+        for byte in address..(address + 2).min(CHIP8_RAM) {
+            self.uses[byte] |= EXECUTED;
+        }
+        for byte in read.unwrap_or(0..0).filter(|&byte| byte < CHIP8_RAM) {
+            self.uses[byte] |= READ;
+        }
+
+        match self.calls.last() {
+            Some(subroutine) => self.subroutines.entry(*subroutine).or_default().cycles += cycles,
+            None => self.main_cycles += cycles,
+        }
+        if opcode >> 12 == 0x2 && self.calls.len() < MAX_CALL_DEPTH {
+            let target = opcode & 0xFFF;
+            self.subroutines.entry(target).or_default().calls += 1;
+            self.calls.push(target);
+        } else if opcode >> 12 == 0x2 {
+            self.dropped_calls += 1;
+        } else if opcode == 0x00EE && self.dropped_calls > 0 {
+            self.dropped_calls -= 1; // returns from a call that wasn't pushed
+        } else if opcode == 0x00EE {
+            self.calls.pop();
+        }
+        *self.frames.last_mut().unwrap() += cycles;
+    }
+
+    fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    fn rom_range(&self) -> Range<usize> {
+        self.rom_start..(self.rom_start + self.rom.len()).min(CHIP8_RAM)
+    }
+
+    // Bytes of the ROM executed, only read as data, and never touched
+    fn coverage(&self) -> (usize, usize, usize) {
+        let uses = &self.uses[self.rom_range()];
+        let executed = uses.iter().filter(|&&used| used & EXECUTED != 0).count();
+        let read = uses.iter().filter(|&&used| used == READ).count();
+        (executed, read, uses.len() - executed - read)
+    }
+
+    fn report(&self) -> String {
+        let total = self.total();
+        let share = |count: u64| if total == 0 { 0.0 } else { 100.0 * count as f64 / total as f64 };
+        // The last frame is still running
+        let frames = &self.frames[..self.frames.len() - 1];
+
+        let cycles: u64 = self.frames.iter().sum();
+        let cycle_share = |count: u64| if cycles == 0 { 0.0 } else { 100.0 * count as f64 / cycles as f64 };
+
+        let mut report = format!("Profile of {}: {} instructions, {} cycles, over {} frames\n", self.name, total, cycles, frames.len());
+        if !frames.is_empty() {
+            let average = frames.iter().sum::<u64>() as f64 / frames.len() as f64;
+            let min = frames.iter().min().unwrap();
+            let max = frames.iter().max().unwrap();
+            report.push_str(&format!("Cycles per frame: min {}, average {:.1}, max {}\n", min, average, max));
+        }
+
+        report.push_str("\nHottest addresses\n     count  share  address  instruction\n");
+        let ram = self.ram_image();
+        let mut hottest: Vec<usize> = (0..CHIP8_RAM).filter(|&address| self.counts[address] > 0).collect();
+        hottest.sort_by_key(|&address| (std::cmp::Reverse(self.counts[address]), address));
+        for &address in hottest.iter().take(HOTTEST) {
+            let count = self.counts[address];
+            let opcode = opcode_at(&ram, address);
+            report.push_str(&format!("{:>10} {:>5.1}%  {:#05X}    {}\n", count, share(count), address, disassemble(opcode)));
+        }
+
+        report.push_str("\nSubroutines, by cycles spent in them (not counting the subroutines they call)\n");
+        report.push_str("    cycles  share  calls  address\n");
+        let mut subroutines: Vec<(Option<u16>, Subroutine)> = self.subroutines.iter().map(|(&address, &subroutine)| (Some(address), subroutine)).collect();
+        subroutines.push((None, Subroutine { calls: 0, cycles: self.main_cycles }));
+        subroutines.sort_by_key(|&(address, subroutine)| (std::cmp::Reverse(subroutine.cycles), address));
+        for (address, subroutine) in subroutines {
+            let name = match address {
+                Some(address) => format!("{:#05X}", address),
+                None => String::from("outside any subroutine"),
+            };
+            report.push_str(&format!("{:>10} {:>5.1}% {:>6}  {}\n", subroutine.cycles, cycle_share(subroutine.cycles), subroutine.calls, name));
+        }
+
+        let (executed, read, untouched) = self.coverage();
+        let size = self.rom.len().max(1) as f64;
+        report.push_str(&format!("\nCoverage of the {} ROM bytes\n", self.rom.len()));
+        report.push_str(&format!("  executed       {:>5} ({:.1}%)\n", executed, 100.0 * executed as f64 / size));
+        report.push_str(&format!("  read as data   {:>5} ({:.1}%)\n", read, 100.0 * read as f64 / size));
+        report.push_str(&format!("  never touched  {:>5} ({:.1}%)\n", untouched, 100.0 * untouched as f64 / size));
+        report
+    }
+
+    // The ROM where it sits in RAM, for disassembly
+    fn ram_image(&self) -> Vec<u8> {
+        let mut ram = vec![0; CHIP8_RAM];
+        let range = self.rom_range();
+        ram[range.clone()].copy_from_slice(&self.rom[..range.len()]);
+        ram
+    }
+
+    fn heatmap(&self) -> String {
+        let ram = self.ram_image();
+        let hottest = self.counts.iter().copied().max().unwrap_or(0).max(1) as f64;
+        let mut rows = String::new();
+        let range = self.rom_range();
+        let mut address = range.start;
+        while address < range.end {
+            // Instructions are 2 bytes, unless one starts on the next byte
+            let length = if self.counts[address] == 0 && address + 1 < range.end && self.counts[address + 1] > 0 { 1 } else { 2 };
+            let end = (address + length).min(range.end);
+            let bytes: Vec<String> = ram[address..end].iter().map(|byte| format!("{:02X}", byte)).collect();
+            let count = self.counts[address];
+            let (class, style, text) = if count > 0 {
+                // Hotter is redder, on a log scale
+                let heat = (count as f64).ln_1p() / hottest.ln_1p();
+                let lightness = 95.0 - 45.0 * heat;
+                ("executed", format!(" style=\"background: hsl(0, 100%, {:.0}%)\"", lightness), disassemble(opcode_at(&ram, address)))
+            } else if self.uses[address..end].iter().any(|&used| used & READ != 0) {
+                ("read", String::new(), String::from("data"))
+            } else if self.uses[address..end].iter().any(|&used| used & EXECUTED != 0) {
+                ("executed", String::new(), String::new())
+            } else {
+                ("untouched", String::new(), disassemble(opcode_at(&ram, address)))
+            };
+            let count = if count > 0 { count.to_string() } else { String::new() };
+            rows.push_str(&format!(
+                "<tr class=\"{}\"{}><td>{:03X}</td><td>{}</td><td>{}</td><td class=\"count\">{}</td></tr>\n",
+                class, style, address, bytes.join(" "), escape(&text), count
+            ));
+            address = end;
+        }
+
+        let (executed, read, untouched) = self.coverage();
+        format!(
+            concat!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Profile of {name}</title>\n<style>\n",
+                "body {{ font-family: monospace; }}\n",
+                "td {{ padding: 0 1em; }}\n",
+                "td.count {{ text-align: right; }}\n",
+                "tr.read {{ background: #cde; }}\n",
+                "tr.untouched {{ color: #999; }}\n",
+                "</style>\n</head>\n<body>\n<h1>Profile of {name}</h1>\n",
+                "<p>{total} instructions. ROM bytes: {executed} executed, ",
+                "<span style=\"background: #cde\">{read} read as data</span>, ",
+                "<span style=\"color: #999\">{untouched} never touched</span>.</p>\n",
+                "<table>\n<tr><th>Address</th><th>Bytes</th><th>Instruction</th><th>Count</th></tr>\n",
+                "{rows}</table>\n</body>\n</html>\n"
+            ),
+            name = escape(&self.name),
+            total = self.total(),
+            executed = executed,
+            read = read,
+            untouched = untouched,
+            rows = rows,
+        )
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+#[path = "./profiler_test.rs"]
+mod tests;
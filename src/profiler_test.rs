@@ -0,0 +1,104 @@
+use super::*;
+use std::env;
+
+// Calls a subroutine drawing the sprite at 0x20C forever; the last bytes are never touched
+const ROM: [u8; 16] = [
+    0x22, 0x04, // 200: CALL 0x204
+    0x12, 0x00, // 202: JP 0x200
+    0xA2, 0x0C, // 204: LD I, 0x20C
+    0xD0, 0x01, // 206: DRW V0, V0, 1
+    0x00, 0xEE, // 208: RET
+    0x00, 0x00, // 20A
+    0xF0, 0x90, // 20C: sprite (only the first byte is drawn)
+    0xFF, 0xFF, // 20E
+];
+
+// Runs `ticks` ticks with a frame every 5
+fn profile(ticks: usize) -> Profiler {
+    let mut profiler = Profiler::new(Some("test"), &ROM, 0x200);
+    let mut cpu = CPU::with_seed(1);
+    cpu.load_program(&ROM);
+    for tick in 0..ticks {
+        profiler.before_tick(&cpu);
+        cpu.tick([false; 16]);
+        profiler.after_tick(&cpu, true);
+        if tick % 5 == 4 {
+            profiler.frame();
+        }
+    }
+    profiler
+}
+
+#[test]
+fn test_counts() {
+    // 5 instructions a time round the loop
+    let profiler = profile(50);
+    let profile = profiler.profile.as_ref().unwrap();
+    assert_eq!(profile.total(), 50);
+    for address in [0x200, 0x202, 0x204, 0x206, 0x208] {
+        assert_eq!(profile.counts[address], 10);
+    }
+    // VIP cycles: 52 for LD I, 108 for DRW and 50 for RET, then 66 for CALL and 52 for JP
+    let subroutine = profile.subroutines[&0x204];
+    assert_eq!((subroutine.calls, subroutine.cycles), (10, 2100));
+    assert_eq!(profile.main_cycles, 1180);
+}
+
+// Calls past MAX_CALL_DEPTH aren't followed, and their returns don't end the ones that were
+#[test]
+fn test_deep_calls() {
+    let mut profiler = Profiler::new(Some("test"), &ROM, 0x200);
+    let profile = profiler.profile.as_mut().unwrap();
+    for _ in 0..MAX_CALL_DEPTH + 2 {
+        profile.count(0x200, 0x2204, None, 66);
+    }
+    for _ in 0..2 {
+        profile.count(0x208, 0x00EE, None, 50);
+    }
+    assert_eq!(profile.calls.len(), MAX_CALL_DEPTH);
+    profile.count(0x208, 0x00EE, None, 50);
+    assert_eq!(profile.calls.len(), MAX_CALL_DEPTH - 1);
+}
+
+#[test]
+fn test_coverage() {
+    let profiler = profile(5);
+    let profile = profiler.profile.as_ref().unwrap();
+    // 10 bytes of code, the sprite byte drawn, 5 bytes never touched
+    assert_eq!(profile.coverage(), (10, 1, 5));
+}
+
+#[test]
+fn test_report() {
+    let report = profile(50).profile.unwrap().report();
+    assert!(report.starts_with("Profile of test: 50 instructions, 3280 cycles, over 10 frames\n"));
+    assert!(report.contains("Cycles per frame: min 328, average 328.0, max 328\n"));
+    assert!(report.contains("        10  20.0%  0x200    CALL 0x204\n"));
+    assert!(report.contains("      2100  64.0%     10  0x204\n"));
+    assert!(report.contains("      1180  36.0%      0  outside any subroutine\n"));
+    assert!(report.contains("  never touched      5 (31.2%)\n"));
+}
+
+#[test]
+fn test_heatmap() {
+    let heatmap = profile(50).profile.unwrap().heatmap();
+    assert!(heatmap.contains("<td>206</td><td>D0 01</td><td>DRW V0, V0, 1</td><td class=\"count\">10</td>"));
+    assert!(heatmap.contains("<tr class=\"read\"><td>20C</td><td>F0 90</td><td>data</td>"));
+    assert!(heatmap.contains("<tr class=\"untouched\"><td>20E</td>"));
+}
+
+#[test]
+fn test_finish_writes_both_files() {
+    let name = env::temp_dir().join(format!("yarc-profile-{}", std::process::id()));
+    let name = name.to_string_lossy().into_owned();
+    let mut profiler = profile(10);
+    profiler.profile.as_mut().unwrap().name = name.clone();
+    profiler.finish().unwrap();
+    for extension in ["txt", "html"] {
+        let path = format!("{}.{}", name, extension);
+        assert!(fs::read_to_string(&path).unwrap().contains("Profile of"));
+        fs::remove_file(path).unwrap();
+    }
+
+    Profiler::new(None, &ROM, 0x200).finish().unwrap(); // nothing to write
+}
@@ -0,0 +1,66 @@
+// Behaviours that differ between CHIP-8 interpreters, which games came to depend on.
+// The presets follow the interpreters most games were written for; yarc is how YARC
+// has always behaved.
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    pub vf_reset: bool, // 8xy1, 8xy2 and 8xy3 clear VF
+    pub shift_vy: bool, // 8xy6 and 8xyE shift Vy into Vx, rather than Vx in place
+    pub memory_increment: bool, // Fx55 and Fx65 leave I pointing past the last register
+    pub jump_vx: bool, // Bxnn jumps to xnn + Vx rather than nnn + V0
+    pub clip_sprites: bool, // sprites are cut off at the screen edges rather than wrapping
+    pub key_release: bool, // Fx0A takes a key when it's let go rather than when it's pressed
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        QuirkPreset::Yarc.quirks()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuirkPreset {
+    Yarc,
+    Vip, // the original COSMAC VIP interpreter
+    Schip, // SUPER-CHIP 1.1 on the HP 48
+    Xochip, // XO-CHIP, as in Octo
+}
+
+impl QuirkPreset {
+    pub const ALL: [QuirkPreset; 4] = [QuirkPreset::Yarc, QuirkPreset::Vip, QuirkPreset::Schip, QuirkPreset::Xochip];
+
+    pub fn quirks(self) -> Quirks {
+        match self {
+            QuirkPreset::Yarc => Quirks { vf_reset: false, shift_vy: false, memory_increment: false, jump_vx: false, clip_sprites: false, key_release: false },
+            QuirkPreset::Vip => Quirks { vf_reset: true, shift_vy: true, memory_increment: true, jump_vx: false, clip_sprites: true, key_release: true },
+            QuirkPreset::Schip => Quirks { vf_reset: false, shift_vy: false, memory_increment: false, jump_vx: true, clip_sprites: true, key_release: false },
+            QuirkPreset::Xochip => Quirks { vf_reset: false, shift_vy: true, memory_increment: true, jump_vx: false, clip_sprites: false, key_release: true },
+        }
+    }
+}
+
+impl fmt::Display for QuirkPreset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            QuirkPreset::Yarc => "yarc",
+            QuirkPreset::Vip => "vip",
+            QuirkPreset::Schip => "schip",
+            QuirkPreset::Xochip => "xochip",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for QuirkPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        QuirkPreset::ALL
+            .iter()
+            .copied()
+            .find(|preset| preset.to_string() == s)
+            .ok_or_else(|| format!("unknown quirk preset {} (expected yarc, vip, schip or xochip)", s))
+    }
+}
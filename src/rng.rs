@@ -0,0 +1,47 @@
+// Random numbers for Cxkk. The CPU owns its generator instead of using a global one,
+// so the core runs anywhere (including WebAssembly) and a seed replays the same game.
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+// xorshift64*: tiny, fast and plenty random for games
+#[derive(Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck on 0, and similar seeds should not give similar sequences
+        Rng { state: splitmix64(seed) | 1 }
+    }
+
+    // Seeded from the OS via the standard library's randomly keyed hasher
+    pub fn from_entropy() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(0);
+        Rng::new(hasher.finish())
+    }
+
+    // Raw generator state, so save states carry on with the same numbers
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn from_state(state: u64) -> Self {
+        Rng { state: state | 1 }
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+}
+
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
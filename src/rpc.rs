@@ -0,0 +1,429 @@
+// JSON-RPC 2.0 control API over WebSocket, started with --rpc PORT, so test dashboards and other
+// tools can drive the emulator. Every text message is one request with named params, answered by
+// one response (none for notifications without an id). Methods:
+//
+//   load_rom {path}                   restart with another ROM; not with netplay, cheats, --script or --profile
+//   pause, resume, step {count}       step needs a pause and answers once `count` ticks have run
+//   set_keys {keys}                   keys held down by the tool, e.g. [1, 4]; [] releases them
+//   registers                         {v, i, pc, sp, dt, st, stack}
+//   read_memory {address, length}     bytes of RAM as an array
+//   write_memory {address, data}
+//   vram                              {width, height, rows}; each row a string of 0s and 1s
+//   save_state, load_state {state}    the state as a hex string
+//   subscribe_frames, unsubscribe_frames
+//
+// Subscribers get a "frame" notification {frame, rows} at the end of every frame the screen changed.
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream};
+use std::fs;
+use std::mem;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Map, Value};
+use tungstenite::handshake::server::{NoCallback, ServerHandshake};
+use tungstenite::handshake::{HandshakeError, MidHandshake};
+use tungstenite::{Error, Message, WebSocket};
+
+use crate::hex;
+use yarc::cpu::CPU;
+use yarc::framebuffer::Framebuffer;
+use yarc::CHIP8_RAM;
+
+// Standard JSON-RPC error codes, and ours for requests that can't be done right now
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const FAILED: i64 = -32000;
+
+// Clients that haven't finished the WebSocket handshake by then are dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+type RpcResult = Result<Value, (i64, String)>;
+type Handshake = ServerHandshake<TcpStream, NoCallback>;
+
+// Without --rpc this never stops the game
+pub struct RpcServer {
+    server: Option<Server>,
+}
+
+struct Server {
+    listener: TcpListener,
+    handshakes: Vec<(MidHandshake<Handshake>, Instant)>, // with when each started
+    clients: Vec<Client>,
+    next_client: u64,
+    paused: bool,
+    keys: [bool; 16],
+    step: Option<Step>,
+    frame: u64,
+    sent: Option<Framebuffer>, // screen in the last frame notification
+    fixed_rom: Option<&'static str>, // what keeps load_rom from changing the ROM
+}
+
+struct Client {
+    id: u64,
+    socket: WebSocket<TcpStream>,
+    frames: bool, // subscribed to frame notifications
+    closed: bool,
+}
+
+// A step request still running
+struct Step {
+    client: u64,
+    request: Option<Value>, // None for a notification, which gets no response
+    remaining: u64,
+}
+
+impl RpcServer {
+    pub fn none() -> Self {
+        RpcServer { server: None }
+    }
+
+    // `address` is a port on localhost, or a full address to listen on. `fixed_rom` says what
+    // needs the ROM the game started with, if anything.
+    pub fn listen(address: Option<&str>, fixed_rom: Option<&'static str>) -> Result<Self, String> {
+        let address = match address {
+            Some(address) if address.parse::<u16>().is_ok() => format!("127.0.0.1:{}", address),
+            Some(address) => address.to_string(),
+            None => return Ok(RpcServer::none()),
+        };
+        let listener = TcpListener::bind(&address).map_err(|e| format!("Can't listen for RPC on {}: {}", address, e))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        println!("Listening for RPC on ws://{}", listener.local_addr().map_err(|e| e.to_string())?);
+        Ok(RpcServer {
+            server: Some(Server {
+                listener,
+                handshakes: Vec::new(),
+                clients: Vec::new(),
+                next_client: 0,
+                paused: false,
+                keys: [false; 16],
+                step: None,
+                frame: 0,
+                sent: None,
+                fixed_rom,
+            }),
+        })
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.server.as_ref().is_some_and(|server| server.paused)
+    }
+
+    // Handles the requests that came in. Returns false if the CPU must not tick now.
+    pub fn before_tick(&mut self, cpu: &mut CPU) -> Result<bool, String> {
+        let server = match self.server.as_mut() {
+            Some(server) => server,
+            None => return Ok(true),
+        };
+        server.accept();
+        server.poll(cpu);
+        Ok(!server.paused || server.step.is_some())
+    }
+
+    pub fn after_tick(&mut self, cpu: &CPU) {
+        let server = match self.server.as_mut() {
+            Some(server) => server,
+            None => return,
+        };
+        let done = match server.step.as_mut() {
+            Some(step) => {
+                step.remaining -= 1;
+                step.remaining == 0
+            }
+            None => false,
+        };
+        if done {
+            let mut step = server.step.take().unwrap();
+            if let Some(id) = step.request.take() {
+                let response = json!({"jsonrpc": "2.0", "id": id, "result": registers(cpu)});
+                if let Some(client) = server.clients.iter_mut().find(|client| client.id == step.client) {
+                    client.send(&response);
+                }
+            }
+        }
+    }
+
+    // The keypad with the tool's keys held down too
+    pub fn keypad(&self, mut keypad: [bool; 16]) -> [bool; 16] {
+        if let Some(server) = self.server.as_ref() {
+            for (key, &held) in keypad.iter_mut().zip(server.keys.iter()) {
+                *key |= held;
+            }
+        }
+        keypad
+    }
+
+    // Call once per 60 Hz frame: sends the screen to subscribers if it changed
+    pub fn frame(&mut self, cpu: &CPU) {
+        let server = match self.server.as_mut() {
+            Some(server) => server,
+            None => return,
+        };
+        server.frame += 1;
+        if !server.clients.iter().any(|client| client.frames) || server.sent.as_ref() == Some(cpu.vram()) {
+            return;
+        }
+        server.sent = Some(cpu.vram().clone());
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "frame",
+            "params": {"frame": server.frame, "rows": rows(cpu)},
+        });
+        for client in server.clients.iter_mut().filter(|client| client.frames) {
+            client.send(&notification);
+        }
+    }
+}
+
+impl Server {
+    // Handshakes go a bit further each time round, like the rest of the loop, so a slow or silent
+    // client can't hold up the game
+    fn accept(&mut self) {
+        for (handshake, started) in mem::take(&mut self.handshakes) {
+            if started.elapsed() < HANDSHAKE_TIMEOUT {
+                self.shake_hands(handshake.handshake(), started);
+            }
+        }
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.shake_hands(tungstenite::accept(stream), Instant::now());
+            }
+        }
+    }
+
+    fn shake_hands(&mut self, result: Result<WebSocket<TcpStream>, HandshakeError<Handshake>>, started: Instant) {
+        match result {
+            Ok(socket) => {
+                self.clients.push(Client { id: self.next_client, socket, frames: false, closed: false });
+                self.next_client += 1;
+            }
+            Err(HandshakeError::Interrupted(handshake)) => self.handshakes.push((handshake, started)),
+            Err(HandshakeError::Failure(_)) => {}
+        }
+    }
+
+    fn poll(&mut self, cpu: &mut CPU) {
+        for index in 0..self.clients.len() {
+            for text in self.clients[index].receive() {
+                let id = self.clients[index].id;
+                if let Some(response) = self.handle(&text, id, cpu) {
+                    self.clients[index].send(&response);
+                }
+            }
+        }
+        self.clients.retain(|client| !client.closed);
+        if let Some(step) = self.step.as_ref() {
+            if !self.clients.iter().any(|client| client.id == step.client) {
+                self.step = None;
+            }
+        }
+    }
+
+    // The response to one message, if it needs one now
+    fn handle(&mut self, text: &str, client: u64, cpu: &mut CPU) -> Option<Value> {
+        let request: Value = match serde_json::from_str(text) {
+            Ok(request) => request,
+            Err(e) => return Some(error(Value::Null, PARSE_ERROR, e.to_string())),
+        };
+        let id = request.get("id").cloned();
+        let method = match request.get("method").and_then(Value::as_str) {
+            Some(method) => method,
+            None => return Some(error(id.unwrap_or(Value::Null), INVALID_REQUEST, String::from("no method"))),
+        };
+        let empty = Map::new();
+        let params = match request.get("params") {
+            None | Some(Value::Null) => &empty,
+            Some(Value::Object(params)) => params,
+            Some(_) => return Some(error(id.unwrap_or(Value::Null), INVALID_PARAMS, String::from("params must be an object"))),
+        };
+
+        if method == "step" {
+            let count = match optional_number(params, "count", 1, u32::MAX as u64) {
+                Ok(count) => count,
+                Err((code, message)) => return id.map(|id| error(id, code, message)),
+            };
+            if !self.paused {
+                return id.map(|id| error(id, FAILED, String::from("pause the game before stepping")));
+            }
+            if self.step.is_some() {
+                return id.map(|id| error(id, FAILED, String::from("a step is already running")));
+            }
+            if count == 0 {
+                return id.map(|id| json!({"jsonrpc": "2.0", "id": id, "result": registers(cpu)}));
+            }
+            self.step = Some(Step { client, request: id, remaining: count });
+            return None;
+        }
+
+        let result = self.call(method, params, client, cpu);
+        let id = id?; // notifications get no response
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => error(id, code, message),
+        })
+    }
+
+    fn call(&mut self, method: &str, params: &Map<String, Value>, client: u64, cpu: &mut CPU) -> RpcResult {
+        match method {
+            "load_rom" => {
+                if let Some(reason) = self.fixed_rom {
+                    return Err((FAILED, format!("can't load another ROM with {}", reason)));
+                }
+                let path = params.get("path").and_then(Value::as_str).ok_or_else(|| missing("path"))?;
+                let rom = fs::read(path).map_err(|e| (FAILED, format!("Can't read {}: {}", path, e)))?;
+                let platform = cpu.platform();
+                if rom.len() > CHIP8_RAM - platform.load_address() as usize {
+                    return Err((FAILED, format!("{} is too big for CHIP-8 memory", path)));
+                }
+                cpu.reset();
+                cpu.load_program(&rom);
+                self.sent = None;
+                Ok(json!({"size": rom.len()}))
+            }
+            "pause" => {
+                self.paused = true;
+                Ok(Value::Null)
+            }
+            "resume" => {
+                self.paused = false;
+                Ok(Value::Null)
+            }
+            "set_keys" => {
+                let keys = params.get("keys").and_then(Value::as_array).ok_or_else(|| missing("keys"))?;
+                let mut held = [false; 16];
+                for key in keys {
+                    let key = key.as_u64().filter(|&key| key < 16).ok_or_else(|| invalid("keys are 0 to 15"))?;
+                    held[key as usize] = true;
+                }
+                self.keys = held;
+                Ok(Value::Null)
+            }
+            "registers" => Ok(registers(cpu)),
+            "read_memory" => {
+                let (start, end) = memory_range(params, number(params, "length", CHIP8_RAM as u64)? as usize)?;
+                Ok(json!(&cpu.ram()[start..end]))
+            }
+            "write_memory" => {
+                let data = params.get("data").and_then(Value::as_array).ok_or_else(|| missing("data"))?;
+                let data = data
+                    .iter()
+                    .map(|byte| byte.as_u64().filter(|&byte| byte <= 0xFF).map(|byte| byte as u8))
+                    .collect::<Option<Vec<u8>>>()
+                    .ok_or_else(|| invalid("data must be bytes"))?;
+                let (start, end) = memory_range(params, data.len())?;
+                cpu.ram_mut()[start..end].copy_from_slice(&data);
+                Ok(Value::Null)
+            }
+            "vram" => Ok(json!({"width": cpu.vram().width(), "height": cpu.vram().height(), "rows": rows(cpu)})),
+            "save_state" => Ok(json!({"state": hex::encode(&cpu.save_state())})),
+            "load_state" => {
+                let state = params.get("state").and_then(Value::as_str).ok_or_else(|| missing("state"))?;
+                let state = hex::decode(state).ok_or_else(|| invalid("state must be hex"))?;
+                cpu.load_state(&state).map_err(|e| (FAILED, e))?;
+                Ok(Value::Null)
+            }
+            "subscribe_frames" | "unsubscribe_frames" => {
+                if let Some(client) = self.clients.iter_mut().find(|other| other.id == client) {
+                    client.frames = method == "subscribe_frames";
+                }
+                self.sent = None; // the new subscriber gets the current screen
+                Ok(Value::Null)
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method {}", method))),
+        }
+    }
+}
+
+impl Client {
+    // Text messages received so far
+    fn receive(&mut self) -> Vec<String> {
+        let mut messages = Vec::new();
+        loop {
+            match self.socket.read() {
+                Ok(Message::Text(text)) => messages.push(text),
+                Ok(Message::Close(_)) => {
+                    self.closed = true;
+                    break;
+                }
+                Ok(_) => {} // pings are answered by tungstenite
+                Err(Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+        // Sends whatever a full socket held back, including pongs
+        match self.socket.flush() {
+            Err(Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(_) => self.closed = true,
+            Ok(()) => {}
+        }
+        messages
+    }
+
+    fn send(&mut self, message: &Value) {
+        match self.socket.send(Message::Text(message.to_string())) {
+            // Queued; receive() sends the rest
+            Err(Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(_) => self.closed = true,
+            Ok(()) => {}
+        }
+    }
+}
+
+fn error(id: Value, code: i64, message: String) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn missing(name: &str) -> (i64, String) {
+    (INVALID_PARAMS, format!("missing {}", name))
+}
+
+fn invalid(message: &str) -> (i64, String) {
+    (INVALID_PARAMS, String::from(message))
+}
+
+fn number(params: &Map<String, Value>, name: &str, max: u64) -> Result<u64, (i64, String)> {
+    let value = params.get(name).ok_or_else(|| missing(name))?;
+    value.as_u64().filter(|&value| value <= max).ok_or_else(|| (INVALID_PARAMS, format!("{} must be 0 to {}", name, max)))
+}
+
+fn optional_number(params: &Map<String, Value>, name: &str, default: u64, max: u64) -> Result<u64, (i64, String)> {
+    match params.get(name) {
+        None => Ok(default),
+        Some(_) => number(params, name, max),
+    }
+}
+
+// `length` bytes from params.address, inside RAM
+fn memory_range(params: &Map<String, Value>, length: usize) -> Result<(usize, usize), (i64, String)> {
+    let start = number(params, "address", CHIP8_RAM as u64 - 1)? as usize;
+    if start + length > CHIP8_RAM {
+        return Err(invalid("past the end of RAM"));
+    }
+    Ok((start, start + length))
+}
+
+fn registers(cpu: &CPU) -> Value {
+    let registers = cpu.registers();
+    json!({
+        "v": registers.v,
+        "i": registers.i,
+        "pc": registers.pc,
+        "sp": registers.sp,
+        "dt": registers.delay_timer,
+        "st": registers.sound_timer,
+        "stack": registers.stack,
+    })
+}
+
+// The screen, a string of 0s and 1s a row
+fn rows(cpu: &CPU) -> Vec<String> {
+    cpu.vram().rows().map(|row| row.iter().map(|&pixel| if pixel != 0 { '1' } else { '0' }).collect()).collect()
+}
+
+#[cfg(test)]
+#[path = "./rpc_test.rs"]
+mod tests;
@@ -0,0 +1,240 @@
+use super::*;
+use std::env;
+use std::thread;
+use tungstenite::stream::MaybeTlsStream;
+
+// V0 = 5, then V0 += 1 forever
+const COUNTER: [u8; 6] = [0x60, 0x05, 0x70, 0x01, 0x12, 0x02];
+// VA = 1 once key 0 is held
+const KEY_WAIT: [u8; 8] = [0xE1, 0x9E, 0x12, 0x00, 0x6A, 0x01, 0x12, 0x06];
+
+struct Connection {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    next_id: u64,
+}
+
+impl Connection {
+    fn send(&mut self, text: &str) {
+        self.socket.send(Message::Text(text.to_string())).unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        loop {
+            if let Message::Text(text) = self.socket.read().unwrap() {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    // The response to a request, skipping frame notifications
+    fn call(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        self.send(&json!({"jsonrpc": "2.0", "id": self.next_id, "method": method, "params": params}).to_string());
+        loop {
+            let message = self.receive();
+            if message.get("id").is_some() {
+                assert_eq!(message["id"], self.next_id);
+                return message;
+            }
+        }
+    }
+
+    fn result(&mut self, method: &str, params: Value) -> Value {
+        let response = self.call(method, params);
+        assert!(response.get("error").is_none(), "{} failed: {}", method, response);
+        response["result"].clone()
+    }
+
+    fn error_code(&mut self, method: &str, params: Value) -> i64 {
+        self.call(method, params)["error"]["code"].as_i64().unwrap()
+    }
+}
+
+// Runs `client` in a thread against a server ticking a CPU like Session does
+fn with_client<T: Send + 'static>(rom: &[u8], client: impl FnOnce(Connection) -> T + Send + 'static) -> (T, CPU) {
+    with_server(RpcServer::listen(Some("127.0.0.1:0"), None).unwrap(), rom, client)
+}
+
+fn with_server<T: Send + 'static>(mut server: RpcServer, rom: &[u8], client: impl FnOnce(Connection) -> T + Send + 'static) -> (T, CPU) {
+    let address = server.server.as_ref().unwrap().listener.local_addr().unwrap();
+    let mut cpu = CPU::with_seed(1);
+    cpu.load_program(rom);
+
+    let client = thread::spawn(move || {
+        let (socket, _) = tungstenite::connect(format!("ws://{}", address)).unwrap();
+        client(Connection { socket, next_id: 0 })
+    });
+    let mut ticks = 0;
+    while !client.is_finished() {
+        if server.before_tick(&mut cpu).unwrap() {
+            cpu.tick(server.keypad([false; 16]));
+            server.after_tick(&cpu);
+        }
+        ticks += 1;
+        if ticks % 16 == 0 {
+            server.frame(&cpu);
+        }
+        thread::sleep(Duration::from_micros(100));
+    }
+    (client.join().unwrap(), cpu)
+}
+
+#[test]
+fn test_registers_and_memory() {
+    let (_, cpu) = with_client(&COUNTER, |mut rpc| {
+        let registers = rpc.result("registers", Value::Null);
+        assert_eq!(registers["v"].as_array().unwrap().len(), 16);
+        assert!(registers["pc"].as_u64().unwrap() >= 0x200);
+
+        assert_eq!(rpc.result("read_memory", json!({"address": 0x200, "length": 4})), json!([0x60, 0x05, 0x70, 0x01]));
+        rpc.result("write_memory", json!({"address": 0x300, "data": [1, 2, 3]}));
+        assert_eq!(rpc.result("read_memory", json!({"address": 0x300, "length": 3})), json!([1, 2, 3]));
+
+        assert_eq!(rpc.error_code("read_memory", json!({"address": 0xFFF, "length": 2})), INVALID_PARAMS);
+        assert_eq!(rpc.error_code("write_memory", json!({"address": 0x300, "data": [256]})), INVALID_PARAMS);
+        assert_eq!(rpc.error_code("read_memory", json!({"length": 2})), INVALID_PARAMS);
+    });
+    assert_eq!(&cpu.ram()[0x300..0x303], &[1, 2, 3]);
+}
+
+#[test]
+fn test_bad_requests() {
+    with_client(&COUNTER, |mut rpc| {
+        assert_eq!(rpc.error_code("reboot", Value::Null), METHOD_NOT_FOUND);
+        assert_eq!(rpc.error_code("read_memory", json!([0x200, 4])), INVALID_PARAMS);
+
+        rpc.send("{not json");
+        assert_eq!(rpc.receive()["error"]["code"], PARSE_ERROR);
+
+        // Notifications get no response, so the next message answers the next request
+        rpc.send(r#"{"jsonrpc": "2.0", "method": "pause"}"#);
+        assert_eq!(rpc.result("registers", Value::Null)["i"], 0x200);
+    });
+}
+
+#[test]
+fn test_pause_and_step() {
+    with_client(&COUNTER, |mut rpc| {
+        assert_eq!(rpc.error_code("step", Value::Null), FAILED); // not paused
+
+        rpc.result("pause", Value::Null);
+        let before = rpc.result("registers", Value::Null);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(rpc.result("registers", Value::Null), before);
+
+        // Two steps go once round the loop
+        let after = rpc.result("step", json!({"count": 2}));
+        assert_eq!(after["pc"], before["pc"]);
+        assert_eq!(after["v"][0].as_u64().unwrap(), (before["v"][0].as_u64().unwrap() + 1) % 256);
+        assert_eq!(rpc.result("registers", Value::Null), after);
+
+        rpc.result("resume", Value::Null);
+        thread::sleep(Duration::from_millis(20));
+        assert_ne!(rpc.result("registers", Value::Null)["v"][0], after["v"][0]);
+    });
+}
+
+#[test]
+fn test_step_notifications() {
+    with_client(&COUNTER, |mut rpc| {
+        rpc.result("pause", Value::Null);
+        let before = rpc.result("registers", Value::Null);
+
+        // A step with no id runs but gets no response, not even one with a null id
+        rpc.send(&json!({"jsonrpc": "2.0", "method": "step", "params": {"count": 2}}).to_string());
+        thread::sleep(Duration::from_millis(20));
+        let after = rpc.result("registers", Value::Null);
+        assert_eq!(after["v"][0].as_u64().unwrap(), (before["v"][0].as_u64().unwrap() + 1) % 256);
+
+        // Another step can't start while one is still running
+        rpc.send(&json!({"jsonrpc": "2.0", "method": "step", "params": {"count": u32::MAX}}).to_string());
+        let response = rpc.call("step", Value::Null);
+        assert_eq!(response["error"]["code"], FAILED);
+        assert_eq!(response["error"]["message"], "a step is already running");
+    });
+}
+
+#[test]
+fn test_set_keys() {
+    with_client(&KEY_WAIT, |mut rpc| {
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(rpc.result("registers", Value::Null)["v"][10], 0);
+        assert_eq!(rpc.error_code("set_keys", json!({"keys": [16]})), INVALID_PARAMS);
+
+        rpc.result("set_keys", json!({"keys": [0]}));
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(rpc.result("registers", Value::Null)["v"][10], 1);
+    });
+}
+
+#[test]
+fn test_states_and_screen() {
+    with_client(&COUNTER, |mut rpc| {
+        rpc.result("pause", Value::Null);
+        let state = rpc.result("save_state", Value::Null)["state"].as_str().unwrap().to_string();
+        assert_eq!(state.len(), 2 * yarc::cpu::STATE_SIZE);
+        let registers = rpc.result("registers", Value::Null);
+        rpc.result("step", json!({"count": 10}));
+        rpc.result("load_state", json!({"state": state}));
+        assert_eq!(rpc.result("registers", Value::Null), registers);
+        assert_eq!(rpc.error_code("load_state", json!({"state": "0102"})), FAILED);
+
+        let screen = rpc.result("vram", Value::Null);
+        assert_eq!(screen["width"], 64);
+        let rows = screen["rows"].as_array().unwrap();
+        assert_eq!(rows.len(), 32);
+        assert_eq!(rows[0], "0".repeat(64));
+    });
+}
+
+#[test]
+fn test_frame_notifications() {
+    with_client(&COUNTER, |mut rpc| {
+        rpc.result("subscribe_frames", Value::Null);
+        let frame = rpc.receive();
+        assert_eq!(frame["method"], "frame");
+        assert_eq!(frame["params"]["rows"].as_array().unwrap().len(), 32);
+        assert!(frame["params"]["frame"].as_u64().unwrap() > 0);
+    });
+}
+
+#[test]
+fn test_load_rom() {
+    let path = env::temp_dir().join(format!("yarc-rpc-{}.ch8", std::process::id()));
+    fs::write(&path, KEY_WAIT).unwrap();
+    let path_text = path.to_string_lossy().into_owned();
+    let (_, cpu) = with_client(&COUNTER, move |mut rpc| {
+        assert_eq!(rpc.result("load_rom", json!({"path": path_text})), json!({"size": 8}));
+        assert_eq!(rpc.error_code("load_rom", json!({"path": "/no/such/rom"})), FAILED);
+    });
+    fs::remove_file(&path).unwrap();
+    assert_eq!(&cpu.ram()[0x200..0x208], &KEY_WAIT);
+}
+
+#[test]
+fn test_load_rom_fixed() {
+    let path = env::temp_dir().join(format!("yarc-rpc-fixed-{}.ch8", std::process::id()));
+    fs::write(&path, KEY_WAIT).unwrap();
+    let path_text = path.to_string_lossy().into_owned();
+    let server = RpcServer::listen(Some("127.0.0.1:0"), Some("netplay")).unwrap();
+    let (_, cpu) = with_server(server, &COUNTER, move |mut rpc| {
+        assert_eq!(rpc.error_code("load_rom", json!({"path": path_text})), FAILED);
+    });
+    fs::remove_file(&path).unwrap();
+    assert_eq!(&cpu.ram()[0x200..0x206], &COUNTER);
+}
+
+#[test]
+fn test_slow_handshake() {
+    with_client(&COUNTER, |mut rpc| {
+        // A client that connects and says nothing holds up neither the game nor the other clients
+        let address = match rpc.socket.get_ref() {
+            MaybeTlsStream::Plain(stream) => stream.peer_addr().unwrap(),
+            _ => unreachable!(),
+        };
+        let _silent = TcpStream::connect(address).unwrap();
+        let before = rpc.result("registers", Value::Null);
+        thread::sleep(Duration::from_millis(20));
+        assert_ne!(rpc.result("registers", Value::Null)["v"][0], before["v"][0]);
+    });
+}
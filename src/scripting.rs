@@ -0,0 +1,370 @@
+// Rhai scripts attached to a running game with --script FILE, e.g. to automate playthroughs.
+//
+// The top level of the script runs once at startup and registers callbacks:
+//   on_frame(|| ...)                      every 60 Hz frame
+//   on_instruction(0x2A4, |address| ...)  before the instruction at an address runs
+//   on_write(0x2F4, |address, value| ...) after the game writes a byte of RAM
+// Callbacks (and the top level) can use:
+//   v(x), set_v(x, value), i(), set_i(value), pc(), set_pc(address), peek(address), poke(address, value),
+//   pixel(x, y), key(k), press(k), release(k), frame_count(),
+//   overlay(text), pause(), resume(), save_state(name), load_state(name),
+//   freeze(address, value), patch(address, value), unfreeze(address), cheats(), save_cheats(),
+//   search_start(), search(filter), search_results()
+// save_state and load_state happen when the callback returns. search() narrows RAM search
+// candidates with "equal", "changed", "increased", "decreased" or a value.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use rhai::{Array, Dynamic, Engine, EvalAltResult, FnPtr, AST, INT};
+
+use crate::cheats::{Cheat, CheatKind, CheatList, RamSearch, SearchFilter};
+use crate::config::Config;
+use yarc::cpu::{Registers, CPU};
+use yarc::framebuffer::Framebuffer;
+use yarc::CHIP8_RAM;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+// What a tick did, like cpu::OutputState without holding on to the CPU
+#[derive(Clone, Copy, Default)]
+pub struct Tick {
+    pub vram_changed: bool,
+    pub beep: bool,
+    pub ran: bool, // false if the CPU didn't tick, e.g. while paused
+}
+
+// Without --script this just ticks the CPU
+pub struct Script {
+    runtime: Option<Runtime>,
+}
+
+struct Runtime {
+    engine: Engine,
+    ast: AST,
+    shared: Rc<RefCell<Shared>>,
+    state_prefix: PathBuf, // save states are <state_prefix>-<name>.state
+}
+
+#[derive(Clone, Copy)]
+enum StateRequest {
+    Save,
+    Load,
+}
+
+// State shared with the functions registered in the engine
+struct Shared {
+    // The machine as the running callback sees it; copied back to the CPU afterwards
+    registers: Registers,
+    ram: Vec<u8>,
+    vram: Framebuffer,
+    keypad: [bool; 16],
+    frame_count: INT,
+
+    held: [bool; 16], // keys pressed by the script
+    overlay: String,
+    paused: bool,
+    state_requests: Vec<(StateRequest, String)>,
+    cheats: Rc<RefCell<CheatList>>,
+    search: Option<RamSearch>,
+
+    frame_hooks: Vec<FnPtr>,
+    instruction_hooks: HashMap<usize, Vec<FnPtr>>,
+    write_hooks: HashMap<usize, Vec<FnPtr>>,
+}
+
+impl Script {
+    pub fn none() -> Self {
+        Script { runtime: None }
+    }
+
+    // Loads the script given with --script, if any, and runs its top level
+    pub fn load(path: Option<&str>, rom_path: &str, config: &Config, cpu: &mut CPU, cheats: Rc<RefCell<CheatList>>) -> Result<Self, String> {
+        let path = match path {
+            Some(path) => path,
+            None => return Ok(Script::none()),
+        };
+        let source = fs::read_to_string(path).map_err(|e| format!("Can't read script {}: {}", path, e))?;
+        let state_dir = PathBuf::from(config.get("script.state_dir").unwrap_or("."));
+        Script::from_source(&source, rom_path, &state_dir, cpu, cheats).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn from_source(source: &str, rom_path: &str, state_dir: &Path, cpu: &mut CPU, cheats: Rc<RefCell<CheatList>>) -> Result<Self, String> {
+        let shared = Rc::new(RefCell::new(Shared {
+            registers: cpu.registers(),
+            ram: cpu.ram().to_vec(),
+            vram: cpu.vram().clone(),
+            keypad: [false; 16],
+            frame_count: 0,
+            held: [false; 16],
+            overlay: String::new(),
+            paused: false,
+            state_requests: Vec::new(),
+            cheats,
+            search: None,
+            frame_hooks: Vec::new(),
+            instruction_hooks: HashMap::new(),
+            write_hooks: HashMap::new(),
+        }));
+        let mut engine = Engine::new();
+        register_api(&mut engine, &shared);
+        let ast = engine.compile(source).map_err(|e| e.to_string())?;
+
+        let rom_name = Path::new(rom_path).file_stem().map(|stem| stem.to_string_lossy().into_owned());
+        let mut runtime = Runtime {
+            engine,
+            ast,
+            shared,
+            state_prefix: state_dir.join(rom_name.unwrap_or_else(|| String::from("yarc"))),
+        };
+        runtime.run(cpu, |engine, ast| engine.run_ast(ast))?;
+        Ok(Script { runtime: Some(runtime) })
+    }
+
+    // One CPU tick with the script's keys held and its hooks around it. Does nothing while paused.
+    pub fn tick(&mut self, cpu: &mut CPU, keypad: [bool; 16]) -> Result<Tick, String> {
+        let runtime = match self.runtime.as_mut() {
+            Some(runtime) => runtime,
+            None => {
+                let output = cpu.tick(keypad);
+                return Ok(Tick { vram_changed: output.vram_changed, beep: output.beep, ran: true });
+            }
+        };
+
+        runtime.shared.borrow_mut().keypad = keypad;
+        let pc = cpu.registers().pc as usize;
+        if !cpu.waiting_for_key() && !runtime.paused() {
+            let hooks = runtime.shared.borrow().instruction_hooks.get(&pc).cloned();
+            for hook in hooks.unwrap_or_default() {
+                runtime.run(cpu, |engine, ast| hook.call::<Dynamic>(engine, ast, (pc as INT,)).map(|_| ()))?;
+            }
+        }
+        if runtime.paused() {
+            return Ok(Tick::default());
+        }
+
+        let mut held = runtime.shared.borrow().held;
+        for (key, &pressed) in held.iter_mut().zip(keypad.iter()) {
+            *key |= pressed;
+        }
+        let output = cpu.tick(held);
+        let tick = Tick { vram_changed: output.vram_changed, beep: output.beep, ran: true };
+
+        for address in cpu.last_ram_write().unwrap_or(0..0) {
+            let hooks = runtime.shared.borrow().write_hooks.get(&address).cloned();
+            for hook in hooks.unwrap_or_default() {
+                let value = cpu.ram()[address] as INT;
+                runtime.run(cpu, |engine, ast| hook.call::<Dynamic>(engine, ast, (address as INT, value)).map(|_| ()))?;
+            }
+        }
+        Ok(tick)
+    }
+
+    // Runs the on_frame callbacks; call once per 60 Hz frame. Returns true if the overlay
+    // text or the pause changed, so the front-end can update its status line.
+    pub fn frame(&mut self, cpu: &mut CPU) -> Result<bool, String> {
+        let runtime = match self.runtime.as_mut() {
+            Some(runtime) => runtime,
+            None => return Ok(false),
+        };
+        let before = (runtime.shared.borrow().overlay.clone(), runtime.paused());
+        let hooks = runtime.shared.borrow().frame_hooks.clone();
+        for hook in hooks {
+            runtime.run(cpu, |engine, ast| hook.call::<Dynamic>(engine, ast, ()).map(|_| ()))?;
+        }
+        let mut shared = runtime.shared.borrow_mut();
+        shared.frame_count += 1;
+        Ok(before != (shared.overlay.clone(), shared.paused))
+    }
+
+    // Text for the status line: the script's overlay, and whether it paused the game
+    pub fn overlay(&self) -> String {
+        match self.runtime.as_ref() {
+            Some(runtime) => {
+                let shared = runtime.shared.borrow();
+                let paused = if shared.paused { "[PAUSED] " } else { "" };
+                format!("{}{}", paused, shared.overlay)
+            }
+            None => String::new(),
+        }
+    }
+}
+
+impl Runtime {
+    fn paused(&self) -> bool {
+        self.shared.borrow().paused
+    }
+
+    // Runs script code against the CPU: copies the machine in, runs, copies it back out
+    fn run<F>(&mut self, cpu: &mut CPU, script: F) -> Result<(), String>
+    where
+        F: FnOnce(&Engine, &AST) -> ScriptResult<()>,
+    {
+        {
+            let mut shared = self.shared.borrow_mut();
+            shared.registers = cpu.registers();
+            shared.ram.copy_from_slice(cpu.ram());
+            shared.vram.clone_from(cpu.vram());
+        }
+        script(&self.engine, &self.ast).map_err(|e| e.to_string())?;
+
+        let requests = {
+            let mut shared = self.shared.borrow_mut();
+            cpu.set_registers(&shared.registers);
+            cpu.ram_mut().copy_from_slice(&shared.ram);
+            std::mem::take(&mut shared.state_requests)
+        };
+        for (request, name) in requests {
+            let path = PathBuf::from(format!("{}-{}.state", self.state_prefix.display(), name));
+            match request {
+                StateRequest::Save => fs::write(&path, cpu.save_state())
+                    .map_err(|e| format!("Can't save state to {}: {}", path.display(), e))?,
+                StateRequest::Load => {
+                    let state = fs::read(&path).map_err(|e| format!("Can't load state from {}: {}", path.display(), e))?;
+                    cpu.load_state(&state).map_err(|e| format!("{}: {}", path.display(), e))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn register_api(engine: &mut Engine, shared: &Rc<RefCell<Shared>>) {
+    // Registers a function with its own handle on the shared state
+    macro_rules! api {
+        ($name:expr, |$shared:ident $(, $arg:ident: $ty:ty)*| -> $ret:ty $body:block) => {{
+            let handle = shared.clone();
+            engine.register_fn($name, move |$($arg: $ty),*| -> $ret {
+                #[allow(unused_mut)]
+                let mut $shared = handle.borrow_mut();
+                $body
+            });
+        }};
+        ($name:expr, |$shared:ident $(, $arg:ident: $ty:ty)*| $body:expr) => {
+            api!($name, |$shared $(, $arg: $ty)*| -> _ { $body })
+        };
+    }
+
+    api!("on_frame", |s, hook: FnPtr| s.frame_hooks.push(hook));
+    api!("on_instruction", |s, address: INT, hook: FnPtr| -> ScriptResult<()> {
+        s.instruction_hooks.entry(check(address, CHIP8_RAM, "address")?).or_default().push(hook);
+        Ok(())
+    });
+    api!("on_write", |s, address: INT, hook: FnPtr| -> ScriptResult<()> {
+        s.write_hooks.entry(check(address, CHIP8_RAM, "address")?).or_default().push(hook);
+        Ok(())
+    });
+
+    api!("v", |s, x: INT| -> ScriptResult<INT> { Ok(s.registers.v[check(x, 16, "register")?] as INT) });
+    api!("set_v", |s, x: INT, value: INT| -> ScriptResult<()> {
+        s.registers.v[check(x, 16, "register")?] = check(value, 0x100, "byte")? as u8;
+        Ok(())
+    });
+    api!("i", |s| s.registers.i as INT);
+    api!("set_i", |s, value: INT| -> ScriptResult<()> {
+        s.registers.i = check(value, 0x10000, "value")? as u16;
+        Ok(())
+    });
+    api!("pc", |s| s.registers.pc as INT);
+    api!("set_pc", |s, address: INT| -> ScriptResult<()> {
+        s.registers.pc = check(address, CHIP8_RAM, "address")? as u16;
+        Ok(())
+    });
+    api!("peek", |s, address: INT| -> ScriptResult<INT> { Ok(s.ram[check(address, CHIP8_RAM, "address")?] as INT) });
+    api!("poke", |s, address: INT, value: INT| -> ScriptResult<()> {
+        let address = check(address, CHIP8_RAM, "address")?;
+        s.ram[address] = check(value, 0x100, "byte")? as u8;
+        Ok(())
+    });
+    api!("pixel", |s, x: INT, y: INT| -> ScriptResult<bool> {
+        Ok(s.vram.get(check(x, s.vram.width(), "x")?, check(y, s.vram.height(), "y")?) != 0)
+    });
+
+    api!("key", |s, key: INT| -> ScriptResult<bool> {
+        let key = check(key, 16, "key")?;
+        Ok(s.keypad[key] || s.held[key])
+    });
+    api!("press", |s, key: INT| -> ScriptResult<()> {
+        s.held[check(key, 16, "key")?] = true;
+        Ok(())
+    });
+    api!("release", |s, key: INT| -> ScriptResult<()> {
+        s.held[check(key, 16, "key")?] = false;
+        Ok(())
+    });
+    api!("frame_count", |s| s.frame_count);
+
+    api!("overlay", |s, text: &str| s.overlay = text.to_string());
+    api!("pause", |s| s.paused = true);
+    api!("resume", |s| s.paused = false);
+    api!("save_state", |s, name: &str| -> ScriptResult<()> {
+        s.state_requests.push((StateRequest::Save, check_name(name)?));
+        Ok(())
+    });
+    api!("load_state", |s, name: &str| -> ScriptResult<()> {
+        s.state_requests.push((StateRequest::Load, check_name(name)?));
+        Ok(())
+    });
+
+    // Cheats take effect right away, and are applied by the front-end after every tick
+    fn add_cheat(s: &mut Shared, address: INT, value: INT, kind: CheatKind) -> ScriptResult<()> {
+        let address = check(address, CHIP8_RAM, "address")?;
+        let value = check(value, 0x100, "byte")? as u8;
+        s.ram[address] = value;
+        s.cheats.borrow_mut().add(Cheat { address, value, kind });
+        Ok(())
+    }
+    api!("freeze", |s, address: INT, value: INT| -> ScriptResult<()> { add_cheat(&mut s, address, value, CheatKind::Freeze) });
+    api!("patch", |s, address: INT, value: INT| -> ScriptResult<()> { add_cheat(&mut s, address, value, CheatKind::Patch) });
+    api!("unfreeze", |s, address: INT| -> ScriptResult<()> {
+        s.cheats.borrow_mut().remove(check(address, CHIP8_RAM, "address")?);
+        Ok(())
+    });
+    api!("cheats", |s| -> Array {
+        let cheats: Array = s.cheats.borrow().cheats().iter().map(|cheat| Dynamic::from(cheat.to_string())).collect();
+        cheats
+    });
+    api!("save_cheats", |s| -> ScriptResult<()> {
+        s.cheats.borrow().save()?;
+        Ok(())
+    });
+
+    api!("search_start", |s| s.search = Some(RamSearch::new(&s.ram)));
+    fn search(s: &mut Shared, filter: SearchFilter) -> ScriptResult<INT> {
+        let Shared { search, ram, .. } = s;
+        match search {
+            Some(search) => Ok(search.filter(ram, filter) as INT),
+            None => Err("call search_start() before search()".into()),
+        }
+    }
+    api!("search", |s, filter: &str| -> ScriptResult<INT> { search(&mut s, filter.parse::<SearchFilter>()?) });
+    api!("search", |s, value: INT| -> ScriptResult<INT> {
+        let value = check(value, 0x100, "byte")? as u8;
+        search(&mut s, SearchFilter::Value(value))
+    });
+    api!("search_results", |s| -> Array {
+        let candidates = s.search.as_ref().map(|search| search.candidates()).unwrap_or(&[]);
+        candidates.iter().map(|&address| Dynamic::from(address as INT)).collect()
+    });
+}
+
+fn check(value: INT, limit: usize, what: &str) -> ScriptResult<usize> {
+    if value < 0 || value as usize >= limit {
+        return Err(format!("{} {:#x} is out of range", what, value).into());
+    }
+    Ok(value as usize)
+}
+
+// Save state names become part of a file name
+fn check_name(name: &str) -> ScriptResult<String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("bad save state name {:?}: use letters, digits, - and _", name).into());
+    }
+    Ok(name.to_string())
+}
+
+#[cfg(test)]
+#[path = "./scripting_test.rs"]
+mod tests;
@@ -0,0 +1,134 @@
+use super::*;
+use std::env;
+
+// V0 = 5, then stores its digits at 0x300 (Fx33) and loops at 0x206
+const ROM: [u8; 8] = [0x60, 0x05, 0xA3, 0x00, 0xF0, 0x33, 0x12, 0x06];
+
+fn script(source: &str) -> (Script, CPU) {
+    let mut cpu = CPU::with_seed(1);
+    cpu.load_program(&ROM);
+    let script = Script::from_source(source, "TEST", &env::temp_dir(), &mut cpu, cheats()).unwrap();
+    (script, cpu)
+}
+
+fn cheats() -> Rc<RefCell<CheatList>> {
+    Rc::new(RefCell::new(CheatList::default()))
+}
+
+fn run(script: &mut Script, cpu: &mut CPU, ticks: usize) {
+    for _ in 0..ticks {
+        script.tick(cpu, [false; 16]).unwrap();
+    }
+}
+
+#[test]
+fn test_no_script() {
+    let mut cpu = CPU::with_seed(1);
+    cpu.load_program(&ROM);
+    let mut script = Script::none();
+    run(&mut script, &mut cpu, 3);
+    assert_eq!(cpu.ram()[0x302], 5);
+    assert!(!script.frame(&mut cpu).unwrap());
+    assert_eq!(script.overlay(), "");
+}
+
+#[test]
+fn test_top_level_pokes_ram() {
+    let (_, cpu) = script("poke(0x400, 42);");
+    assert_eq!(cpu.ram()[0x400], 42);
+}
+
+#[test]
+fn test_on_instruction() {
+    // Changes the value before Fx33 stores it
+    let (mut script, mut cpu) = script("on_instruction(0x204, |address| set_v(0, 123));");
+    run(&mut script, &mut cpu, 3);
+    assert_eq!(cpu.ram()[0x300..0x303], [1, 2, 3]);
+}
+
+#[test]
+fn test_on_write() {
+    let (mut script, mut cpu) = script("on_write(0x302, |address, value| poke(0x400, value + 1));");
+    run(&mut script, &mut cpu, 3);
+    assert_eq!(cpu.ram()[0x400], 6);
+}
+
+#[test]
+fn test_on_frame_and_overlay() {
+    let (mut script, mut cpu) = script(r#"on_frame(|| overlay("frame " + frame_count()));"#);
+    assert!(script.frame(&mut cpu).unwrap());
+    assert_eq!(script.overlay(), "frame 0");
+    assert!(script.frame(&mut cpu).unwrap());
+    assert_eq!(script.overlay(), "frame 1");
+}
+
+#[test]
+fn test_pause() {
+    let (mut script, mut cpu) = script("pause();");
+    run(&mut script, &mut cpu, 3);
+    assert_eq!(cpu.registers().pc, 0x200);
+    assert_eq!(script.overlay(), "[PAUSED] ");
+}
+
+#[test]
+fn test_press() {
+    // Fx0A waits for a key; the script presses 7
+    let mut cpu = CPU::with_seed(1);
+    cpu.load_program(&[0xF1, 0x0A, 0x12, 0x02]);
+    let mut script = Script::from_source("press(7);", "TEST", &env::temp_dir(), &mut cpu, cheats()).unwrap();
+    run(&mut script, &mut cpu, 2);
+    assert_eq!(cpu.registers().v[1], 7);
+}
+
+#[test]
+fn test_save_and_load_state() {
+    let dir = env::temp_dir().join("yarc-scripting-test");
+    fs::create_dir_all(&dir).unwrap();
+    let mut cpu = CPU::with_seed(1);
+    cpu.load_program(&ROM);
+    let source = r#"
+        on_instruction(0x206, |address| {
+            if peek(0x500) == 0 { save_state("slot"); poke(0x500, 1); set_v(0, 99); }
+            else if peek(0x500) == 1 { poke(0x500, 2); load_state("slot"); }
+        });
+    "#;
+    let mut script = Script::from_source(source, "roms/TEST.ch8", &dir, &mut cpu, cheats()).unwrap();
+    run(&mut script, &mut cpu, 5);
+    assert!(dir.join("TEST-slot.state").exists());
+    // Saved when the first callback returned, and loading undid the second one's poke
+    assert_eq!(cpu.registers().v[0], 99);
+    assert_eq!(cpu.ram()[0x500], 1);
+}
+
+#[test]
+fn test_errors() {
+    let mut cpu = CPU::with_seed(1);
+    assert!(Script::from_source("let x = ;", "TEST", &env::temp_dir(), &mut cpu, cheats()).is_err());
+    assert!(Script::from_source("poke(0x1000, 1);", "TEST", &env::temp_dir(), &mut cpu, cheats()).is_err());
+    assert!(Script::from_source(r#"save_state("../x");"#, "TEST", &env::temp_dir(), &mut cpu, cheats()).is_err());
+
+    let mut script = Script::from_source("on_frame(|| v(16));", "TEST", &env::temp_dir(), &mut cpu, cheats()).unwrap();
+    assert!(script.frame(&mut cpu).is_err());
+}
+
+#[test]
+fn test_cheats_and_search() {
+    let mut cpu = CPU::with_seed(1);
+    cpu.load_program(&ROM);
+    let cheats = cheats();
+    let source = r#"
+        search_start();
+        on_frame(|| {
+            if frame_count() == 0 { search("changed"); search(5); }
+            if frame_count() == 1 { freeze(search_results()[0], 9); overlay(cheats()[0]); }
+        });
+    "#;
+    let mut script = Script::from_source(source, "TEST", &env::temp_dir(), &mut cpu, cheats.clone()).unwrap();
+    run(&mut script, &mut cpu, 3);
+    script.frame(&mut cpu).unwrap();
+    script.frame(&mut cpu).unwrap();
+    // Fx33 wrote 0, 0, 5 to 0x300; only 0x302 changed to 5
+    assert_eq!(cheats.borrow().cheats(), [Cheat { address: 0x302, value: 9, kind: CheatKind::Freeze }]);
+    assert_eq!(cpu.ram()[0x302], 9);
+    assert_eq!(script.overlay(), "0x302=09");
+}
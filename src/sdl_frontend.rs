@@ -0,0 +1,299 @@
+// SDL front-end: a window with sound, keyboard input and the display filters and effects,
+// or with --tile, a grid of games without the effects
+use std::iter;
+use std::path::Path;
+use std::thread; // for concurrency
+use std::time::Duration; // Duration is an enum that supports timing For timing of clock
+
+use crate::capture::Capture;
+use crate::config::{self, Config, Options};
+use yarc::cpu::CPU;
+use yarc::timing::Timing;
+use crate::display_filter::{DisplayFilter, FilterMode};
+use crate::drivers::*;
+use crate::frame_clock::FrameClock;
+use crate::grid::Grid;
+use crate::inspector::Inspector;
+use crate::palette::Palette;
+use crate::post_process::{PostProcessor, ShaderMode, ShaderSettings};
+use crate::session::Session;
+
+pub fn run(mut cpu: CPU, mut session: Session, options: &Options, config: &Config) -> Result<(), String> {
+    let sleep_duration = Duration::from_millis(1);
+
+    let sdl_context = sdl2::init()?;
+
+    let audio_driver = AudioDriver::new(&sdl_context);
+    let palette = Palette::from_config(config)?;
+    let platform = cpu.platform();
+    let mut graphic_driver = GraphicDriver::new(&sdl_context, palette, platform);
+    let mut input_driver = InputDriver::new(&sdl_context);
+    let screen = (platform.width(), platform.height());
+    let mut display_filter = DisplayFilter::new(config.get_or("display_filter", FilterMode::FrameBoundary)?, screen);
+    let mut shader = config.get_or("shader", ShaderMode::Off)?;
+    // Check the shader settings up front rather than when the shader is first switched on
+    let crt_settings = ShaderSettings::from_config(ShaderMode::Crt, config)?;
+    let lcd_settings = ShaderSettings::from_config(ShaderMode::Lcd, config)?;
+    let scale = graphic_driver.scale_factor();
+    let post_processor = |shader| match shader {
+        ShaderMode::Off => None,
+        ShaderMode::Crt => Some(PostProcessor::new(&crt_settings, screen.0, screen.1, scale)),
+        ShaderMode::Lcd => Some(PostProcessor::new(&lcd_settings, screen.0, screen.1, scale)),
+    };
+    graphic_driver.set_post_processor(post_processor(shader));
+
+    let mut capture = Capture::from_config(&options.rom, config, palette, screen)?;
+    let mut inspector = None;
+    if options.inspector {
+        let opened = Inspector::open(&sdl_context, palette, &cpu)?;
+        input_driver.set_game_window(graphic_driver.window_id());
+        inspector = Some(opened);
+    }
+
+    graphic_driver.set_status(&status(&display_filter, shader, &capture, &session));
+
+    let mut clock = FrameClock::new();
+    let mut vram_dirty = false;
+    let mut result = Ok(());
+
+    while let Ok(keypad) = input_driver.poll() {
+
+        let output = match session.run(&mut cpu, keypad) {
+            Ok(output) => output,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        };
+
+        if let Some(inspector) = inspector.as_mut() {
+            for event in input_driver.other_window_events() {
+                inspector.handle(&event, &mut cpu);
+            }
+        }
+
+        for hotkey in input_driver.hotkeys() {
+            match hotkey {
+                Hotkey::CycleDisplayFilter => display_filter.set_mode(display_filter.mode().next()),
+                Hotkey::CycleShader => {
+                    shader = shader.next();
+                    graphic_driver.set_post_processor(post_processor(shader));
+                }
+                Hotkey::Screenshot => match capture.screenshot(cpu.vram(), cpu.colours()) {
+                    Ok(paths) => paths.iter().for_each(|path| println!("Saved screenshot {}", path.display())),
+                    Err(e) => eprintln!("Screenshot failed: {}", e),
+                },
+                Hotkey::ToggleRecording => {
+                    let result = if capture.is_recording() {
+                        capture.stop_recording().map(|_| println!("Stopped recording"))
+                    } else {
+                        capture.start_recording().map(|path| println!("Recording to {}", path.display()))
+                    };
+                    if let Err(e) = result {
+                        eprintln!("Recording failed: {}", e);
+                    }
+                }
+                Hotkey::NextTile | Hotkey::ToggleBroadcast | Hotkey::Click(..) => continue,
+            }
+            graphic_driver.set_status(&status(&display_filter, shader, &capture, &session));
+            vram_dirty = true;
+        }
+
+        vram_dirty |= output.vram_changed;
+
+        // Only present once per 60 Hz frame, and only if the filtered picture changed since the last one.
+        // Filters that fade pixels out keep changing for a few frames after vram stops changing.
+        if clock.frame_due() {
+            match session.frame(&mut cpu) {
+                Ok(true) => graphic_driver.set_status(&status(&display_filter, shader, &capture, &session)),
+                Ok(false) => {}
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+            if let Err(e) = capture.record_frame(cpu.vram(), cpu.colours()) {
+                eprintln!("Recording failed: {}", e);
+                let _ = capture.stop_recording();
+                graphic_driver.set_status(&status(&display_filter, shader, &capture, &session));
+            }
+            if display_filter.apply(cpu.vram()) || vram_dirty {
+                graphic_driver.draw(display_filter.frame(), cpu.colours());
+                vram_dirty = false;
+            }
+            if let Some(inspector) = inspector.as_mut() {
+                inspector.frame(&cpu);
+            }
+        }
+
+        if output.beep {
+            audio_driver.start_beep();
+        } else {
+            audio_driver.stop_beep();
+        }
+
+        thread::sleep(sleep_duration);
+    }
+
+    let stopped = capture.stop_recording();
+    let profiled = session.finish();
+    result.and(stopped).and(profiled)
+}
+
+fn status(display_filter: &DisplayFilter, shader: ShaderMode, capture: &Capture, session: &Session) -> String {
+    let recording = if capture.is_recording() { " [REC]" } else { "" };
+    format!("filter: {}, shader: {}{}  {}", display_filter.mode(), shader, recording, session.status())
+}
+
+// A game in grid mode: a CPU with no session around it
+struct Game {
+    name: String,
+    cpu: CPU,
+    display_filter: DisplayFilter,
+    capture: Capture,
+    vram_dirty: bool,
+}
+
+// Grid mode: the main game and each --tile in one window. The keypad goes to the focused tile,
+// picked with Tab or a click, or to all of them after F4.
+pub fn run_grid(options: &Options, config: &Config) -> Result<(), String> {
+    let sleep_duration = Duration::from_millis(1);
+
+    let sdl_context = sdl2::init()?;
+
+    let audio_driver = AudioDriver::new(&sdl_context);
+    let palette = Palette::from_config(config)?;
+    // Always CHIP-8 for now; see config::platform
+    let platform = config::platform(options, config)?;
+    let screen = (platform.width(), platform.height());
+    let mut graphic_driver = GraphicDriver::new(&sdl_context, palette, platform);
+    let mut input_driver = InputDriver::new(&sdl_context);
+    let filter_mode = config.get_or("display_filter", FilterMode::FrameBoundary)?;
+
+    let main_game = (&options.rom, options.quirks, options.timing);
+    let tiles = options.tiles.iter().map(|tile| (&tile.rom, tile.quirks, tile.timing));
+    let font = config::font(options.font, options.font_file.as_deref(), config)?;
+    let mut games = Vec::new();
+    for (rom, quirks, timing) in iter::once(main_game).chain(tiles) {
+        let cartridge_driver = CartridgeDriver::new(rom);
+        let mut cpu = CPU::new();
+        cpu.set_platform(platform);
+        cpu.set_quirks(config::quirks(quirks, config)?);
+        cpu.set_timing(config::timing(timing, config)?);
+        cpu.set_font(&font)?;
+        cpu.load_program(&cartridge_driver.rom[..cartridge_driver.size]);
+        let name = Path::new(rom).file_name().map_or(rom.clone(), |name| name.to_string_lossy().into_owned());
+        let capture = Capture::from_config(rom, config, palette, screen)?;
+        games.push(Game { name, cpu, display_filter: DisplayFilter::new(filter_mode, screen), capture, vram_dirty: true });
+    }
+
+    let mut grid = Grid::new(games.len(), screen);
+    let (width, height) = graphic_driver.size();
+    let mut shown_status = grid_status(&grid, &games);
+    graphic_driver.set_status(&shown_status);
+
+    let mut clock = FrameClock::new();
+    let mut hotkey_pressed = false; // the focus outline or the filters may have changed
+    let mut result = Ok(());
+
+    while let Ok(keypad) = input_driver.poll() {
+        let mut beep = false;
+        for (index, game) in games.iter_mut().enumerate() {
+            let (vram_changed, beeping) = run_cpu(&mut game.cpu, grid.keypad(index, keypad));
+            game.vram_dirty |= vram_changed;
+            beep |= beeping && grid.has_input(index);
+        }
+
+        for hotkey in input_driver.hotkeys() {
+            let game = &mut games[grid.focused()];
+            match hotkey {
+                Hotkey::CycleDisplayFilter => {
+                    let mode = game.display_filter.mode().next();
+                    for game in games.iter_mut() {
+                        game.display_filter.set_mode(mode);
+                    }
+                }
+                Hotkey::CycleShader => {} // no effects on tiles
+                Hotkey::Screenshot => match game.capture.screenshot(game.cpu.vram(), game.cpu.colours()) {
+                    Ok(paths) => paths.iter().for_each(|path| println!("Saved screenshot {}", path.display())),
+                    Err(e) => eprintln!("Screenshot failed: {}", e),
+                },
+                Hotkey::ToggleRecording => {
+                    let result = if game.capture.is_recording() {
+                        game.capture.stop_recording().map(|_| println!("Stopped recording"))
+                    } else {
+                        game.capture.start_recording().map(|path| println!("Recording to {}", path.display()))
+                    };
+                    if let Err(e) = result {
+                        eprintln!("Recording failed: {}", e);
+                    }
+                }
+                Hotkey::NextTile => grid.focus_next(),
+                Hotkey::ToggleBroadcast => grid.toggle_broadcast(),
+                Hotkey::Click(x, y) => {
+                    if let Some(index) = grid.tile_at(x, y, width, height) {
+                        grid.focus(index);
+                    }
+                }
+            }
+            hotkey_pressed = true;
+        }
+
+        if clock.frame_due() {
+            let mut redraw = hotkey_pressed;
+            hotkey_pressed = false;
+            for game in games.iter_mut() {
+                game.cpu.vblank();
+                if let Err(e) = game.capture.record_frame(game.cpu.vram(), game.cpu.colours()) {
+                    eprintln!("Recording failed: {}", e);
+                    let _ = game.capture.stop_recording();
+                }
+                redraw |= game.display_filter.apply(game.cpu.vram()) || game.vram_dirty;
+                game.vram_dirty = false;
+            }
+            if redraw {
+                let tiles: Vec<_> = games.iter().enumerate().map(|(index, game)| (game.display_filter.frame(), grid.tile_rect(index, width, height))).collect();
+                graphic_driver.draw_tiles(&tiles, Some(grid.tile_rect(grid.focused(), width, height)));
+            }
+            let status = grid_status(&grid, &games);
+            if status != shown_status {
+                graphic_driver.set_status(&status);
+                shown_status = status;
+            }
+        }
+
+        if beep {
+            audio_driver.start_beep();
+        } else {
+            audio_driver.stop_beep();
+        }
+
+        thread::sleep(sleep_duration);
+    }
+
+    for game in games.iter_mut() {
+        result = result.and(game.capture.stop_recording());
+    }
+    result
+}
+
+// One tick, or with VIP timing every instruction up to the next vertical blank, like
+// Session::run. Returns whether the screen changed and whether it's beeping.
+fn run_cpu(cpu: &mut CPU, keypad: [bool; 16]) -> (bool, bool) {
+    let output = cpu.tick(keypad);
+    let (mut vram_changed, mut beep) = (output.vram_changed, output.beep);
+    while cpu.timing() == Timing::Vip && !cpu.waiting_for_vblank() && !cpu.waiting_for_key() {
+        let output = cpu.tick(keypad);
+        vram_changed |= output.vram_changed;
+        beep = output.beep;
+    }
+    (vram_changed, beep)
+}
+
+fn grid_status(grid: &Grid, games: &[Game]) -> String {
+    let game = &games[grid.focused()];
+    let keys = if grid.is_broadcasting() { " [ALL KEYS]" } else { "" };
+    let recording = if game.capture.is_recording() { " [REC]" } else { "" };
+    let waiting = if game.cpu.waiting_for_key() { " [KEY]" } else { "" };
+    format!("tile {}/{}: {}{}{}{}, filter: {}", grid.focused() + 1, games.len(), game.name, keys, recording, waiting, game.display_filter.mode())
+}
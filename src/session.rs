@@ -0,0 +1,146 @@
+// Everything that runs around cpu.tick(), so the front-ends only deal with keys and pixels:
+// the GDB stub and the RPC server decide whether to run at all, then netplay settles the keys,
+// the script's hooks run around the tick, which the tracer and profiler watch, and the cheats are
+// applied after it.
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::cheats::CheatList;
+use crate::config::{self, Config, Options};
+use crate::gdb_stub::GdbStub;
+use crate::netplay::{Machine, Netplay};
+use crate::profiler::Profiler;
+use crate::rpc::RpcServer;
+use crate::scripting::{Script, Tick};
+use crate::trace::Tracer;
+use yarc::cpu::CPU;
+use yarc::timing::Timing;
+
+pub struct Session {
+    netplay: Netplay,
+    gdb: GdbStub,
+    rpc: RpcServer,
+    script: Script,
+    tracer: Tracer,
+    profiler: Profiler,
+    cheats: Rc<RefCell<CheatList>>, // shared with the script, which can change them
+    status: String, // last status() seen by frame()
+    waiting_for_key: bool, // Fx0A was waiting after the last tick
+}
+
+impl Session {
+    // Sets up everything asked for on the command line, and the CPU to run the ROM on
+    pub fn start(options: &Options, config: &Config, rom: &[u8]) -> Result<(Session, CPU), String> {
+        let timing = config::timing(options.timing, config)?;
+        // The peers tick in step, but each would see its own vertical blanks
+        if timing == Timing::Vip && options.netplay.is_some() {
+            return Err(String::from("netplay needs fixed timing"));
+        }
+        let machine = Machine {
+            rom,
+            platform: config::platform(options, config)?,
+            quirks: config::quirks(options.quirks, config)?,
+            font: config::font(options.font, options.font_file.as_deref(), config)?,
+        };
+        // Netplay peers must have the same machine and the same random numbers
+        let netplay = Netplay::start(options.netplay.as_ref(), options.keys, &machine, config)?;
+        let mut cpu = match netplay.seed() {
+            Some(seed) => CPU::with_seed(seed),
+            None => CPU::new(),
+        };
+        cpu.set_platform(machine.platform);
+        cpu.set_quirks(machine.quirks);
+        cpu.set_timing(timing);
+        cpu.set_font(&machine.font)?;
+        cpu.load_program(rom);
+
+        let cheats = CheatList::load(rom, &options.cheats, config)?;
+        cheats.apply_patches(cpu.ram_mut());
+        let cheats = Rc::new(RefCell::new(cheats));
+
+        let script = Script::load(options.script.as_deref(), &options.rom, config, &mut cpu, cheats.clone())?;
+        let gdb = GdbStub::listen(options.gdb.as_deref())?;
+        // They all belong to the ROM the game started with, so load_rom has to leave it alone
+        let fixed_rom = if options.netplay.is_some() {
+            Some("netplay")
+        } else if !cheats.borrow().cheats().is_empty() {
+            Some("cheats")
+        } else if options.script.is_some() {
+            Some("--script")
+        } else if options.profile.is_some() {
+            Some("--profile")
+        } else {
+            None
+        };
+        let rpc = RpcServer::listen(options.rpc.as_deref(), fixed_rom)?;
+        let tracer = Tracer::open(options.trace.as_deref(), options.trace_filter.clone(), options.trace_format)?;
+        let profiler = Profiler::new(options.profile.as_deref(), rom, cpu.platform().load_address());
+        let session = Session { netplay, gdb, rpc, script, tracer, profiler, cheats, status: String::new(), waiting_for_key: false };
+        Ok((session, cpu))
+    }
+
+    pub fn tick(&mut self, cpu: &mut CPU, keypad: [bool; 16]) -> Result<Tick, String> {
+        // Both handle their requests even while the other one holds the game
+        let run = self.gdb.before_tick(cpu)? & self.rpc.before_tick(cpu)?;
+        if !run {
+            return Ok(Tick::default());
+        }
+        let keypad = self.netplay.sync(cpu, self.rpc.keypad(keypad))?;
+        self.tracer.before_tick(cpu);
+        self.profiler.before_tick(cpu);
+        let tick = self.script.tick(cpu, keypad)?;
+        self.tracer.after_tick(cpu, tick.ran)?;
+        self.profiler.after_tick(cpu, tick.ran);
+        self.cheats.borrow().apply_freezes(cpu.ram_mut());
+        self.gdb.after_tick(cpu)?;
+        self.rpc.after_tick(cpu);
+        self.waiting_for_key = cpu.waiting_for_key();
+        Ok(tick)
+    }
+
+    // What the front-ends call each time round their loop: one tick, or with VIP timing every
+    // instruction up to the next vertical blank, as the loop goes round slower than a VIP runs
+    pub fn run(&mut self, cpu: &mut CPU, keypad: [bool; 16]) -> Result<Tick, String> {
+        if cpu.timing() == Timing::Fixed {
+            return self.tick(cpu, keypad);
+        }
+        let mut ticks = Tick::default();
+        while !cpu.waiting_for_vblank() {
+            let tick = self.tick(cpu, keypad)?;
+            ticks.vram_changed |= tick.vram_changed;
+            ticks.beep = tick.beep;
+            ticks.ran |= tick.ran;
+            // Paused, or waiting for a key, which takes no cycles
+            if !tick.ran || cpu.waiting_for_key() {
+                break;
+            }
+        }
+        Ok(ticks)
+    }
+
+    // Call once per 60 Hz frame. Returns true if status() changed.
+    pub fn frame(&mut self, cpu: &mut CPU) -> Result<bool, String> {
+        cpu.vblank();
+        self.script.frame(cpu)?;
+        self.rpc.frame(cpu);
+        self.tracer.frame();
+        self.profiler.frame();
+        let status = self.status();
+        let changed = status != self.status;
+        self.status = status;
+        Ok(changed)
+    }
+
+    // Call when the game quits
+    pub fn finish(&self) -> Result<(), String> {
+        self.profiler.finish()
+    }
+
+    // For the front-end's status line
+    pub fn status(&self) -> String {
+        let gdb = if self.gdb.is_halted() { "[GDB] " } else { "" };
+        let rpc = if self.rpc.is_paused() { "[RPC] " } else { "" };
+        let key = if self.waiting_for_key { "[KEY] " } else { "" };
+        format!("{}{}{}{}", gdb, rpc, key, self.script.overlay())
+    }
+}
@@ -0,0 +1,295 @@
+// Terminal front-end: draws the screen with Unicode half blocks and reads keys from stdin.
+// It needs no SDL, so it also works over SSH.
+//
+// Every character cell shows two pixels stacked on top of each other: the upper half block
+// is coloured with the top pixel and the cell background with the bottom one.
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::capture::Capture;
+use crate::config::{Config, Options};
+use yarc::cpu::CPU;
+use yarc::framebuffer::Framebuffer;
+use yarc::platform::Colours;
+use crate::display_filter::{DisplayFilter, FilterMode};
+use crate::frame_clock::FrameClock;
+use crate::palette::Palette;
+use crate::session::Session;
+
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+const CTRL_C: u8 = 3;
+const ESCAPE: u8 = 0x1b;
+const DEFAULT_KEY_HOLD_MS: u64 = 150;
+
+// Terminals only report key presses, never releases. A key counts as held for
+// `hold` after it was last pressed; keyboard auto-repeat keeps refreshing that.
+pub struct TerminalInput {
+    hold: Duration,
+    released_at: [Option<Instant>; 16], // when each keypad key stops counting as held
+    escape: Vec<u8>, // escape sequence read so far, for function keys
+}
+
+// Emulator controls besides the keypad
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    Quit, // Ctrl-C
+    CycleDisplayFilter, // F2
+    ToggleRecording, // F11
+    Screenshot, // F12
+}
+
+impl TerminalInput {
+    pub fn new(hold: Duration) -> Self {
+        TerminalInput { hold, released_at: [None; 16], escape: Vec::new() }
+    }
+
+    // Handles one byte read from the terminal at time `now`
+    pub fn feed(&mut self, byte: u8, now: Instant) -> Option<Action> {
+        if !self.escape.is_empty() {
+            if self.escape.len() > 1 || byte == b'[' || byte == b'O' {
+                self.escape.push(byte);
+                return self.feed_escape();
+            }
+            // A lone ESC, e.g. from Alt+key or the Escape key: the byte after it is a key of its own
+            self.escape.clear();
+        }
+
+        match byte {
+            CTRL_C => Some(Action::Quit),
+            ESCAPE => {
+                self.escape.push(byte);
+                None
+            }
+            _ => {
+                if let Some(index) = keypad_index(byte) {
+                    self.released_at[index] = Some(now + self.hold);
+                }
+                None
+            }
+        }
+    }
+
+    // Function keys arrive as ESC O <letter> or ESC [ <digits> ~
+    fn feed_escape(&mut self) -> Option<Action> {
+        let sequence = &self.escape[1..];
+        let complete = match sequence {
+            [b'O', _] => true,
+            [b'[', .., last] => sequence.len() > 1 && (0x40..=0x7e).contains(last),
+            _ => false,
+        };
+        if !complete {
+            return None;
+        }
+
+        let action = match sequence {
+            b"OQ" | b"[12~" => Some(Action::CycleDisplayFilter),
+            b"[23~" => Some(Action::ToggleRecording),
+            b"[24~" => Some(Action::Screenshot),
+            _ => None,
+        };
+        self.escape.clear();
+        action
+    }
+
+    pub fn keypad(&self, now: Instant) -> [bool; 16] {
+        let mut keypad = [false; 16];
+        for (key, released_at) in keypad.iter_mut().zip(self.released_at.iter()) {
+            *key = released_at.is_some_and(|released_at| now < released_at);
+        }
+        keypad
+    }
+}
+
+// Same layout as the SDL front-end
+fn keypad_index(byte: u8) -> Option<usize> {
+    match byte.to_ascii_lowercase() {
+        b'1' => Some(0x1),
+        b'2' => Some(0x2),
+        b'3' => Some(0x3),
+        b'4' => Some(0xc),
+        b'q' => Some(0x4),
+        b'w' => Some(0x5),
+        b'e' => Some(0x6),
+        b'r' => Some(0xd),
+        b'a' => Some(0x7),
+        b's' => Some(0x8),
+        b'd' => Some(0x9),
+        b'f' => Some(0xe),
+        b'z' => Some(0xa),
+        b'x' => Some(0x0),
+        b'c' => Some(0xb),
+        b'v' => Some(0xf),
+        _ => None,
+    }
+}
+
+// ANSI escapes that draw `frame` from the top left corner of the terminal, in the CPU's `colours`
+// on platforms that have them, otherwise in the palette's.
+// Colours are only sent when they change, which keeps the output small over SSH.
+pub fn render(frame: &Framebuffer, palette: &Palette, colours: Option<&Colours>) -> String {
+    let color = |x: usize, y: usize, p: u8| match colours {
+        Some(colours) => colours.rgb(x, y, p),
+        None => palette.rgb(p),
+    };
+    let mut out = String::from("\x1b[H");
+    let width = frame.width();
+    for (row, rows) in frame.pixels().chunks(width * 2).enumerate() {
+        let mut current = None;
+        for x in 0..width {
+            let top = color(x, 2 * row, rows[x]);
+            let bottom = color(x, 2 * row + 1, rows.get(width + x).copied().unwrap_or(0));
+            if current != Some((top, bottom)) {
+                out.push_str(&format!("\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m",
+                        top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]));
+                current = Some((top, bottom));
+            }
+            out.push(UPPER_HALF_BLOCK);
+        }
+        out.push_str("\x1b[0m\r\n");
+    }
+    out
+}
+
+// Puts the terminal in raw mode (no echo, no line buffering) until dropped
+struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    fn enable() -> Result<Self, String> {
+        // Safe: termios is plain data that tcgetattr fills in
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                return Err(String::from("stdin is not a terminal"));
+            }
+            let original = termios;
+            libc::cfmakeraw(&mut termios);
+            termios.c_oflag |= libc::OPOST; // keep normal output processing
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) != 0 {
+                return Err(String::from("could not put the terminal in raw mode"));
+            }
+            Ok(RawMode { original })
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+// Reading stdin blocks, so it happens on its own thread
+fn spawn_stdin_reader() -> Receiver<u8> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = io::stdin();
+        let mut buffer = [0u8; 64];
+        while let Ok(count) = stdin.read(&mut buffer) {
+            if count == 0 || buffer[..count].iter().any(|&byte| sender.send(byte).is_err()) {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+pub fn run(mut cpu: CPU, mut session: Session, options: &Options, config: &Config) -> Result<(), String> {
+    let sleep_duration = Duration::from_millis(1);
+    let palette = Palette::from_config(config)?;
+    let hold = Duration::from_millis(config.get_or("terminal.key_hold_ms", DEFAULT_KEY_HOLD_MS)?);
+    let screen = (cpu.platform().width(), cpu.platform().height());
+    let mut display_filter = DisplayFilter::new(config.get_or("display_filter", FilterMode::FrameBoundary)?, screen);
+    let mut capture = Capture::from_config(&options.rom, config, palette, screen)?;
+
+    let raw_mode = RawMode::enable()?;
+    let keys = spawn_stdin_reader();
+    let mut input = TerminalInput::new(hold);
+    let mut clock = FrameClock::new();
+    let mut stdout = io::stdout();
+    let mut message = String::new(); // shown under the screen
+    let mut redraw = true;
+    let mut beeping = false;
+    let mut result = Ok(());
+
+    let _ = write!(stdout, "\x1b[2J\x1b[?25l"); // clear the terminal and hide the cursor
+
+    'running: loop {
+        let now = Instant::now();
+        let output = match session.run(&mut cpu, input.keypad(now)) {
+            Ok(output) => output,
+            Err(e) => {
+                result = Err(e);
+                break 'running;
+            }
+        };
+
+        while let Ok(byte) = keys.try_recv() {
+            let result = match input.feed(byte, now) {
+                None => continue,
+                Some(Action::Quit) => break 'running,
+                Some(Action::CycleDisplayFilter) => {
+                    display_filter.set_mode(display_filter.mode().next());
+                    Ok(String::new())
+                }
+                Some(Action::Screenshot) => capture.screenshot(cpu.vram(), cpu.colours())
+                        .map(|paths| format!("Saved screenshot {}", paths[0].display())),
+                Some(Action::ToggleRecording) => if capture.is_recording() {
+                    capture.stop_recording().map(|_| String::from("Stopped recording"))
+                } else {
+                    capture.start_recording().map(|path| format!("Recording to {}", path.display()))
+                },
+            };
+            message = result.unwrap_or_else(|e| e);
+            redraw = true;
+        }
+
+        redraw |= output.vram_changed;
+
+        if clock.frame_due() {
+            match session.frame(&mut cpu) {
+                Ok(changed) => redraw |= changed,
+                Err(e) => {
+                    result = Err(e);
+                    break 'running;
+                }
+            }
+            if let Err(e) = capture.record_frame(cpu.vram(), cpu.colours()) {
+                message = e;
+                let _ = capture.stop_recording();
+            }
+            if display_filter.apply(cpu.vram()) || redraw {
+                let recording = if capture.is_recording() { " [REC]" } else { "" };
+                let screen = render(display_filter.frame(), &palette, cpu.colours());
+                let _ = write!(stdout, "{}filter: {}{}  {}  {}\x1b[K", screen, display_filter.mode(), recording, session.status(), message);
+                let _ = stdout.flush();
+                redraw = false;
+            }
+        }
+
+        // The terminal bell is the closest thing to a beeper we have
+        if output.beep && !beeping {
+            let _ = write!(stdout, "\x07");
+        }
+        beeping = output.beep;
+
+        thread::sleep(sleep_duration);
+    }
+
+    let _ = write!(stdout, "\x1b[0m\x1b[?25h\r\n"); // reset colours and show the cursor again
+    let _ = stdout.flush();
+    drop(raw_mode);
+    let stopped = capture.stop_recording();
+    let profiled = session.finish();
+    result.and(stopped).and(profiled)
+}
+
+
+#[cfg(test)]
+#[path = "./terminal_test.rs"]
+mod tests;
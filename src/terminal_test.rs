@@ -0,0 +1,96 @@
+use super::*;
+use crate::{CHIP8_HEIGHT, CHIP8_WIDTH};
+
+const HOLD: Duration = Duration::from_millis(100);
+
+#[test]
+fn test_key_is_held_for_a_while() {
+    let mut input = TerminalInput::new(HOLD);
+    let start = Instant::now();
+    assert_eq!(input.feed(b'w', start), None);
+
+    assert!(input.keypad(start)[0x5]);
+    assert!(input.keypad(start + HOLD / 2)[0x5]);
+    assert!(!input.keypad(start + HOLD)[0x5]);
+    // Only that key is down
+    assert_eq!(input.keypad(start).iter().filter(|&&key| key).count(), 1);
+}
+
+#[test]
+fn test_repeat_extends_hold() {
+    let mut input = TerminalInput::new(HOLD);
+    let start = Instant::now();
+    input.feed(b'Q', start);
+    input.feed(b'q', start + HOLD / 2);
+    assert!(input.keypad(start + HOLD)[0x4]);
+}
+
+#[test]
+fn test_actions() {
+    let mut input = TerminalInput::new(HOLD);
+    let now = Instant::now();
+    assert_eq!(input.feed(CTRL_C, now), Some(Action::Quit));
+
+    // F12
+    let actions: Vec<_> = b"\x1b[24~".iter().map(|&byte| input.feed(byte, now)).collect();
+    assert_eq!(actions, vec![None, None, None, None, Some(Action::Screenshot)]);
+
+    // F2, then a normal key still works
+    assert_eq!(input.feed(ESCAPE, now), None);
+    assert_eq!(input.feed(b'O', now), None);
+    assert_eq!(input.feed(b'Q', now), Some(Action::CycleDisplayFilter));
+    input.feed(b'v', now);
+    assert!(input.keypad(now)[0xf]);
+
+    // Arrow keys are ignored and do not press anything
+    for &byte in b"\x1b[A" {
+        assert_eq!(input.feed(byte, now), None);
+    }
+    assert!(!input.keypad(now)[0xa]);
+}
+
+#[test]
+fn test_escape_then_key() {
+    let mut input = TerminalInput::new(HOLD);
+    let now = Instant::now();
+    // Alt+W, or Escape then W: W still presses its key
+    assert_eq!(input.feed(ESCAPE, now), None);
+    assert_eq!(input.feed(b'w', now), None);
+    assert!(input.keypad(now)[0x5]);
+
+    // Escape then Ctrl+C still quits, and Escape then F2 still cycles the filter
+    assert_eq!(input.feed(ESCAPE, now), None);
+    assert_eq!(input.feed(CTRL_C, now), Some(Action::Quit));
+    let actions: Vec<_> = b"\x1b\x1bOQ".iter().map(|&byte| input.feed(byte, now)).collect();
+    assert_eq!(actions, vec![None, None, None, Some(Action::CycleDisplayFilter)]);
+}
+
+#[test]
+fn test_render() {
+    let mut frame = Framebuffer::new(CHIP8_WIDTH, CHIP8_HEIGHT, 8);
+    frame.set(0, 0, 255);
+    frame.set(1, 1, 255);
+    let palette = Palette { off: [0, 0, 0], on: [1, 2, 3] };
+    let out = render(&frame, &palette, None);
+
+    assert!(out.starts_with("\x1b[H\x1b[38;2;1;2;3m\x1b[48;2;0;0;0m\u{2580}\x1b[38;2;0;0;0m\x1b[48;2;1;2;3m\u{2580}"));
+    assert_eq!(out.matches('\u{2580}').count(), CHIP8_WIDTH * 16);
+    assert_eq!(out.matches("\r\n").count(), 16);
+}
+
+// CHIP-8X pixels take their strip's colour over the background, whatever the palette
+#[test]
+fn test_render_colours() {
+    let mut frame = Framebuffer::new(CHIP8_WIDTH, CHIP8_HEIGHT, 8);
+    frame.set(0, 0, 255);
+    let out = render(&frame, &Palette::default(), Some(&Colours::default()));
+    assert!(out.starts_with("\x1b[H\x1b[38;2;255;0;0m\x1b[48;2;0;0;128m\u{2580}\x1b[38;2;0;0;128m"));
+}
+
+#[test]
+fn test_render_skips_repeated_colours() {
+    let frame = Framebuffer::new(CHIP8_WIDTH, CHIP8_HEIGHT, 8);
+    let out = render(&frame, &Palette::default(), None);
+    // One colour change at the start of each row
+    assert_eq!(out.matches("\x1b[38;2;").count(), 16);
+}
@@ -0,0 +1,77 @@
+// How fast the CPU runs. Fixed is YARC's simple model: one instruction per tick, with the timers
+// counting down every tick. Vip charges each instruction roughly what it took the COSMAC VIP's
+// interpreter, in 1802 machine cycles, gives the game a frame's worth of them between vertical
+// blanks, counts the timers down once a frame and lets Dxyn draw only once a frame, as games
+// written for the VIP expect.
+use std::fmt;
+use std::str::FromStr;
+
+// A VIP has 3668 machine cycles each 60 Hz frame, less about 1100 taken by the display and its
+// interrupt routine
+pub const FRAME_CYCLES: u32 = 2568;
+
+// Fetching and decoding, which every instruction pays on top of its own work
+const FETCH_CYCLES: u32 = 40;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Timing {
+    #[default]
+    Fixed,
+    Vip,
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Timing::Fixed => "fixed",
+            Timing::Vip => "vip",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Timing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fixed" => Ok(Timing::Fixed),
+            "vip" => Ok(Timing::Vip),
+            _ => Err(format!("unknown timing {} (expected fixed or vip)", s)),
+        }
+    }
+}
+
+// Machine cycles a VIP takes over `opcode`, given Vx as it was before it ran and whether it
+// skipped the next instruction
+pub(crate) fn vip_cycles(opcode: u16, vx: u8, skipped: bool) -> u32 {
+    let x = (opcode >> 8 & 0xF) as u32;
+    let n = (opcode & 0xF) as u32;
+    let skip = if skipped { 4 } else { 0 };
+    let work = match opcode >> 12 {
+        0x0 if opcode == 0x00E0 => 3038, // clears the 256 bytes of the screen one at a time
+        0x0 if opcode == 0x00EE => 10,
+        0x1 => 12,
+        0x2 => 26,
+        0x3 | 0x4 => 10 + skip,
+        0x5 | 0x9 => 18 + skip,
+        0x6 => 6,
+        0x7 => 10,
+        0x8 => 44,
+        0xA => 12,
+        0xB => 22,
+        0xC => 36,
+        // Each row is shifted into place, which takes longer off a byte boundary
+        0xD => 22 + n * if vx.is_multiple_of(8) { 46 } else { 56 },
+        0xE => 14 + skip,
+        0xF => match opcode & 0xFF {
+            0x1E | 0x29 | 0x30 => 16,
+            // Digits are found by counting down, so bigger ones take longer
+            0x33 => 80 + 16 * (vx / 100 + vx / 10 % 10 + vx % 10) as u32,
+            0x55 | 0x65 => 14 + 14 * (x + 1),
+            _ => 10,
+        },
+        _ => 0,
+    };
+    FETCH_CYCLES + work
+}
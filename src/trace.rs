@@ -0,0 +1,193 @@
+// Instruction trace, started with --trace FILE, to diff YARC against other emulators.
+//
+// Each executed instruction is one line. The yarc format gives its cycle number (counting every
+// instruction, traced or not), pc, opcode, disassembly and the registers it changed:
+//   42 204 7001 ADD V0, 0x01     V0=06
+// The full format gives the whole machine before each instruction instead, the way most other
+// CHIP-8 emulators log, so traces line up in a diff no matter which registers changed:
+//   PC:0204 OP:7001 I:0200 SP:0 DT:00 ST:00 V:05,00,00,00,00,00,00,00,00,00,00,00,00,00,00,00
+//
+// --trace-pc, --trace-ops and --trace-frames only trace some instructions.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use yarc::cpu::{Registers, CPU};
+use yarc::disasm::{disassemble, opcode_at};
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum TraceFormat {
+    #[default]
+    Yarc,
+    Full,
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "yarc" => Ok(TraceFormat::Yarc),
+            "full" => Ok(TraceFormat::Full),
+            _ => Err(format!("unknown trace format {} (expected yarc or full)", s)),
+        }
+    }
+}
+
+// Which instructions to trace; None traces all
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TraceFilter {
+    pub pcs: Option<RangeInclusive<u16>>,
+    pub classes: Option<u16>, // bit n: opcodes nXXX
+    pub frames: Option<RangeInclusive<u64>>,
+}
+
+impl TraceFilter {
+    fn matches(&self, pc: u16, opcode: u16, frame: u64) -> bool {
+        self.pcs.as_ref().is_none_or(|pcs| pcs.contains(&pc))
+            && self.classes.is_none_or(|classes| classes & (1 << (opcode >> 12)) != 0)
+            && self.frames.as_ref().is_none_or(|frames| frames.contains(&frame))
+    }
+}
+
+// --trace-pc: addresses in hex, e.g. 200-2FF
+pub fn parse_pcs(range: &str) -> Result<RangeInclusive<u16>, String> {
+    let (start, end) = split_range(range, 16).ok_or_else(|| format!("bad address range {} (expected e.g. 200-2FF)", range))?;
+    if start > 0xFFF || end > 0xFFF {
+        return Err(format!("address range {} is past the end of RAM", range));
+    }
+    Ok(start as u16..=end as u16)
+}
+
+// --trace-frames: 60 Hz frames, e.g. 600-660, or 600- for everything after
+pub fn parse_frames(range: &str) -> Result<RangeInclusive<u64>, String> {
+    let (start, end) = split_range(range, 10).ok_or_else(|| format!("bad frame range {} (expected e.g. 600-660)", range))?;
+    Ok(start..=end)
+}
+
+// --trace-ops: the first hex digit of the opcodes to trace, e.g. D or 1,2,B
+pub fn parse_classes(digits: &str) -> Result<u16, String> {
+    let mut classes = 0;
+    for digit in digits.chars().filter(|&c| c != ',') {
+        let class = digit.to_digit(16).ok_or_else(|| format!("bad opcode class {} in --trace-ops (expected hex digits)", digit))?;
+        classes |= 1 << class;
+    }
+    Ok(classes)
+}
+
+fn split_range(range: &str, radix: u32) -> Option<(u64, u64)> {
+    let mut parts = range.splitn(2, '-');
+    let start = u64::from_str_radix(parts.next()?.trim(), radix).ok()?;
+    let end = match parts.next().map(str::trim) {
+        None => start,
+        Some("") => u64::MAX,
+        Some(end) => u64::from_str_radix(end, radix).ok()?,
+    };
+    if end < start {
+        return None;
+    }
+    Some((start, end))
+}
+
+// Without --trace this does nothing
+pub struct Tracer {
+    trace: Option<Trace>,
+}
+
+struct Trace {
+    output: Box<dyn Write>,
+    filter: TraceFilter,
+    format: TraceFormat,
+    cycle: u64,
+    frame: u64,
+    before: Option<(Registers, u16)>, // machine and opcode before the instruction running now
+}
+
+impl Tracer {
+    pub fn none() -> Self {
+        Tracer { trace: None }
+    }
+
+    pub fn open(path: Option<&str>, filter: TraceFilter, format: TraceFormat) -> Result<Self, String> {
+        let path = match path {
+            Some(path) => path,
+            None => return Ok(Tracer::none()),
+        };
+        let file = File::create(path).map_err(|e| format!("Can't write the trace to {}: {}", path, e))?;
+        Ok(Tracer::to_writer(Box::new(BufWriter::new(file)), filter, format))
+    }
+
+    pub fn to_writer(output: Box<dyn Write>, filter: TraceFilter, format: TraceFormat) -> Self {
+        Tracer { trace: Some(Trace { output, filter, format, cycle: 0, frame: 0, before: None }) }
+    }
+
+    pub fn before_tick(&mut self, cpu: &CPU) {
+        if let Some(trace) = self.trace.as_mut() {
+            // Waiting for a key runs no instruction
+            trace.before = if cpu.waiting_for_key() {
+                None
+            } else {
+                let registers = cpu.registers();
+                Some((registers, opcode_at(cpu.ram(), registers.pc as usize)))
+            };
+        }
+    }
+
+    // `ran` is false if the tick didn't happen after all, e.g. because a script paused the game
+    pub fn after_tick(&mut self, cpu: &CPU, ran: bool) -> Result<(), String> {
+        let trace = match self.trace.as_mut() {
+            Some(trace) => trace,
+            None => return Ok(()),
+        };
+        let (before, opcode) = match trace.before.take() {
+            Some(before) if ran => before,
+            _ => return Ok(()),
+        };
+        let cycle = trace.cycle;
+        trace.cycle += 1;
+        if !trace.filter.matches(before.pc, opcode, trace.frame) {
+            return Ok(());
+        }
+        let line = match trace.format {
+            TraceFormat::Yarc => yarc_line(cycle, &before, opcode, &cpu.registers()),
+            TraceFormat::Full => full_line(&before, opcode),
+        };
+        writeln!(trace.output, "{}", line).map_err(|e| format!("Can't write the trace: {}", e))
+    }
+
+    pub fn frame(&mut self) {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.frame += 1;
+        }
+    }
+}
+
+fn yarc_line(cycle: u64, before: &Registers, opcode: u16, after: &Registers) -> String {
+    let mut changes = Vec::new();
+    for (register, (old, new)) in before.v.iter().zip(after.v.iter()).enumerate() {
+        if old != new {
+            changes.push(format!("V{:X}={:02X}", register, new));
+        }
+    }
+    if before.i != after.i {
+        changes.push(format!("I={:03X}", after.i));
+    }
+    if before.sp != after.sp {
+        changes.push(format!("SP={}", after.sp));
+    }
+    let line = format!("{} {:03X} {:04X} {:<16} {}", cycle, before.pc, opcode, disassemble(opcode), changes.join(" "));
+    line.trim_end().to_string()
+}
+
+fn full_line(before: &Registers, opcode: u16) -> String {
+    let v: Vec<String> = before.v.iter().map(|value| format!("{:02X}", value)).collect();
+    format!(
+        "PC:{:04X} OP:{:04X} I:{:04X} SP:{:X} DT:{:02X} ST:{:02X} V:{}",
+        before.pc, opcode, before.i, before.sp, before.delay_timer, before.sound_timer, v.join(",")
+    )
+}
+
+#[cfg(test)]
+#[path = "./trace_test.rs"]
+mod tests;
@@ -0,0 +1,112 @@
+use super::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// V0 = 5, I = 0x300, then V0 += 1 forever
+const ROM: [u8; 8] = [0x60, 0x05, 0xA3, 0x00, 0x70, 0x01, 0x12, 0x04];
+
+// Lets the test read what the tracer wrote
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// Runs `ticks` ticks, a frame every 2 ticks, and returns the trace lines
+fn trace(rom: &[u8], ticks: usize, filter: TraceFilter, format: TraceFormat) -> Vec<String> {
+    let output = Output::default();
+    let mut tracer = Tracer::to_writer(Box::new(output.clone()), filter, format);
+    let mut cpu = CPU::with_seed(1);
+    cpu.load_program(rom);
+    for tick in 0..ticks {
+        tracer.before_tick(&cpu);
+        cpu.tick([false; 16]);
+        tracer.after_tick(&cpu, true).unwrap();
+        if tick % 2 == 1 {
+            tracer.frame();
+        }
+    }
+    let text = String::from_utf8(output.0.borrow().clone()).unwrap();
+    text.lines().map(String::from).collect()
+}
+
+#[test]
+fn test_yarc_format() {
+    let lines = trace(&ROM, 5, TraceFilter::default(), TraceFormat::Yarc);
+    assert_eq!(lines, [
+        "0 200 6005 LD V0, 0x05      V0=05",
+        "1 202 A300 LD I, 0x300      I=300",
+        "2 204 7001 ADD V0, 0x01     V0=06",
+        "3 206 1204 JP 0x204",
+        "4 204 7001 ADD V0, 0x01     V0=07",
+    ]);
+}
+
+#[test]
+fn test_full_format() {
+    let lines = trace(&ROM, 3, TraceFilter::default(), TraceFormat::Full);
+    assert_eq!(lines[0], "PC:0200 OP:6005 I:0200 SP:0 DT:00 ST:00 V:00,00,00,00,00,00,00,00,00,00,00,00,00,00,00,00");
+    assert_eq!(lines[2], "PC:0204 OP:7001 I:0300 SP:0 DT:00 ST:00 V:05,00,00,00,00,00,00,00,00,00,00,00,00,00,00,00");
+}
+
+#[test]
+fn test_filters() {
+    // Cycle numbers count the instructions left out too
+    let filter = TraceFilter { pcs: Some(0x204..=0x205), ..TraceFilter::default() };
+    let lines = trace(&ROM, 6, filter, TraceFormat::Yarc);
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("2 204"));
+    assert!(lines[1].starts_with("4 204"));
+
+    let filter = TraceFilter { classes: Some(1 << 0x1 | 1 << 0xA), ..TraceFilter::default() };
+    let lines = trace(&ROM, 6, filter, TraceFormat::Yarc);
+    let opcodes: Vec<&str> = lines.iter().map(|line| line.split(' ').nth(2).unwrap()).collect();
+    assert_eq!(opcodes, ["A300", "1204", "1204"]);
+
+    // Frames 1 and 2 are ticks 2 to 5
+    let filter = TraceFilter { frames: Some(1..=2), ..TraceFilter::default() };
+    let lines = trace(&ROM, 8, filter, TraceFormat::Yarc);
+    let cycles: Vec<&str> = lines.iter().map(|line| line.split(' ').next().unwrap()).collect();
+    assert_eq!(cycles, ["2", "3", "4", "5"]);
+}
+
+#[test]
+fn test_untraced_ticks() {
+    let output = Output::default();
+    let mut tracer = Tracer::to_writer(Box::new(output.clone()), TraceFilter::default(), TraceFormat::Yarc);
+    let mut cpu = CPU::with_seed(1);
+    cpu.load_program(&[0xF0, 0x0A, 0x12, 0x00]); // wait for a key
+
+    tracer.before_tick(&cpu);
+    tracer.after_tick(&cpu, false).unwrap(); // e.g. paused by a script
+    tracer.before_tick(&cpu);
+    cpu.tick([false; 16]);
+    tracer.after_tick(&cpu, true).unwrap();
+    tracer.before_tick(&cpu);
+    cpu.tick([false; 16]); // still waiting
+    tracer.after_tick(&cpu, true).unwrap();
+
+    let text = String::from_utf8(output.0.borrow().clone()).unwrap();
+    assert_eq!(text, "0 200 F00A LD V0, K\n");
+}
+
+#[test]
+fn test_ranges() {
+    assert_eq!(parse_pcs("200-2FF"), Ok(0x200..=0x2FF));
+    assert_eq!(parse_pcs("2A4"), Ok(0x2A4..=0x2A4));
+    assert!(parse_pcs("200-1000").is_err());
+    assert!(parse_pcs("x-1").is_err());
+    assert_eq!(parse_frames("600-660"), Ok(600..=660));
+    assert_eq!(parse_frames("600-"), Ok(600..=u64::MAX));
+    assert!(parse_frames("660-600").is_err());
+    assert_eq!(parse_classes("1,2,B"), Ok(1 << 1 | 1 << 2 | 1 << 0xB));
+    assert_eq!(parse_classes("df"), Ok(1 << 0xD | 1 << 0xF));
+}
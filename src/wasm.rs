@@ -0,0 +1,83 @@
+// JavaScript bindings for the WebAssembly build. See web/ for a page that uses them.
+use wasm_bindgen::prelude::*;
+
+use crate::cpu::CPU;
+use crate::{CHIP8_WIDTH, CHIP8_HEIGHT};
+
+// Roughly what the desktop front-ends run per 60 Hz frame
+const DEFAULT_TICKS_PER_FRAME: u32 = 16;
+
+#[wasm_bindgen]
+pub struct Emulator {
+    cpu: CPU,
+    seed: u32,
+    keypad: [bool; 16],
+    beeping: bool,
+    ticks_per_frame: u32,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    // There is no OS to ask for randomness, so pass a seed in, e.g. from Math.random()
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: u32) -> Emulator {
+        Emulator {
+            cpu: CPU::with_seed(seed as u64),
+            seed,
+            keypad: [false; 16],
+            beeping: false,
+            ticks_per_frame: DEFAULT_TICKS_PER_FRAME,
+        }
+    }
+
+    // Resets the machine and loads a ROM at 0x200
+    pub fn load_rom(&mut self, rom: &[u8]) {
+        self.cpu = CPU::with_seed(self.seed as u64);
+        self.cpu.load_program(rom);
+        self.beeping = false;
+    }
+
+    // Bit n of `keys` set means key n of the keypad is held
+    pub fn set_keys(&mut self, keys: u16) {
+        for (index, key) in self.keypad.iter_mut().enumerate() {
+            *key = keys & (1 << index) != 0;
+        }
+    }
+
+    // Runs one 60 Hz frame worth of ticks. Returns true if the screen changed.
+    pub fn run_frame(&mut self) -> bool {
+        let mut vram_changed = false;
+        for _ in 0..self.ticks_per_frame {
+            let output = self.cpu.tick(self.keypad);
+            vram_changed |= output.vram_changed;
+            self.beeping = output.beep;
+        }
+        vram_changed
+    }
+
+    pub fn set_ticks_per_frame(&mut self, ticks: u32) {
+        self.ticks_per_frame = ticks;
+    }
+
+    // The screen as width() * height() bytes, row-major, 1 for a lit pixel and 0 otherwise
+    pub fn framebuffer(&self) -> Vec<u8> {
+        self.cpu.vram().pixels().to_vec()
+    }
+
+    pub fn is_beeping(&self) -> bool {
+        self.beeping
+    }
+
+    // True while the game waits for a key (Fx0A), so the page can say so
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.cpu.waiting_for_key()
+    }
+
+    pub fn width() -> u32 {
+        CHIP8_WIDTH as u32
+    }
+
+    pub fn height() -> u32 {
+        CHIP8_HEIGHT as u32
+    }
+}
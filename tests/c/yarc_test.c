@@ -0,0 +1,89 @@
+/* Exercises the C API in include/yarc.h. tests/ffi.rs builds and runs it, or by hand:
+ *   cargo build
+ *   cc -Iinclude tests/c/yarc_test.c -Ltarget/debug -lyarc -o yarc_test
+ *   LD_LIBRARY_PATH=target/debug ./yarc_test
+ */
+#include <stdio.h>
+#include <string.h>
+
+#include "yarc.h"
+
+#define CHECK(condition)                                                  \
+    do {                                                                  \
+        if (!(condition)) {                                               \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,        \
+                    __LINE__, #condition);                                \
+            return 1;                                                     \
+        }                                                                 \
+    } while (0)
+
+/* Draws the font sprite for 5 at (5, 5), then loops forever */
+static const uint8_t ROM[] = {
+    0x60, 0x05, /* LD V0, 5 */
+    0xF0, 0x29, /* LD F, V0 */
+    0xD0, 0x05, /* DRW V0, V0, 5 */
+    0x12, 0x06, /* JP 0x206 */
+};
+
+int main(void) {
+    uint8_t framebuffer[YARC_WIDTH * YARC_HEIGHT];
+    uint8_t state[YARC_STATE_SIZE];
+    uint8_t memory[4];
+    yarc_registers registers;
+
+    yarc_cpu *cpu = yarc_cpu_create(42);
+    CHECK(cpu != NULL);
+    CHECK(yarc_cpu_load_rom(cpu, ROM, sizeof ROM) == YARC_OK);
+    CHECK(yarc_cpu_load_rom(NULL, ROM, sizeof ROM) == YARC_ERROR);
+
+    /* LD V0, 5 */
+    CHECK(yarc_cpu_step(cpu) == 0);
+    CHECK(yarc_cpu_get_registers(cpu, &registers) == YARC_OK);
+    CHECK(registers.v[0] == 5);
+    CHECK(registers.pc == 0x202);
+
+    /* The rest of the ROM draws */
+    CHECK(yarc_cpu_run_frame(cpu, 10) & YARC_VRAM_CHANGED);
+    CHECK(yarc_cpu_framebuffer(cpu, framebuffer, sizeof framebuffer) == YARC_OK);
+    CHECK(yarc_cpu_framebuffer(cpu, framebuffer, 10) == YARC_ERROR);
+    CHECK(framebuffer[5 * YARC_WIDTH + 5] == 1);
+    CHECK(framebuffer[0] == 0);
+
+    /* Memory: the ROM is at 0x200 */
+    CHECK(yarc_cpu_read_memory(cpu, 0x200, memory, 2) == YARC_OK);
+    CHECK(memory[0] == 0x60 && memory[1] == 0x05);
+    CHECK(yarc_cpu_read_memory(cpu, YARC_RAM_SIZE - 1, memory, 2) == YARC_ERROR);
+    CHECK(yarc_cpu_read_memory(cpu, 0x200, memory, SIZE_MAX) == YARC_ERROR);
+    CHECK(yarc_cpu_write_memory(cpu, 0x200, memory, SIZE_MAX) == YARC_ERROR);
+    memory[0] = 0xAB;
+    CHECK(yarc_cpu_write_memory(cpu, 0x300, memory, 1) == YARC_OK);
+    CHECK(yarc_cpu_read_memory(cpu, 0x300, memory + 1, 1) == YARC_OK);
+    CHECK(memory[1] == 0xAB);
+
+    /* Registers, and the sound timer beeping */
+    registers.v[3] = 0x33;
+    registers.sound_timer = 10;
+    CHECK(yarc_cpu_set_registers(cpu, &registers) == YARC_OK);
+    CHECK(yarc_cpu_step(cpu) & YARC_BEEP);
+    CHECK(yarc_cpu_get_registers(cpu, &registers) == YARC_OK);
+    CHECK(registers.v[3] == 0x33);
+
+    /* Save, change things, load: back to how it was */
+    CHECK(yarc_cpu_save_state(cpu, state, sizeof state) == YARC_OK);
+    CHECK(yarc_cpu_load_rom(cpu, ROM, sizeof ROM) == YARC_OK);
+    CHECK(yarc_cpu_get_registers(cpu, &registers) == YARC_OK);
+    CHECK(registers.v[3] == 0);
+    CHECK(yarc_cpu_load_state(cpu, state, sizeof state) == YARC_OK);
+    CHECK(yarc_cpu_get_registers(cpu, &registers) == YARC_OK);
+    CHECK(registers.v[3] == 0x33);
+    CHECK(yarc_cpu_framebuffer(cpu, framebuffer, sizeof framebuffer) == YARC_OK);
+    CHECK(framebuffer[5 * YARC_WIDTH + 5] == 1);
+    CHECK(yarc_cpu_load_state(cpu, state, 100) == YARC_ERROR);
+
+    CHECK(yarc_cpu_set_keypad(cpu, 1 << 0xA) == YARC_OK);
+
+    yarc_cpu_destroy(cpu);
+    yarc_cpu_destroy(NULL);
+    printf("yarc_test: ok\n");
+    return 0;
+}
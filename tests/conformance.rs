@@ -0,0 +1,398 @@
+// Runs our own versions of the well-known CHIP-8 test ROMs (IBM logo, BC_test, corax+ opcode
+// test, flags test, quirks test, keypad test) headlessly under every quirks preset, and compares the final
+// screen against known-good hashes. The ROMs are assembled here so what each checks is readable.
+//
+// After a deliberate change in behaviour, run with --nocapture to see the new screens and hashes.
+use yarc::cpu::CPU;
+use yarc::framebuffer::Framebuffer;
+use yarc::quirks::QuirkPreset;
+
+const PROGRAM_START: u16 = 0x200;
+const SCRATCH: u16 = 0x400; // RAM the ROMs use for Fx33, Fx55 and Fx65, past their code
+
+// A ROM under construction, one opcode at a time
+struct Program {
+    opcodes: Vec<u16>,
+}
+
+impl Program {
+    fn new() -> Self {
+        Program { opcodes: Vec::new() }
+    }
+
+    // Address of the next opcode
+    fn here(&self) -> u16 {
+        PROGRAM_START + 2 * self.opcodes.len() as u16
+    }
+
+    fn push(&mut self, opcodes: &[u16]) -> &mut Self {
+        self.opcodes.extend_from_slice(opcodes);
+        self
+    }
+
+    // Ends the ROM with a jump to itself, so it sits there showing its results
+    fn rom(&mut self) -> Vec<u8> {
+        let here = self.here();
+        self.push(&[0x1000 | here]);
+        self.opcodes.iter().flat_map(|opcode| opcode.to_be_bytes()).collect()
+    }
+}
+
+// 64-bit FNV-1a over the pixels, row by row
+fn hash(vram: &Framebuffer) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &pixel in vram.pixels() {
+        hash ^= pixel as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn ascii_art(vram: &Framebuffer) -> String {
+    vram.rows()
+        .map(|row| row.iter().map(|&pixel| if pixel != 0 { '#' } else { '.' }).collect::<String>() + "\n")
+        .collect()
+}
+
+// Runs `rom` for `ticks` ticks under each preset, with `keys(tick)` held down, and checks the
+// screen against `expected`, in the order of QuirkPreset::ALL
+fn check(name: &str, rom: &[u8], ticks: usize, keys: impl Fn(usize) -> [bool; 16], expected: [u64; 4]) {
+    let mut failures = Vec::new();
+    for (preset, expected) in QuirkPreset::ALL.iter().zip(expected) {
+        let mut cpu = CPU::with_seed(1);
+        cpu.set_quirks(preset.quirks());
+        cpu.load_program(rom);
+        for tick in 0..ticks {
+            cpu.tick(keys(tick));
+        }
+        let actual = hash(cpu.vram());
+        if actual != expected {
+            println!("{} under {}: hash {:#018x}, expected {:#018x}\n{}", name, preset, actual, expected, ascii_art(cpu.vram()));
+            failures.push(preset.to_string());
+        }
+    }
+    assert!(failures.is_empty(), "{} differs under {}", name, failures.join(", "));
+}
+
+fn no_keys(_tick: usize) -> [bool; 16] {
+    [false; 16]
+}
+
+// Like the IBM logo ROM: only 00E0, Annn, 6xkk, 7xkk, Dxyn and 1nnn. The last letters cross the
+// right edge, where sprites wrap or clip depending on the preset.
+fn logo_rom() -> Vec<u8> {
+    let mut program = Program::new();
+    program.push(&[
+        0x00E0, // CLS
+        0x6008, // LD V0, 8
+        0x610C, // LD V1, 12
+    ]);
+    // 0x0A to 0x0F from the font, 11 pixels apart, the last one at x = 63
+    for digit in 0xA..=0xF {
+        program.push(&[
+            0xA000 | (digit * 5), // LD I, font digit
+            0xD015, // DRW V0, V1, 5
+            0x700B, // ADD V0, 11
+        ]);
+    }
+    // Then one at the bottom edge
+    program.push(&[0x6010, 0x611E, 0xA000, 0xD015]).rom()
+}
+
+// Prints V3 as three decimal digits at (VA, VB), then V7 as one digit; like the flags test's
+// output. Returns the subroutine's address.
+fn print_subroutine(program: &mut Program) -> u16 {
+    let print = program.here();
+    program.push(&[
+        0xA000 | SCRATCH, // LD I, SCRATCH
+        0xF333, // LD B, V3
+        0xF265, // LD V2, [I]
+        0xF029, // LD F, V0
+        0xDAB5, // DRW VA, VB, 5
+        0x7A05, // ADD VA, 5
+        0xF129, // LD F, V1
+        0xDAB5,
+        0x7A05,
+        0xF229, // LD F, V2
+        0xDAB5,
+        0x7A05,
+        0xF729, // LD F, V7
+        0xDAB5,
+        0x00EE, // RET
+    ]);
+    print
+}
+
+// Each arithmetic instruction, with the result and VF printed for ordinary operands, operands
+// carrying or borrowing, and VF itself as Vx
+fn flags_rom() -> Vec<u8> {
+    // (Vx, Vy, opcode), with Vy in V6 and Vx in V5 or VF
+    const CASES: [(u8, u8, u16); 15] = [
+        (0x0F, 0xF0, 0x8561), // OR, with VF set beforehand
+        (0x0F, 0xFF, 0x8562), // AND
+        (0x0F, 0xFF, 0x8563), // XOR
+        (0x0F, 0x0F, 0x8564), // ADD, no carry
+        (0xFF, 0x02, 0x8564), // ADD, carry
+        (0x0F, 0x01, 0x8565), // SUB, no borrow
+        (0x01, 0x0F, 0x8565), // SUB, borrow
+        (0x05, 0x0C, 0x8566), // SHR
+        (0x01, 0x0F, 0x8567), // SUBN, no borrow
+        (0x0F, 0x01, 0x8567), // SUBN, borrow
+        (0x81, 0x0C, 0x856E), // SHL
+        (0xFF, 0x02, 0x8F64), // ADD into VF
+        (0x01, 0x0F, 0x8F65), // SUB into VF
+        (0x05, 0x0C, 0x8F66), // SHR into VF
+        (0x81, 0x0C, 0x8F6E), // SHL into VF
+    ];
+    let mut program = Program::new();
+    program.push(&[0x0000]); // the jump over the subroutine, below
+    let print = print_subroutine(&mut program);
+    program.opcodes[0] = 0x1000 | program.here();
+
+    for (case, &(vx, vy, opcode)) in CASES.iter().enumerate() {
+        let x = (opcode >> 8) & 0xF;
+        let (column, row) = (case % 3, case / 3);
+        program.push(&[
+            0x6000 | (x << 8) | vx as u16, // LD Vx, vx
+            0x6600 | vy as u16, // LD V6, vy
+            0x6F01, // LD VF, 1, unless VF is Vx
+            0x6000 | (x << 8) | vx as u16,
+            opcode,
+            0x8300 | (x << 4), // LD V3, Vx
+            0x87F0, // LD V7, VF
+            0x6A00 | (21 * column) as u16, // LD VA, column
+            0x6B00 | (6 * row) as u16, // LD VB, row
+            0x2000 | print, // CALL print
+        ]);
+    }
+    program.rom()
+}
+
+// Like BC_test: runs the tests in turn and stops at the first that fails, showing its number as
+// the error code, or 00 once they have all passed
+fn bc_rom() -> Vec<u8> {
+    let mut program = Program::new();
+    program.push(&[0x0000]); // the jump over the subroutines, below
+    let nested = program.here();
+    program.push(&[
+        0x2000 | (nested + 6), // nested: CALL inner
+        0x7001, // ADD V0, 1
+        0x00EE, // RET
+        0x7001, // inner: ADD V0, 1
+        0x00EE, // RET
+    ]);
+    let error = program.here();
+    program.push(&[
+        0xA000 | SCRATCH, // error: LD I, SCRATCH
+        0xFE33, // LD B, VE
+        0xF265, // LD V2, [I]
+        0x6A00, // LD VA, 0
+        0x6B00, // LD VB, 0
+        0xF129, // LD F, V1
+        0xDAB5, // DRW VA, VB, 5
+        0x7A05, // ADD VA, 5
+        0xF229, // LD F, V2
+        0xDAB5, // DRW VA, VB, 5
+    ]);
+    let here = program.here();
+    program.push(&[0x1000 | here]);
+    program.opcodes[0] = 0x1000 | program.here();
+
+    // Each leaves its result in V0
+    let tests: [(&[u16], u8); 16] = [
+        (&[0x6005, 0x3006, 0x6001], 0x01), // 3xkk doesn't skip when different
+        (&[0x6005, 0x4005, 0x6001], 0x01), // 4xkk doesn't skip when equal
+        (&[0x6005, 0x6106, 0x5010, 0x6001], 0x01), // 5xy0 doesn't skip when different
+        (&[0x6005, 0x6105, 0x9010, 0x6001], 0x01), // 9xy0 doesn't skip when equal
+        (&[0x6F05, 0x60FF, 0x7002, 0x80F0], 0x05), // 7xkk leaves VF alone
+        (&[0x60FF, 0x6101, 0x8014, 0x80F0], 0x01), // 8xy4 carries
+        (&[0x6001, 0x6101, 0x8014, 0x80F0], 0x00), // 8xy4 doesn't
+        (&[0x6001, 0x6102, 0x8015, 0x80F0], 0x00), // 8xy5 borrows
+        (&[0x6005, 0x6105, 0x8015, 0x80F0], 0x01), // 8xy5 doesn't on equal operands
+        (&[0x6005, 0x6101, 0x8017, 0x80F0], 0x00), // 8xy7 borrows
+        (&[0x6005, 0x6105, 0x8017, 0x80F0], 0x01), // 8xy7 doesn't on equal operands
+        (&[0x6003, 0x8006, 0x80F0], 0x01), // 8xy6 shifts out the low bit
+        (&[0x6080, 0x800E, 0x80F0], 0x01), // 8xyE shifts out the high bit
+        (&[0x6000, 0x2000 | nested], 0x02), // 2nnn and 00EE, two deep
+        (&[0xA000 | SCRATCH, 0x6005, 0xF01E, 0x6042, 0xF055, 0xA005 | SCRATCH, 0xF065], 0x42), // Fx1E
+        (&[0x6000, 0xF029, 0xF065], 0xF0), // Fx29 points at the top of the 0
+    ];
+    for (number, &(code, expected)) in tests.iter().enumerate() {
+        program.push(code);
+        program.push(&[
+            0x6E01 + number as u16, // LD VE, test number
+            0x3000 | expected as u16, // SE V0, expected
+            0x1000 | error, // JP error
+        ]);
+    }
+    program.push(&[0x6E00, 0x1000 | error]).rom()
+}
+
+// Like corax+: each test draws a 0 in its place on the grid if it passed
+fn opcodes_rom() -> Vec<u8> {
+    const SUBROUTINE: u16 = PROGRAM_START + 2;
+    const MAIN: u16 = PROGRAM_START + 6;
+    // Each leaves its result in V0. 1nnn and Bnnn here are relative to the test's start, and
+    // come first so Bnnn's x, the high digit of nnn, is 2 like V0.
+    let tests: [(&[u16], u8); 24] = [
+        (&[0x6001, 0x1006, 0x6003], 0x01), // 1nnn jumps over 6003
+        (&[0x6002, 0x6202, 0xB006, 0x6003], 0x02), // Bnnn, with or without jump_vx
+        (&[0x6001, 0x3001, 0x6002], 0x01), // 3xkk skips
+        (&[0x6001, 0x4002, 0x6003], 0x01), // 4xkk skips
+        (&[0x6001, 0x6101, 0x5010, 0x6003], 0x01), // 5xy0 skips
+        (&[0x6001, 0x6102, 0x9010, 0x6003], 0x01), // 9xy0 skips
+        (&[0x60FF, 0x7002], 0x01), // 7xkk wraps
+        (&[0x6107, 0x8010], 0x07), // 8xy0
+        (&[0x6003, 0x6105, 0x8011], 0x07), // 8xy1
+        (&[0x6003, 0x6105, 0x8012], 0x01), // 8xy2
+        (&[0x6003, 0x6105, 0x8013], 0x06), // 8xy3
+        (&[0x60F0, 0x6120, 0x8014], 0x10), // 8xy4
+        (&[0x6010, 0x6101, 0x8015], 0x0F), // 8xy5
+        (&[0x6005, 0x8006], 0x02), // 8xy6, with Vy = Vx so shift_vy doesn't matter
+        (&[0x6001, 0x6110, 0x8017], 0x0F), // 8xy7
+        (&[0x6081, 0x800E], 0x02), // 8xyE
+        (&[0x6000, 0x2000 | SUBROUTINE], 0x2A), // 2nnn and 00EE
+        (&[0xC000], 0x00), // Cxkk masks
+        (&[0x609C, 0xA000 | SCRATCH, 0xF033, 0xA000 | SCRATCH, 0xF265], 0x01), // Fx33 hundreds
+        (&[0x609C, 0xA000 | SCRATCH, 0xF033, 0xA000 | SCRATCH, 0xF265, 0x8020], 0x06), // Fx33 units
+        (&[0x6011, 0x6122, 0xA000 | SCRATCH, 0xF155, 0x6000, 0x6100, 0xA000 | SCRATCH, 0xF165, 0x8010], 0x22), // Fx55, Fx65
+        (&[0x6077, 0xA010 | SCRATCH, 0xF055, 0xA000 | SCRATCH, 0x6010, 0xF01E, 0xF065], 0x77), // Fx1E
+        (&[0x6020, 0xF015, 0xF007], 0x1F), // Fx07 a tick after Fx15
+        (&[0x6038, 0x6118, 0xA000, 0xD015, 0xD015, 0x80F0], 0x01), // Dxyn collision
+    ];
+
+    let mut program = Program::new();
+    program.push(&[
+        0x1000 | MAIN, // JP main
+        0x602A, // subroutine: LD V0, 0x2A
+        0x00EE, // RET
+        0xA000, // main: LD I, font 0
+        0xD005, // DRW V0, V0, 5
+        0x00E0, // CLS
+        0x6D00, // LD VD, 0: the pass mark, font 0
+    ]);
+    for (number, &(code, expected)) in tests.iter().enumerate() {
+        let start = program.here();
+        for &opcode in code {
+            let relative = matches!(opcode >> 12, 0x1 | 0xB);
+            program.push(&[if relative { opcode + start } else { opcode }]);
+        }
+        program.push(&[
+            0x6A00 | (6 * (number % 10)) as u16, // LD VA, column
+            0x6B00 | (6 * (number / 10)) as u16, // LD VB, row
+            0xFD29, // LD F, VD
+            0x4000 | expected as u16, // SNE V0, expected
+            0xDAB5, // DRW VA, VB, 5
+        ]);
+    }
+    program.rom()
+}
+
+// Like the quirks test: one line per quirk, showing what the instruction did
+fn quirks_rom() -> Vec<u8> {
+    let mut program = Program::new();
+    program.push(&[0x0000]); // the jump over the subroutine, below
+    let print = print_subroutine(&mut program);
+    program.opcodes[0] = 0x1000 | program.here();
+    // Prints V3, and V7 as the line number
+    let show = |program: &mut Program, line: u16| {
+        program.push(&[0x6700 | line, 0x6A00, 0x6B00 | (6 * line), 0x2000 | print]);
+    };
+
+    // vf_reset: VF after OR is 5 or 0
+    program.push(&[0x6F05, 0x6001, 0x8011, 0x83F0]);
+    show(&mut program, 0);
+    // shift_vy: V0 = 1 >> 1 or 4 >> 1
+    program.push(&[0x6001, 0x6104, 0x8016, 0x8300]);
+    show(&mut program, 1);
+    // memory_increment: Fx65 after Fx55 reads back 7 or the 0 past it
+    program.push(&[0x6007, 0xA000 | SCRATCH, 0xF055, 0xF065, 0x8300]);
+    show(&mut program, 2);
+    // jump_vx: V0 = 0 and V2 = 4, so Bnnn lands on 6301 or 6302
+    let start = program.here();
+    program.push(&[
+        0x6000, // start: LD V0, 0
+        0x6204, // LD V2, 4
+        0xB000 | (start + 6), // JP V0, start + 6
+        0x6301, // start + 6: LD V3, 1
+        0x1000 | (start + 14), // JP start + 14
+        0x6302, // start + 10: LD V3, 2
+        0x0000, // never run
+    ]);
+    show(&mut program, 3);
+    // clip_sprites: an 8 over the bottom right corner
+    program.push(&[0x603C, 0x611D, 0x6208, 0xF229, 0xD015]).rom()
+}
+
+// Like the keypad test: Fx0A, then Ex9E and ExA1 waiting for keys, drawing each key as it comes
+fn keypad_rom() -> Vec<u8> {
+    let mut program = Program::new();
+    program.push(&[
+        0xF00A, // LD V0, K
+        0xF029, // LD F, V0
+        0x6A00, // LD VA, 0
+        0x6B00, // LD VB, 0
+        0xDAB5, // DRW VA, VB, 5
+        0x6105, // LD V1, 5
+    ]);
+    let wait = program.here();
+    program.push(&[
+        0xE19E, // wait: SKP V1
+        0x1000 | wait, // JP wait
+        0xF129, // LD F, V1
+        0x6A06, // LD VA, 6
+        0xDAB5,
+        0x6107, // LD V1, 7
+    ]);
+    let wait = program.here();
+    program.push(&[
+        0xE1A1, // wait: SKNP V1
+        0x1000 | (wait + 6), // JP pressed
+        0x1000 | wait, // JP wait
+        0xF129, // pressed: LD F, V1
+        0x6A0C, // LD VA, 12
+        0xDAB5,
+    ])
+    .rom()
+}
+
+#[test]
+fn logo() {
+    check("logo", &logo_rom(), 100, no_keys, [0x56d4009cf7e72f1c, 0xd73ff8e7b49ca7d8, 0xd73ff8e7b49ca7d8, 0x56d4009cf7e72f1c]);
+}
+
+#[test]
+fn flags() {
+    check("flags", &flags_rom(), 1000, no_keys, [0xef968800d56165a9, 0xae36ee85c9789924, 0xef968800d56165a9, 0x3e7d2009c1890c8c]);
+}
+
+#[test]
+fn bc() {
+    check("bc", &bc_rom(), 1000, no_keys, [0xe73b7b7f3e10824d, 0xe73b7b7f3e10824d, 0xe73b7b7f3e10824d, 0xe73b7b7f3e10824d]);
+}
+
+#[test]
+fn opcodes() {
+    check("opcodes", &opcodes_rom(), 1000, no_keys, [0x6ddbe69e80959a75, 0x6ddbe69e80959a75, 0x6ddbe69e80959a75, 0x6ddbe69e80959a75]);
+}
+
+#[test]
+fn quirks() {
+    check("quirks", &quirks_rom(), 500, no_keys, [0x3b15e4c0c641080f, 0x23b9f50d00bce0f5, 0x4b10772239aee95d, 0x26bcaf93d8daac53]);
+}
+
+#[test]
+fn keypad() {
+    // 3 for Fx0A, then 5, then 7, with nothing held in between
+    let keys = |tick: usize| {
+        let mut keypad = [false; 16];
+        match tick {
+            10..=19 => keypad[3] = true,
+            30..=39 => keypad[5] = true,
+            50..=59 => keypad[7] = true,
+            _ => {}
+        }
+        keypad
+    };
+    check("keypad", &keypad_rom(), 100, keys, [0x50c26416958526f5, 0x50c26416958526f5, 0x50c26416958526f5, 0x50c26416958526f5]);
+}
@@ -0,0 +1,79 @@
+// Runs random ROMs on CPU and on the reference interpreter in tests/reference, which must agree
+// after every tick. The fuzz targets in fuzz/ do the same with coverage-guided inputs; this
+// keeps a quick version of them in cargo test, along with inputs that once crashed CPU.
+mod reference;
+
+// xorshift64, so the ROMs are the same on every run
+fn random_bytes(seed: u64, length: usize) -> Vec<u8> {
+    let mut state = seed;
+    (0..length)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+#[test]
+fn random_roms() {
+    for seed in 1..=200 {
+        let data = random_bytes(seed, 9 + 256);
+        reference::run(&data);
+        reference::run_differential(&data);
+    }
+}
+
+// Random ROMs are mostly jumps away into empty RAM; these keep to arithmetic, skips and drawing
+#[test]
+fn random_instructions() {
+    for seed in 1..=200 {
+        let mut data = random_bytes(seed, 9 + 512);
+        for opcode in data[9..].chunks_mut(2) {
+            opcode[0] = match opcode[0] >> 4 {
+                0x1 | 0x2 | 0xB => 0x60 | (opcode[0] & 0xF), // LD instead of jumps
+                _ => opcode[0],
+            };
+        }
+        reference::run_differential(&data);
+    }
+}
+
+// Header: preset, then 4 keypad states
+const VIP: [u8; 9] = [1, 0, 0, 0, 0, 0, 0, 0, 0];
+
+fn rom(opcodes: &[u16]) -> Vec<u8> {
+    let mut data = VIP.to_vec();
+    data.extend(opcodes.iter().flat_map(|opcode| opcode.to_be_bytes()));
+    data
+}
+
+#[test]
+fn crashes() {
+    for opcodes in [
+        &[0xAFFF, 0x6063, 0xF033][..], // Fx33 past the end of RAM
+        &[0xAFFE, 0xFF55, 0xAFFE, 0xFF65], // Fx55 and Fx65 past the end of RAM
+        &[0xAFFC, 0xD00F], // Dxyn reading past the end of RAM
+        &[0x00EE], // RET with nothing on the stack
+        &[0x2200], // recursing forever
+        &[0x1FFE], // the last instruction in RAM
+        &[0x60FF, 0xBFFF], // Bnnn past the end of RAM
+        &[0x60FF, 0xF01E, 0x1202], // I growing past 0xFFFF
+        &[0x60FF, 0xE09E, 0xE0A1], // keys past F
+    ] {
+        let data = rom(opcodes);
+        reference::run(&data);
+        reference::run_differential(&data);
+    }
+    // Too big for RAM
+    reference::run(&[0; 0x1000]);
+}
+
+// VF as an operand, which the reference caught CPU getting wrong
+#[test]
+fn vf_operands() {
+    for opcode in [0x8F15, 0x81F5, 0x8F16, 0x8F17, 0x81F7, 0x8F1E, 0xDF15, 0xD1F5] {
+        reference::run_differential(&rom(&[0x6F85, 0x6113, 0xA000, opcode]));
+    }
+}
@@ -0,0 +1,54 @@
+// Builds tests/c/yarc_test.c against the yarc cdylib and runs it. Skipped without a C compiler.
+#![cfg(unix)]
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn c_api() {
+    // The test binary lives in target/<profile>/deps, next to the cdylib's directory
+    let exe = env::current_exe().unwrap();
+    let lib_dir = exe.parent().and_then(|deps| deps.parent()).unwrap().to_path_buf();
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let program = lib_dir.join("yarc_test");
+
+    // cargo test only builds the rlib, so build the cdylib too
+    let mut build = Command::new(env!("CARGO"));
+    build.arg("build").arg("--lib").arg("--manifest-path").arg(root.join("Cargo.toml"));
+    if lib_dir.ends_with("release") {
+        build.arg("--release");
+    }
+    assert!(build.status().unwrap().success(), "building the yarc library failed");
+
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(&compiler)
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(root.join("include"))
+        .arg(root.join("tests/c/yarc_test.c"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg("-lyarc")
+        .arg("-o")
+        .arg(&program)
+        .status();
+    let status = match status {
+        Ok(status) => status,
+        Err(_) => {
+            eprintln!("No C compiler ({}), skipping", compiler);
+            return;
+        }
+    };
+    assert!(status.success(), "compiling yarc_test.c failed");
+
+    let output = Command::new(&program)
+        .env("LD_LIBRARY_PATH", &lib_dir)
+        .env("DYLD_LIBRARY_PATH", &lib_dir)
+        .output()
+        .unwrap();
+    print!("{}", String::from_utf8_lossy(&output.stdout));
+    eprint!("{}", String::from_utf8_lossy(&output.stderr));
+    assert!(output.status.success());
+}
@@ -0,0 +1,321 @@
+// A reference CHIP-8 interpreter to check CPU against, written to be read rather than to be
+// fast: no lookup tables, no shared helpers between instructions, every address wrapped where
+// it is used. It follows YARC's documented behaviour, quirks included, on the CHIP-8 platform.
+//
+// Shared by tests/differential.rs and the fuzz targets in fuzz/.
+use yarc::cpu::{Registers, CPU};
+use yarc::quirks::{QuirkPreset, Quirks};
+use yarc::{CHIP8_HEIGHT, CHIP8_RAM, CHIP8_WIDTH};
+
+const TICKS: usize = 2000;
+
+pub struct Reference {
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+    sp: u8,
+    delay_timer: u8,
+    sound_timer: u8,
+    stack: [u16; 16],
+    ram: Vec<u8>,
+    vram: [[u8; CHIP8_WIDTH]; CHIP8_HEIGHT],
+    waiting: Option<usize>, // register Fx0A is waiting to put a key in
+    held: Option<usize>, // key Fx0A saw pressed, with key_release
+    quirks: Quirks,
+    font_address: u16, // small digits, with the big ones 80 bytes on
+}
+
+impl Reference {
+    // Starts from wherever `cpu` is, which has the font and the ROM loaded
+    pub fn new(cpu: &CPU) -> Self {
+        let registers = cpu.registers();
+        let mut vram = [[0; CHIP8_WIDTH]; CHIP8_HEIGHT];
+        for (row, cpu_row) in vram.iter_mut().zip(cpu.vram().rows()) {
+            row.copy_from_slice(cpu_row);
+        }
+        Reference {
+            v: registers.v,
+            i: registers.i,
+            pc: registers.pc,
+            sp: registers.sp,
+            delay_timer: registers.delay_timer,
+            sound_timer: registers.sound_timer,
+            stack: registers.stack,
+            ram: cpu.ram().to_vec(),
+            vram,
+            waiting: None,
+            held: None,
+            quirks: cpu.quirks(),
+            font_address: cpu.font().address,
+        }
+    }
+
+    fn read(&self, address: usize) -> u8 {
+        self.ram[address % CHIP8_RAM]
+    }
+
+    fn write(&mut self, address: usize, value: u8) {
+        self.ram[address % CHIP8_RAM] = value;
+    }
+
+    // One CPU tick. `random` is what Cxkk got from the CPU's random number generator, which the
+    // reference can't predict; only that Cxkk masks it with kk is checked.
+    pub fn tick(&mut self, keypad: [bool; 16], random: u8) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+        if let Some(register) = self.waiting {
+            let key = match self.held {
+                Some(key) if keypad[key] => None,
+                Some(key) => Some(key),
+                None => (0..16).find(|&key| keypad[key]),
+            };
+            if let Some(key) = key {
+                if self.quirks.key_release && self.held.is_none() {
+                    self.held = Some(key);
+                } else {
+                    self.v[register] = key as u8;
+                    self.waiting = None;
+                    self.held = None;
+                }
+            }
+            return;
+        }
+
+        let opcode = (self.read(self.pc as usize) as u16) << 8 | self.read(self.pc as usize + 1) as u16;
+        let x = (opcode >> 8 & 0xF) as usize;
+        let y = (opcode >> 4 & 0xF) as usize;
+        let n = (opcode & 0xF) as usize;
+        let kk = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+        let mut next = self.pc as usize + 2;
+        let skip = self.pc as usize + 4;
+
+        match opcode >> 12 {
+            0x0 if opcode == 0x00E0 => self.vram = [[0; CHIP8_WIDTH]; CHIP8_HEIGHT],
+            0x0 if opcode == 0x00EE => {
+                self.sp = if self.sp == 0 { 15 } else { (self.sp - 1) % 16 };
+                next = self.stack[self.sp as usize] as usize;
+            }
+            0x1 => next = nnn as usize,
+            0x2 => {
+                self.stack[self.sp as usize % 16] = (next % CHIP8_RAM) as u16;
+                self.sp = (self.sp + 1) % 16;
+                next = nnn as usize;
+            }
+            0x3 if self.v[x] == kk => next = skip,
+            0x4 if self.v[x] != kk => next = skip,
+            0x5 if n == 0 && self.v[x] == self.v[y] => next = skip,
+            0x6 => self.v[x] = kk,
+            0x7 => self.v[x] = self.v[x].wrapping_add(kk),
+            0x8 => self.arithmetic(x, y, n),
+            0x9 if n == 0 && self.v[x] != self.v[y] => next = skip,
+            0xA => self.i = nnn,
+            0xB if self.quirks.jump_vx => next = nnn as usize + self.v[x] as usize,
+            0xB => next = nnn as usize + self.v[0] as usize,
+            0xC => {
+                assert_eq!(random & !kk, 0, "Cxkk didn't mask with {:02X}", kk);
+                self.v[x] = random;
+            }
+            0xD => self.draw(x, y, n),
+            0xE if kk == 0x9E && keypad[self.v[x] as usize % 16] => next = skip,
+            0xE if kk == 0xA1 && !keypad[self.v[x] as usize % 16] => next = skip,
+            0xF => self.misc(x, kk),
+            _ => {}
+        }
+        self.pc = (next % CHIP8_RAM) as u16;
+    }
+
+    // 8xyN. Flags are written after the result, so with VF as Vx the flag is what's left.
+    fn arithmetic(&mut self, x: usize, y: usize, n: usize) {
+        let (vx, vy) = (self.v[x], self.v[y]);
+        match n {
+            0x0 => self.v[x] = vy,
+            0x1..=0x3 => {
+                self.v[x] = match n {
+                    0x1 => vx | vy,
+                    0x2 => vx & vy,
+                    _ => vx ^ vy,
+                };
+                if self.quirks.vf_reset {
+                    self.v[0xF] = 0;
+                }
+            }
+            0x4 => {
+                let sum = vx as u16 + vy as u16;
+                self.v[x] = (sum % 256) as u8;
+                self.v[0xF] = (sum > 255) as u8;
+            }
+            0x5 => {
+                self.v[x] = ((vx as i16 - vy as i16 + 256) % 256) as u8;
+                self.v[0xF] = (vx >= vy) as u8;
+            }
+            0x7 => {
+                self.v[x] = ((vy as i16 - vx as i16 + 256) % 256) as u8;
+                self.v[0xF] = (vy >= vx) as u8;
+            }
+            0x6 | 0xE => {
+                let value = if self.quirks.shift_vy { vy } else { vx };
+                if n == 0x6 {
+                    self.v[x] = value / 2;
+                    self.v[0xF] = value % 2;
+                } else {
+                    self.v[x] = (value as u16 * 2 % 256) as u8;
+                    self.v[0xF] = value / 128;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(&mut self, x: usize, y: usize, n: usize) {
+        let left = self.v[x] as usize % CHIP8_WIDTH;
+        let top = self.v[y] as usize % CHIP8_HEIGHT;
+        self.v[0xF] = 0;
+        for row in 0..n {
+            let sprite = self.read(self.i as usize + row);
+            for column in 0..8 {
+                let (mut px, mut py) = (left + column, top + row);
+                if px >= CHIP8_WIDTH || py >= CHIP8_HEIGHT {
+                    if self.quirks.clip_sprites {
+                        continue;
+                    }
+                    px %= CHIP8_WIDTH;
+                    py %= CHIP8_HEIGHT;
+                }
+                let pixel = sprite >> (7 - column) & 1;
+                if pixel == 1 && self.vram[py][px] == 1 {
+                    self.v[0xF] = 1;
+                }
+                self.vram[py][px] ^= pixel;
+            }
+        }
+    }
+
+    fn misc(&mut self, x: usize, kk: u8) {
+        match kk {
+            0x07 => self.v[x] = self.delay_timer,
+            0x0A => {
+                self.waiting = Some(x);
+                self.held = None;
+            }
+            0x15 => self.delay_timer = self.v[x],
+            0x18 => self.sound_timer = self.v[x],
+            0x1E => {
+                self.i = ((self.i as u32 + self.v[x] as u32) % 0x10000) as u16;
+                self.v[0xF] = (self.i > 0xF00) as u8;
+            }
+            0x29 => self.i = self.font_address + self.v[x] as u16 * 5,
+            0x30 => self.i = self.font_address + 80 + self.v[x] as u16 * 10,
+            0x33 => {
+                let value = self.v[x];
+                self.write(self.i as usize, value / 100);
+                self.write(self.i as usize + 1, value / 10 % 10);
+                self.write(self.i as usize + 2, value % 10);
+            }
+            0x55 | 0x65 => {
+                for register in 0..=x {
+                    let address = self.i as usize + register;
+                    if kk == 0x55 {
+                        self.write(address, self.v[register]);
+                    } else {
+                        self.v[register] = self.read(address);
+                    }
+                }
+                if self.quirks.memory_increment {
+                    self.i = ((self.i as u32 + x as u32 + 1) % 0x10000) as u16;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn registers(&self) -> Registers {
+        Registers {
+            v: self.v,
+            i: self.i,
+            pc: self.pc,
+            sp: self.sp,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            stack: self.stack,
+        }
+    }
+
+    // Panics describing the first difference from `cpu`
+    pub fn assert_matches(&self, cpu: &CPU, opcode: u16) {
+        assert_eq!(cpu.registers(), self.registers(), "registers differ after {:04X}", opcode);
+        assert_eq!(cpu.waiting_for_key(), self.waiting.is_some(), "key wait differs after {:04X}", opcode);
+        if cpu.ram()[..] != self.ram[..] {
+            let address = (0..CHIP8_RAM).find(|&address| cpu.ram()[address] != self.ram[address]).unwrap();
+            panic!("RAM differs at {:03X} after {:04X}", address, opcode);
+        }
+        let screen = cpu.vram().rows().eq(self.vram.iter().map(|row| &row[..]));
+        assert!(screen, "screen differs after {:04X}", opcode);
+    }
+}
+
+// What a fuzzer's bytes are turned into: a quirks preset, 4 keypad states held 16 ticks each
+// in turn, and the ROM
+pub struct Input<'a> {
+    pub quirks: Quirks,
+    pub keypads: [[bool; 16]; 4],
+    pub rom: &'a [u8],
+}
+
+impl<'a> Input<'a> {
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        if data.len() < 9 {
+            return None;
+        }
+        let preset = QuirkPreset::ALL[data[0] as usize % QuirkPreset::ALL.len()];
+        let mut keypads = [[false; 16]; 4];
+        for (keypad, bits) in keypads.iter_mut().zip(data[1..9].chunks(2)) {
+            let bits = u16::from_be_bytes([bits[0], bits[1]]);
+            for (key, pressed) in keypad.iter_mut().enumerate() {
+                *pressed = bits & (1 << key) != 0;
+            }
+        }
+        Some(Input { quirks: preset.quirks(), keypads, rom: &data[9..] })
+    }
+
+    pub fn keypad(&self, tick: usize) -> [bool; 16] {
+        self.keypads[tick / 16 % 4]
+    }
+
+    pub fn cpu(&self) -> CPU {
+        let mut cpu = CPU::with_seed(1);
+        cpu.set_quirks(self.quirks);
+        cpu.load_program(self.rom);
+        cpu
+    }
+}
+
+// Runs the ROM, checking only that nothing panics
+pub fn run(data: &[u8]) {
+    if let Some(input) = Input::parse(data) {
+        let mut cpu = input.cpu();
+        for tick in 0..TICKS {
+            cpu.tick(input.keypad(tick));
+        }
+    }
+}
+
+// Runs the ROM on both CPU and the reference, comparing them after every tick
+pub fn run_differential(data: &[u8]) {
+    let input = match Input::parse(data) {
+        Some(input) => input,
+        None => return,
+    };
+    let mut cpu = input.cpu();
+    let mut reference = Reference::new(&cpu);
+    for tick in 0..TICKS {
+        let pc = cpu.registers().pc as usize;
+        let opcode = (cpu.ram()[pc] as u16) << 8 | cpu.ram()[(pc + 1) % CHIP8_RAM] as u16;
+        let keypad = input.keypad(tick);
+        cpu.tick(keypad);
+        // Whatever Cxkk came up with, so both agree on it
+        let random = cpu.registers().v[(opcode >> 8 & 0xF) as usize];
+        reference.tick(keypad, random);
+        reference.assert_matches(&cpu, opcode);
+    }
+}
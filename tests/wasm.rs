@@ -0,0 +1,58 @@
+// Tests for the JavaScript bindings. They run under Node, no browser needed:
+// cargo test --target wasm32-unknown-unknown --no-default-features --test wasm
+// (with wasm-bindgen-test-runner set as the test runner)
+#![cfg(target_arch = "wasm32")]
+
+use wasm_bindgen_test::*;
+use yarc::wasm::Emulator;
+
+// Draws the 0 font sprite at (0, 0) and then loops forever:
+// 6000 (V0 = 0), F029 (I = sprite for V0), D005 (draw 5 rows), 1206 (jump to self)
+const DRAW_ZERO: [u8; 8] = [0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06];
+
+#[wasm_bindgen_test]
+fn framebuffer_size() {
+    let emulator = Emulator::new(1);
+    assert_eq!(emulator.framebuffer().len(), (Emulator::width() * Emulator::height()) as usize);
+}
+
+#[wasm_bindgen_test]
+fn run_frame_draws() {
+    let mut emulator = Emulator::new(1);
+    emulator.load_rom(&DRAW_ZERO);
+    assert!(emulator.run_frame());
+
+    let framebuffer = emulator.framebuffer();
+    let width = Emulator::width() as usize;
+    // Top row of the 0 glyph is 0xF0
+    assert_eq!(framebuffer[0..8], [1, 1, 1, 1, 0, 0, 0, 0]);
+    // Second row is 0x90
+    assert_eq!(framebuffer[width..width + 8], [1, 0, 0, 1, 0, 0, 0, 0]);
+
+    // Nothing else is drawn afterwards
+    assert!(!emulator.run_frame());
+}
+
+#[wasm_bindgen_test]
+fn beeping_and_keys() {
+    // 6A05 (VA = 5), EA9E (skip if key 5 down), 1202 (loop until it is),
+    // 6020 (V0 = 0x20), F018 (ST = V0), 120A (jump to self)
+    let rom = [0x6A, 0x05, 0xEA, 0x9E, 0x12, 0x02, 0x60, 0x20, 0xF0, 0x18, 0x12, 0x0A];
+    let mut emulator = Emulator::new(1);
+    emulator.load_rom(&rom);
+    emulator.run_frame();
+    assert!(!emulator.is_beeping());
+
+    emulator.set_keys(1 << 5);
+    emulator.run_frame();
+    assert!(emulator.is_beeping());
+}
+
+#[wasm_bindgen_test]
+fn load_rom_resets() {
+    let mut emulator = Emulator::new(1);
+    emulator.load_rom(&DRAW_ZERO);
+    emulator.run_frame();
+    emulator.load_rom(&[0x12, 0x00]);
+    assert!(emulator.framebuffer().iter().all(|&pixel| pixel == 0));
+}
@@ -0,0 +1,89 @@
+// Minimal browser front-end for the WebAssembly build.
+// Build the package into web/pkg first (see the README), then serve the repository root
+// over HTTP and open web/index.html.
+import init, { Emulator } from "./pkg/yarc.js";
+
+// Same layout as the desktop front-ends
+const KEYMAP = {
+  "1": 0x1, "2": 0x2, "3": 0x3, "4": 0xc,
+  "q": 0x4, "w": 0x5, "e": 0x6, "r": 0xd,
+  "a": 0x7, "s": 0x8, "d": 0x9, "f": 0xe,
+  "z": 0xa, "x": 0x0, "c": 0xb, "v": 0xf,
+};
+const ON = [0, 250, 0];
+
+await init();
+
+const emulator = new Emulator(Math.floor(Math.random() * 0xffffffff));
+const width = Emulator.width();
+const height = Emulator.height();
+
+const canvas = document.getElementById("screen");
+canvas.width = width;
+canvas.height = height;
+const context = canvas.getContext("2d");
+const image = context.createImageData(width, height);
+
+let keys = 0;
+document.addEventListener("keydown", (event) => {
+  const key = KEYMAP[event.key.toLowerCase()];
+  if (key !== undefined) keys |= 1 << key;
+});
+document.addEventListener("keyup", (event) => {
+  const key = KEYMAP[event.key.toLowerCase()];
+  if (key !== undefined) keys &= ~(1 << key);
+});
+
+// Square wave beeper, switched on and off by connecting it
+let audio = null;
+let oscillator = null;
+function beep(on) {
+  if (on && !oscillator) {
+    audio = audio || new AudioContext();
+    oscillator = audio.createOscillator();
+    oscillator.type = "square";
+    oscillator.frequency.value = 440;
+    const gain = audio.createGain();
+    gain.gain.value = 0.1;
+    oscillator.connect(gain).connect(audio.destination);
+    oscillator.start();
+  } else if (!on && oscillator) {
+    oscillator.stop();
+    oscillator = null;
+  }
+}
+
+function draw() {
+  const framebuffer = emulator.framebuffer();
+  for (let i = 0; i < framebuffer.length; i++) {
+    const lit = framebuffer[i] !== 0;
+    image.data[i * 4] = lit ? ON[0] : 0;
+    image.data[i * 4 + 1] = lit ? ON[1] : 0;
+    image.data[i * 4 + 2] = lit ? ON[2] : 0;
+    image.data[i * 4 + 3] = 255;
+  }
+  context.putImageData(image, 0, 0);
+}
+
+async function load(bytes) {
+  emulator.load_rom(new Uint8Array(bytes));
+  draw();
+}
+
+document.getElementById("rom").addEventListener("change", async (event) => {
+  load(await (await fetch(event.target.value)).arrayBuffer());
+});
+document.getElementById("file").addEventListener("change", async (event) => {
+  load(await event.target.files[0].arrayBuffer());
+});
+
+function frame() {
+  emulator.set_keys(keys);
+  if (emulator.run_frame()) draw();
+  beep(emulator.is_beeping());
+  document.getElementById("waiting").hidden = !emulator.is_waiting_for_key();
+  requestAnimationFrame(frame);
+}
+
+await load(await (await fetch(document.getElementById("rom").value)).arrayBuffer());
+requestAnimationFrame(frame);